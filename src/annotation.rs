@@ -1,18 +1,22 @@
-use std::{borrow::Cow, str::FromStr};
+use alloc::borrow::Cow;
+use core::str::FromStr;
 
 use winnow::{
     ModalParser, Parser,
-    ascii::alphanumeric1,
-    combinator::{alt, delimited, opt, preceded, repeat, separated, terminated},
-    error::InputError,
+    ascii::{alphanumeric1, digit0, digit1},
+    combinator::{alt, delimited, eof, opt, preceded, repeat, separated, terminated},
     token::{literal, one_of, take_till},
 };
 
 use crate::{
-    SmaliError,
-    field_ref::{FieldRef, parse_field_ref},
-    parse_string_lit,
-    signature::type_signature::{TypeSignature, parse_typesignature},
+    ParseFailure, ParseOptions, SmaliError, enter_nested,
+    field_ref::{FieldRef, parse_field_ref_with},
+    fit_bits,
+    method_ref::{MethodRef, parse_method_ref_with},
+    op::{write_float, write_signed_hex},
+    parse_int_lit, parse_string_lit,
+    prelude::*,
+    signature::type_signature::{TypeSignature, parse_typesignature, parse_typesignature_with},
     ws,
 };
 
@@ -35,8 +39,7 @@ impl AnnotationVisibility {
     }
 }
 
-pub fn parse_visibility<'a>() -> impl ModalParser<&'a str, AnnotationVisibility, InputError<&'a str>>
-{
+pub fn parse_visibility<'a>() -> impl ModalParser<&'a str, AnnotationVisibility, ParseFailure> {
     ws(alt((
         literal("build").value(AnnotationVisibility::Build),
         literal("runtime").value(AnnotationVisibility::Runtime),
@@ -52,10 +55,83 @@ pub enum AnnotationValue<'a> {
     Array(Vec<AnnotationValue<'a>>),
     SubAnnotation(Annotation<'a>),
     Enum(FieldRef<'a>),
+    /// A method reference such as `Lfoo;->bar()V`, as used by method handle metadata
+    Method(MethodRef<'a>),
+    /// An integral literal such as `-0x10`. The `postfix` gives its type: `t` for a
+    /// byte, `s` for a short, `L` for a long and none for an int.
+    Int {
+        value: i64,
+        postfix: Option<char>,
+    },
+    /// A floating point literal such as `3.14` or `1.0f`, with `f` marking a float and
+    /// none or `d` a double
+    Float {
+        value: f64,
+        postfix: Option<char>,
+    },
 
     Any(Cow<'a, str>),
 }
 
+impl AnnotationValue<'_> {
+    /// Copies any borrowed text, detaching the value from the parsed source
+    pub fn into_owned(self) -> AnnotationValue<'static> {
+        match self {
+            AnnotationValue::String(s) => AnnotationValue::String(Cow::Owned(s.into_owned())),
+            AnnotationValue::Array(a) => {
+                AnnotationValue::Array(a.into_iter().map(AnnotationValue::into_owned).collect())
+            }
+            AnnotationValue::SubAnnotation(a) => AnnotationValue::SubAnnotation(a.into_owned()),
+            AnnotationValue::Enum(f) => AnnotationValue::Enum(f.into_owned()),
+            AnnotationValue::Method(m) => AnnotationValue::Method(m.into_owned()),
+            AnnotationValue::Int { value, postfix } => AnnotationValue::Int { value, postfix },
+            AnnotationValue::Float { value, postfix } => AnnotationValue::Float { value, postfix },
+            AnnotationValue::Any(s) => AnnotationValue::Any(Cow::Owned(s.into_owned())),
+        }
+    }
+}
+
+impl<'a> AnnotationValue<'a> {
+    /// An integral literal such as `0x7f`, `-0x1L`, `0x1t` (byte) or `0x2s` (short)
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            AnnotationValue::Int { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            AnnotationValue::Any(s) if s.trim() == "true" => Some(true),
+            AnnotationValue::Any(s) if s.trim() == "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The contents of a string literal, still escaped as in the source
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AnnotationValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// A class literal such as `Ljava/lang/String;` or `[I`
+    pub fn as_type(&self) -> Option<TypeSignature<'_>> {
+        let AnnotationValue::Any(s) = self else {
+            return None;
+        };
+        parse_typesignature().parse(s.trim()).ok()
+    }
+
+    pub fn as_array(&self) -> Option<&[AnnotationValue<'a>]> {
+        match self {
+            AnnotationValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
 impl FromStr for AnnotationVisibility {
     type Err = SmaliError;
 
@@ -85,36 +161,111 @@ pub struct AnnotationElement<'a> {
 ///
 #[derive(Debug, PartialEq, Clone)]
 pub struct Annotation<'a> {
+    /// Required on `.annotation`. A `.subannotation` has no visibility of its own, so
+    /// parsed ones get `System` and the writer leaves it out.
     pub visibility: AnnotationVisibility,
     pub annotation_type: TypeSignature<'a>,
     pub elements: Vec<AnnotationElement<'a>>,
 }
 
-pub fn parse_annotation<'a>() -> impl ModalParser<&'a str, Annotation<'a>, InputError<&'a str>> {
-    delimited(
-        ws(alt((literal(".annotation"), literal(".subannotation")))),
+impl<'a> Annotation<'a> {
+    /// Copies any borrowed text, detaching the annotation from the parsed source
+    pub fn into_owned(self) -> Annotation<'static> {
+        Annotation {
+            visibility: self.visibility,
+            annotation_type: self.annotation_type.into_owned(),
+            elements: self
+                .elements
+                .into_iter()
+                .map(|e| AnnotationElement {
+                    name: Cow::Owned(e.name.into_owned()),
+                    value: e.value.into_owned(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Value of the element called `name`, if present
+    pub fn element(&self, name: &str) -> Option<&AnnotationValue<'a>> {
+        self.elements
+            .iter()
+            .find(|e| e.name == name)
+            .map(|e| &e.value)
+    }
+
+    /// Value of the element called `name` if it is a string
+    pub fn element_str(&self, name: &str) -> Option<&str> {
+        self.element(name)?.as_str()
+    }
+
+    /// Values of the element called `name` if it is an array,
+    /// e.g. the classes listed by `Ldalvik/annotation/MemberClasses;`
+    pub fn element_array(&self, name: &str) -> Option<&[AnnotationValue<'a>]> {
+        self.element(name)?.as_array()
+    }
+
+    /// Value of the element called `name` if it is an `.enum` constant
+    pub fn element_enum(&self, name: &str) -> Option<&FieldRef<'a>> {
+        match self.element(name)? {
+            AnnotationValue::Enum(f) => Some(f),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse_annotation<'a>() -> impl ModalParser<&'a str, Annotation<'a>, ParseFailure> {
+    parse_annotation_with(ParseOptions::default())
+}
+
+/// Parses an annotation with the nesting limit of `options`
+pub fn parse_annotation_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, Annotation<'a>, ParseFailure> {
+    annotation(options, 0)
+}
+
+/// An annotation whose values are `depth` levels into other annotation values
+fn annotation<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, Annotation<'a>, ParseFailure> {
+    let body = move || {
         (
-            opt(parse_visibility()),
-            parse_typesignature(),
-            repeat(0.., parse_annotation_element()),
+            parse_typesignature_with(options),
+            repeat(0.., annotation_element(options, depth)),
+        )
+    };
+    alt((
+        delimited(
+            ws(literal(".annotation")),
+            (parse_visibility(), body()),
+            ws(literal(".end annotation")),
         ),
-        ws(alt((
-            literal(".end annotation"),
-            literal(".end subannotation"),
-        ))),
-    )
-    .map(|(v, annotation_type, elements)| Annotation {
-        visibility: v.unwrap_or(AnnotationVisibility::System),
+        delimited(
+            ws(literal(".subannotation")),
+            body().map(|b| (AnnotationVisibility::System, b)),
+            ws(literal(".end subannotation")),
+        ),
+    ))
+    .map(|(visibility, (annotation_type, elements))| Annotation {
+        visibility,
         annotation_type,
         elements,
     })
 }
 
 pub fn parse_annotation_element<'a>()
--> impl ModalParser<&'a str, AnnotationElement<'a>, InputError<&'a str>> {
+-> impl ModalParser<&'a str, AnnotationElement<'a>, ParseFailure> {
+    annotation_element(ParseOptions::default(), 0)
+}
+
+fn annotation_element<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, AnnotationElement<'a>, ParseFailure> {
     (
         terminated(ws(alphanumeric1), ws(one_of('='))),
-        parse_annotation_value(),
+        nested_annotation_value(options, depth),
     )
         .map(|(name, value)| AnnotationElement {
             name: name.into(),
@@ -122,26 +273,102 @@ pub fn parse_annotation_element<'a>()
         })
 }
 
-pub fn parse_annotation_value<'a>()
--> impl ModalParser<&'a str, AnnotationValue<'a>, InputError<&'a str>> {
+pub fn parse_annotation_value<'a>() -> impl ModalParser<&'a str, AnnotationValue<'a>, ParseFailure>
+{
+    nested_annotation_value(ParseOptions::default(), 0)
+}
+
+/// An annotation value `depth` levels into another, which fails once that is deeper
+/// than [`ParseOptions::max_depth`]
+fn nested_annotation_value<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, AnnotationValue<'a>, ParseFailure> {
+    move |input: &mut &'a str| {
+        enter_nested(options, depth)?;
+        annotation_value(options, depth).parse_next(input)
+    }
+}
+
+fn annotation_value<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, AnnotationValue<'a>, ParseFailure> {
     alt((
-        (|input: &mut &'a str| parse_annotation().parse_next(input))
+        (move |input: &mut &'a str| annotation(options, depth + 1).parse_next(input))
             .map(AnnotationValue::SubAnnotation),
         delimited(
             ws(one_of('{')),
-            separated(
-                0..,
-                |input: &mut &'a str| parse_annotation_value().parse_next(input),
-                ws(one_of(',')),
+            terminated(
+                separated(
+                    0..,
+                    move |input: &mut &'a str| {
+                        nested_annotation_value(options, depth + 1).parse_next(input)
+                    },
+                    ws(one_of(',')),
+                ),
+                // some framework annotations end their arrays with a trailing comma
+                opt(ws(one_of(','))),
             ),
             ws(one_of('}')),
         )
         .map(AnnotationValue::Array),
         parse_string_lit().map(|s: &'a str| AnnotationValue::String(s.into())),
-        preceded(ws(literal(".enum")), parse_field_ref()).map(AnnotationValue::Enum),
+        // built when reached, keeping them out of the frame every nesting level takes
+        move |input: &mut &'a str| {
+            alt((
+                preceded(ws(literal(".enum")), parse_field_ref_with(options))
+                    .map(AnnotationValue::Enum),
+                ws(parse_method_ref_with(options)).map(AnnotationValue::Method),
+            ))
+            .parse_next(input)
+        },
         // TODO: This can be any type, needed fixes
-        take_till(0.., |c| c == ',' || c == '}' || c == '\n')
-            .map(|s: &'a str| AnnotationValue::Any(s.into())),
+        take_till(1.., |c| c == ',' || c == '}' || c == '\n' || c == '\r').map(|s: &'a str| {
+            parse_number()
+                .parse(s.trim())
+                .unwrap_or(AnnotationValue::Any(s.into()))
+        }),
+    ))
+}
+
+/// Parses a numeric literal the way baksmali writes them in annotations
+fn parse_number<'a>() -> impl ModalParser<&'a str, AnnotationValue<'a>, ParseFailure> {
+    let exponent = || (one_of(['e', 'E']), opt(one_of(['+', '-'])), digit1);
+    alt((
+        // a float starts like an int, so the int has to span the whole literal
+        (
+            parse_int_lit::<i64>().with_taken(),
+            opt(one_of(['t', 's'])),
+            eof,
+        )
+            .try_map(|((value, lit), postfix, _)| {
+                let postfix = postfix.or(lit.ends_with('L').then_some('L'));
+                let bits = match postfix {
+                    Some('t') => 8,
+                    Some('s') => 16,
+                    Some(_) => 64,
+                    None => 32,
+                };
+                fit_bits(value, bits).map(|value| AnnotationValue::Int { value, postfix })
+            }),
+        (
+            (
+                opt(one_of('-')),
+                alt((
+                    literal("Infinity"),
+                    literal("NaN"),
+                    (digit1, one_of('.'), digit0, opt(exponent())).take(),
+                    (digit1, exponent()).take(),
+                )),
+            )
+                .take(),
+            opt(one_of(['f', 'd'])),
+        )
+            .try_map(|(lit, postfix): (&str, _)| {
+                lit.parse::<f64>()
+                    .map(|value| AnnotationValue::Float { value, postfix })
+            }),
     ))
 }
 
@@ -185,16 +412,18 @@ pub fn write_annotation_value(
     inset: &str,
 ) {
     match &i {
+        AnnotationValue::Array(a) if a.is_empty() => out.push_str("{}\n"),
         AnnotationValue::Array(a) => {
             out.push_str("{\n");
-            let mut c = 0;
-            for v in a {
+            for (c, v) in a.iter().enumerate() {
                 out.push_str(indent);
                 out.push_str(inset);
                 out.push_str(inset);
-                write_annotation_value(out, v, indented, indent, inset);
-                c += 1;
-                if c < a.len() {
+                // every value ends its own line, the separating comma goes before that break
+                let mut value = String::new();
+                write_annotation_value(&mut value, v, indented, indent, inset);
+                out.push_str(value.trim_end_matches('\n'));
+                if c + 1 < a.len() {
                     out.push(',');
                 }
                 out.push('\n');
@@ -212,9 +441,23 @@ pub fn write_annotation_value(
                 f.param.ts
             ));
         }
+        AnnotationValue::Method(m) => {
+            out.push_str(&format!("{m}\n"));
+        }
         AnnotationValue::String(s) => {
             out.push_str(&format!("\"{s}\"\n"));
         }
+        AnnotationValue::Int { value, postfix } => {
+            // writing to a `String` can't fail
+            let _ = write_signed_hex(out, *value);
+            out.extend(postfix);
+            out.push('\n');
+        }
+        AnnotationValue::Float { value, postfix } => {
+            let _ = write_float(out, *value);
+            out.extend(postfix);
+            out.push('\n');
+        }
         AnnotationValue::Any(s) => {
             out.push_str(&format!("{s}\n"));
         }
@@ -234,9 +477,7 @@ mod tests {
             AnnotationValue::Array(a) => {
                 assert_eq!(a[0], AnnotationValue::String(Cow::Borrowed("a,")));
             }
-            _ => {
-                println!("{a:?}");
-            }
+            other => panic!("expected an array, got {other:?}"),
         }
 
         let a = parse_annotation_element().parse_next(&mut " value = .enum Ljava/lang/annotation/RetentionPolicy;->SOURCE:Ljava/lang/annotation/RetentionPolicy;\n").unwrap();
@@ -248,9 +489,7 @@ mod tests {
                     "Ljava/lang/annotation/RetentionPolicy;"
                 );
             }
-            _ => {
-                println!("{a:?}");
-            }
+            other => panic!("expected an enum, got {other:?}"),
         }
     }
 
@@ -264,6 +503,356 @@ mod tests {
         Lokhttp3/OkHttpClient$Companion;
     }
 .end annotation";
-        println!("{:?}", parse_annotation().parse(input).unwrap());
+        let a = parse_annotation().parse(input).unwrap();
+        assert_eq!(a.elements.len(), 1);
+        assert_eq!(a.element_array("value").map(<[_]>::len), Some(2));
+    }
+
+    #[test]
+    fn test_annotation_array_of_subannotations() {
+        use super::*;
+        use winnow::Parser;
+
+        let input = r#".annotation runtime Lcom/example/Routes;
+    value = {
+        .subannotation Lcom/example/Route;
+            path = "/a"
+            methods = {
+                "GET",
+                "POST",
+            }
+        .end subannotation,
+        .subannotation Lcom/example/Route;
+            path = "/b"
+            methods = {}
+        .end subannotation,
+    }
+.end annotation"#;
+        let a = parse_annotation().parse(input).unwrap();
+        let AnnotationValue::Array(routes) = &a.elements[0].value else {
+            panic!("{a:?}");
+        };
+        assert_eq!(routes.len(), 2);
+        let AnnotationValue::SubAnnotation(first) = &routes[0] else {
+            panic!("{a:?}");
+        };
+        assert_eq!(
+            first.elements[1].value,
+            AnnotationValue::Array(vec![
+                AnnotationValue::String(Cow::Borrowed("GET")),
+                AnnotationValue::String(Cow::Borrowed("POST")),
+            ])
+        );
+        let AnnotationValue::SubAnnotation(second) = &routes[1] else {
+            panic!("{a:?}");
+        };
+        assert_eq!(second.elements[1].value, AnnotationValue::Array(vec![]));
+
+        let written = write_annotation(&a, false, false);
+        assert!(written.contains(".end subannotation,\n"), "{written}");
+        assert!(written.contains("\"GET\",\n"), "{written}");
+        assert_eq!(parse_annotation().parse(written.trim_end()), Ok(a));
+    }
+
+    #[test]
+    fn test_annotation_element_getters() {
+        use super::*;
+        use winnow::Parser;
+
+        let a = parse_annotation()
+            .parse(
+                r#".annotation runtime Lcom/example/Meta;
+    name = "meta"
+    policy = .enum Ljava/lang/annotation/RetentionPolicy;->RUNTIME:Ljava/lang/annotation/RetentionPolicy;
+    value = {
+        Lokhttp3/OkHttpClient$Builder;,
+        Lokhttp3/OkHttpClient$Companion;
+    }
+.end annotation"#,
+            )
+            .unwrap();
+        assert_eq!(a.element_str("name"), Some("meta"));
+        assert_eq!(a.element_enum("policy").unwrap().param.ident, "RUNTIME");
+        assert_eq!(
+            a.element_array("value"),
+            Some(
+                &[
+                    AnnotationValue::Any(Cow::Borrowed("Lokhttp3/OkHttpClient$Builder;")),
+                    AnnotationValue::Any(Cow::Borrowed("Lokhttp3/OkHttpClient$Companion;")),
+                ][..]
+            )
+        );
+        assert_eq!(a.element_str("value"), None);
+        assert!(a.element("missing").is_none());
+    }
+
+    #[test]
+    fn test_method_ref_value() {
+        use super::*;
+        use winnow::Parser;
+
+        let input = r#".annotation system Ldalvik/annotation/Bootstrap;
+    method = Ljava/lang/invoke/LambdaMetafactory;->metafactory(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;
+    handles = {
+        Lcom/example/Main;->lambda$run$0()V,
+        Lcom/example/Main;->count:I
+    }
+.end annotation"#;
+        let a = parse_annotation().parse(input).unwrap();
+        let Some(AnnotationValue::Method(m)) = a.element("method") else {
+            panic!("{a:?}");
+        };
+        assert_eq!(m.param.ident, "metafactory");
+        let handles = a.element_array("handles").unwrap();
+        assert!(
+            matches!(&handles[0], AnnotationValue::Method(m) if m.param.ident == "lambda$run$0")
+        );
+        // field references aren't method references
+        assert!(matches!(&handles[1], AnnotationValue::Any(_)));
+
+        let written = write_annotation(&a, false, false);
+        assert!(
+            written.contains("    method = Ljava/lang/invoke/LambdaMetafactory;->metafactory(")
+        );
+        assert_eq!(parse_annotation().parse(written.trim_end()), Ok(a));
+    }
+
+    #[test]
+    fn test_source_debug_extension() {
+        use super::*;
+        use winnow::Parser;
+
+        let input = r#".annotation system Ldalvik/annotation/SourceDebugExtension;
+    value = "SMAP\nMain.kt\nKotlin\n*S Kotlin\n*F\n+ 1 Main.kt\ncom/example/MainKt\n+ 2 _Collections.kt\nkotlin/collections/CollectionsKt___CollectionsKt\n*L\n1#1,12:1\n1855#2,2:13\n*E\n*S KotlinDebug\n*F\n+ 1 Main.kt\ncom/example/MainKt\n*L\n5#1:13,2\n*E\n\t\"quoted\" \\ é \u00e9\n"
+.end annotation"#;
+        let a = parse_annotation().parse(input).unwrap();
+        let value = a.element_str("value").unwrap();
+        assert!(value.starts_with(r"SMAP\nMain.kt\n"), "{value}");
+        assert!(value.ends_with(r#"\t\"quoted\" \\ é \u00e9\n"#), "{value}");
+
+        let written = write_annotation(&a, false, false);
+        assert!(written.contains(r#"\u00e9\n""#), "{written}");
+        assert_eq!(parse_annotation().parse(written.trim_end()), Ok(a));
+    }
+
+    #[test]
+    fn test_marker_annotation() {
+        use super::*;
+        use winnow::Parser;
+
+        let input = ".annotation build Lfoo;\n.end annotation\n";
+        let a = parse_annotation().parse(input.trim_end()).unwrap();
+        assert!(a.elements.is_empty());
+        assert_eq!(write_annotation(&a, false, false), input);
+        assert_eq!(
+            write_annotation(&a, false, true),
+            "    .annotation build Lfoo;\n    .end annotation\n"
+        );
+
+        let input = ".annotation runtime Lfoo;
+    value = .subannotation Lbar;
+    .end subannotation
+.end annotation
+";
+        let a = parse_annotation().parse(input.trim_end()).unwrap();
+        assert_eq!(write_annotation(&a, false, false), input);
+    }
+
+    #[test]
+    fn test_annotation_visibility() {
+        use super::*;
+        use winnow::Parser;
+
+        let input = ".annotation build Lcom/example/Marker;
+    inner = .subannotation Lcom/example/Inner;
+        count = 0x1
+    .end subannotation
+.end annotation";
+        let a = parse_annotation().parse(input).unwrap();
+        assert_eq!(a.visibility, AnnotationVisibility::Build);
+        let written = write_annotation(&a, false, false);
+        assert!(written.starts_with(".annotation build Lcom/example/Marker;\n"));
+        assert!(written.contains("inner = .subannotation Lcom/example/Inner;\n"));
+        assert_eq!(parse_annotation().parse(written.trim_end()), Ok(a));
+
+        for v in ["runtime", "system"] {
+            let input = format!(".annotation {v} Lcom/example/Marker;\n.end annotation");
+            let a = parse_annotation().parse(input.as_str()).unwrap();
+            assert_eq!(write_annotation(&a, false, false), format!("{input}\n"));
+        }
+
+        // `.annotation` must say its visibility, `.subannotation` must not
+        for input in [
+            ".annotation Lcom/example/Marker;\n.end annotation",
+            ".subannotation build Lcom/example/Marker;\n.end subannotation",
+            ".annotation build Lcom/example/Marker;\n.end subannotation",
+        ] {
+            assert!(parse_annotation().parse(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_annotation_nesting_limit() {
+        use super::*;
+        use crate::DEFAULT_MAX_DEPTH;
+        use winnow::Parser;
+
+        let nested = |depth: usize| {
+            format!(
+                ".annotation system La;\n    value = {}\"x\"{}\n.end annotation",
+                "{".repeat(depth),
+                "}".repeat(depth)
+            )
+        };
+        let (deepest, too_deep, huge) = (
+            nested(DEFAULT_MAX_DEPTH - 1),
+            nested(DEFAULT_MAX_DEPTH),
+            nested(1_000_000),
+        );
+        assert!(parse_annotation().parse(deepest.as_str()).is_ok());
+        assert!(parse_annotation().parse(too_deep.as_str()).is_err());
+        assert!(parse_annotation().parse(huge.as_str()).is_err());
+    }
+
+    #[test]
+    fn test_value_extraction() {
+        use super::*;
+        use winnow::Parser;
+
+        let a = parse_annotation()
+            .parse(
+                r#".annotation runtime Lkotlin/Metadata;
+    k = 0x1
+    mv = {
+        0x1,
+        0x9,
+        0x0
+    }
+    xi = -0x30L
+    b = 0x7ft
+    s = 0x100s
+    pn = "kotlin"
+    enabled = true
+    hidden = false
+    type = [Ljava/lang/String;
+.end annotation"#,
+            )
+            .unwrap();
+        let value = |name| a.element(name).unwrap();
+        assert_eq!(value("k").as_i64(), Some(1));
+        let mv: Vec<_> = value("mv")
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_i64().unwrap())
+            .collect();
+        assert_eq!(mv, [1, 9, 0]);
+        assert_eq!(value("xi").as_i64(), Some(-0x30));
+        assert_eq!(value("b").as_i64(), Some(0x7f));
+        assert_eq!(value("s").as_i64(), Some(0x100));
+        assert_eq!(value("pn").as_str(), Some("kotlin"));
+        assert_eq!(value("enabled").as_bool(), Some(true));
+        assert_eq!(value("hidden").as_bool(), Some(false));
+        assert_eq!(
+            value("type").as_type().unwrap().to_jni(),
+            "[Ljava/lang/String;"
+        );
+
+        assert_eq!(value("pn").as_i64(), None);
+        assert_eq!(value("k").as_bool(), None);
+        assert_eq!(value("enabled").as_i64(), None);
+        assert!(value("k").as_array().is_none());
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)] // `3.14` is the literal under test, not pi
+    fn test_numeric_values() {
+        use super::*;
+        use winnow::Parser;
+
+        for (input, expected, written) in [
+            (
+                "-0x10",
+                AnnotationValue::Int {
+                    value: -0x10,
+                    postfix: None,
+                },
+                "-0x10",
+            ),
+            (
+                "0xffffffff",
+                AnnotationValue::Int {
+                    value: -1,
+                    postfix: None,
+                },
+                "-0x1",
+            ),
+            (
+                "-0x1L",
+                AnnotationValue::Int {
+                    value: -1,
+                    postfix: Some('L'),
+                },
+                "-0x1L",
+            ),
+            (
+                "0x7ft",
+                AnnotationValue::Int {
+                    value: 0x7f,
+                    postfix: Some('t'),
+                },
+                "0x7ft",
+            ),
+            (
+                "3.14",
+                AnnotationValue::Float {
+                    value: 3.14,
+                    postfix: None,
+                },
+                "3.14",
+            ),
+            (
+                "1.0f",
+                AnnotationValue::Float {
+                    value: 1.0,
+                    postfix: Some('f'),
+                },
+                "1.0f",
+            ),
+            (
+                "-2.5e3d",
+                AnnotationValue::Float {
+                    value: -2500.0,
+                    postfix: Some('d'),
+                },
+                "-2500.0d",
+            ),
+            (
+                "-Infinityf",
+                AnnotationValue::Float {
+                    value: f64::NEG_INFINITY,
+                    postfix: Some('f'),
+                },
+                "-Infinityf",
+            ),
+        ] {
+            let element = format!(" value = {input}\n");
+            let a = parse_annotation_element()
+                .parse_next(&mut element.as_str())
+                .unwrap();
+            assert_eq!(a.value, expected, "{input}");
+            let mut out = String::new();
+            write_annotation_value(&mut out, &a.value, false, "", "");
+            assert_eq!(out, format!("{written}\n"));
+        }
+
+        // not numbers, or out of range for their postfix
+        for input in ["0x100t", "1.5x", "Lfoo;", "true"] {
+            let element = format!(" value = {input}\n");
+            let a = parse_annotation_element()
+                .parse_next(&mut element.as_str())
+                .unwrap();
+            assert_eq!(a.value, AnnotationValue::Any(input.into()), "{input}");
+        }
     }
 }
@@ -1,27 +1,38 @@
-use std::{
-    borrow::Cow,
-    fs,
+use alloc::{borrow::Cow, collections::BTreeSet};
+use core::{
+    fmt::Write,
     hash::{Hash, Hasher},
-    path::Path,
 };
-
-use anyhow::Result;
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
 
 use winnow::{
     ModalParser, Parser,
-    combinator::{opt, preceded, repeat},
-    error::InputError,
+    ascii::multispace0,
+    combinator::{alt, cut_err, eof, not, opt, preceded, repeat, terminated},
     token::literal,
 };
 
 use crate::{
-    SmaliError,
-    annotation::{Annotation, parse_annotation, write_annotation},
-    field::{Field, parse_field},
-    method::{Method, parse_method, write_method},
-    modifier::{Modifier, parse_modifiers, write_modifiers},
-    object_identifier::{ObjectIdentifier, parse_object_identifier},
-    parse_string_lit, ws,
+    ParseFailure, ParseOptions, SmaliError,
+    annotation::{Annotation, AnnotationValue, parse_annotation_with, write_annotation},
+    comment,
+    field::{Field, parse_field_with},
+    format::{FormatStyle, LineEnding},
+    method::{Method, parse_method_with, write_method_into},
+    modifier::{
+        Modifier, modifiers_to_flags, parse_modifiers, write_hidden_api_restrictions,
+        write_modifiers,
+    },
+    object_identifier::{ObjectIdentifier, parse_object_identifier_with},
+    op::{Op, dex_op::DexOp, parse_unknown_directive},
+    parse_complete, parse_string_lit,
+    prelude::*,
+    signature::{
+        method_signature::{MethodParameter, MethodSignature},
+        type_signature::{TypeParameter, TypeSignature, parse_typesignature},
+    },
+    ws,
 };
 
 /// Represents a smali class i.e. the whole .smali file
@@ -29,17 +40,25 @@ use crate::{
 /// # Examples
 ///
 /// ```no_run
-///  use std::path::Path;
-///  use smali::types::SmaliClass;
+///  use std::fs;
+///  use smali::{class::parse_class, types::SmaliClass};
+///  use winnow::Parser;
 ///
-///  let c = SmaliClass::read_from_file(Path::new("smali/com/cool/Class.smali")).expect("Uh oh, does the file exist?");
-///  println!("Java class: {}", c.name.as_java_type());
+///  let smali = fs::read_to_string("smali/com/cool/Class.smali").expect("Uh oh, does the file exist?");
+///  let c: SmaliClass = parse_class().parse_next(&mut smali.as_str()).unwrap();
+///  println!("Java class: {}", c.meta.name.as_java_type());
 /// ```
 #[derive(Debug, PartialEq, Clone)]
 pub struct Class<'a> {
+    /// Comment lines ahead of `.class`, e.g. a license block, without their leading `#`
+    pub header_comments: Vec<Cow<'a, str>>,
     pub meta: ClassMeta<'a>,
     /// List of all the interfaces the class implements
     pub implements: Vec<ObjectIdentifier<'a>>,
+    /// Class level directives the crate doesn't model, each kept as its whole line when
+    /// parsing with [`ParseOptions::lenient`](crate::ParseOptions::lenient), along with where it was so it
+    /// is written back in the same place
+    pub directives: Vec<(DirectivePosition, Cow<'a, str>)>,
     /// Class level annotations
     pub annotations: Vec<Annotation<'a>>,
     /// All the fields defined by the class
@@ -60,25 +79,178 @@ pub struct ClassMeta<'a> {
     pub super_class: ObjectIdentifier<'a>,
 }
 
-pub fn parse_class<'a>() -> impl ModalParser<&'a str, Class<'a>, InputError<&'a str>> {
+/// Where an unknown directive sat in a class. Positions past the end of their list,
+/// e.g. after removing members, are written at the end of that section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectivePosition {
+    /// Right after `.super` and `.source`
+    Header,
+    /// After the interface at this index into [`Class::implements`]
+    Implements(usize),
+    /// After the class annotation at this index into [`Class::annotations`]
+    Annotation(usize),
+    /// After the field at this index into [`Class::fields`]
+    Field(usize),
+    /// After the method at this index into [`Class::methods`]
+    Method(usize),
+}
+
+/// One item of a class body, in source order
+enum ClassItem<'a> {
+    Implements(ObjectIdentifier<'a>),
+    Annotation(Annotation<'a>),
+    Field(Field<'a>),
+    Method(Method<'a>),
+    Directive(&'a str),
+}
+
+/// Appends the `items` not already in `into`, returning the index each one has there
+fn merge_into<T: PartialEq>(into: &mut Vec<T>, items: Vec<T>) -> Vec<usize> {
+    items
+        .into_iter()
+        .map(|item| match into.iter().position(|i| *i == item) {
+            Some(i) => i,
+            None => {
+                into.push(item);
+                into.len() - 1
+            }
+        })
+        .collect()
+}
+
+/// Least number of items inserted, removed or replaced to turn `a` into `b`. The
+/// common prefix and suffix are skipped first, edits are usually few and close together.
+fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    // the Levenshtein table, one row at a time
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(x != y);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Stably sorts `items` by `key`, returning the new index of each item by its old one
+fn sort_tracking<T, K: Ord>(items: &mut Vec<T>, key: impl Fn(&T) -> K) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by_cached_key(|&i| key(&items[i]));
+    let mut slots: Vec<Option<T>> = core::mem::take(items).into_iter().map(Some).collect();
+    *items = order.iter().filter_map(|&i| slots[i].take()).collect();
+    let mut moved = vec![0; order.len()];
+    for (new, old) in order.into_iter().enumerate() {
+        moved[old] = new;
+    }
+    moved
+}
+
+pub fn parse_class<'a>() -> impl ModalParser<&'a str, Class<'a>, ParseFailure> {
+    parse_class_with(ParseOptions::default())
+}
+
+/// Parses a class as `options` say, e.g. keeping the comments trailing instructions
+pub fn parse_class_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, Class<'a>, ParseFailure> {
     (
+        repeat(0.., preceded(multispace0, comment()).map(Cow::Borrowed)),
         preceded(
             ws(literal(".class")),
-            (parse_modifiers(), ws(parse_object_identifier())),
+            (parse_modifiers(), ws(parse_object_identifier_with(options))),
+        ),
+        preceded(
+            ws(literal(".super")),
+            ws(parse_object_identifier_with(options)),
         ),
-        preceded(ws(literal(".super")), ws(parse_object_identifier())),
         opt(preceded(ws(literal(".source")), ws(parse_string_lit())).map(Cow::Borrowed)),
         repeat(
             0..,
-            preceded(ws(literal(".implements")), ws(parse_object_identifier())),
+            alt((
+                preceded(
+                    ws(literal(".implements")),
+                    ws(parse_object_identifier_with(options)),
+                )
+                .map(ClassItem::Implements),
+                parse_unknown_directive(options).map(ClassItem::Directive),
+            )),
+        ),
+        repeat(
+            0..,
+            alt((
+                parse_annotation_with(options).map(ClassItem::Annotation),
+                parse_unknown_directive(options).map(ClassItem::Directive),
+            )),
+        ),
+        repeat(
+            0..,
+            alt((
+                parse_field_with(options).map(ClassItem::Field),
+                parse_unknown_directive(options).map(ClassItem::Directive),
+            )),
+        ),
+        repeat(
+            0..,
+            alt((
+                parse_method_with(options).map(ClassItem::Method),
+                parse_unknown_directive(options).map(ClassItem::Directive),
+            )),
         ),
-        repeat(0.., parse_annotation()),
-        repeat(0.., parse_field()),
-        repeat(0.., parse_method()),
     )
         .map(
-            |((modifiers, name), super_class, source, implements, annotations, fields, methods)| {
+            |(
+                header_comments,
+                (modifiers, name),
+                super_class,
+                source,
+                header_items,
+                annotation_items,
+                field_items,
+                method_items,
+            )| {
+                let items: [Vec<ClassItem>; 4] =
+                    [header_items, annotation_items, field_items, method_items];
+                let mut implements = vec![];
+                let mut annotations = vec![];
+                let mut fields = vec![];
+                let mut methods = vec![];
+                let mut directives = vec![];
+                let mut position = DirectivePosition::Header;
+                for item in items.into_iter().flatten() {
+                    match item {
+                        ClassItem::Implements(i) => {
+                            position = DirectivePosition::Implements(implements.len());
+                            implements.push(i);
+                        }
+                        ClassItem::Annotation(a) => {
+                            position = DirectivePosition::Annotation(annotations.len());
+                            annotations.push(a);
+                        }
+                        ClassItem::Field(f) => {
+                            position = DirectivePosition::Field(fields.len());
+                            fields.push(f);
+                        }
+                        ClassItem::Method(m) => {
+                            position = DirectivePosition::Method(methods.len());
+                            methods.push(m);
+                        }
+                        ClassItem::Directive(d) => directives.push((position, Cow::Borrowed(d))),
+                    }
+                }
                 Class {
+                    header_comments,
                     meta: ClassMeta {
                         name,
                         modifiers,
@@ -86,6 +258,7 @@ pub fn parse_class<'a>() -> impl ModalParser<&'a str, Class<'a>, InputError<&'a
                         super_class,
                     },
                     implements,
+                    directives,
                     annotations,
                     fields,
                     methods,
@@ -94,6 +267,33 @@ pub fn parse_class<'a>() -> impl ModalParser<&'a str, Class<'a>, InputError<&'a
         )
 }
 
+/// Parses `input` as exactly one class, failing rather than panicking on any malformed
+/// or trailing input. Meant for untrusted sources such as decompiled third party APKs.
+pub fn parse_class_complete(input: &str) -> Result<Class<'_>, SmaliError> {
+    parse_complete(input, "class", parse_class())
+}
+
+/// Parses every class in a stream of concatenated `.class` blocks, separated by blank
+/// lines or comments. The error points into the first block that fails to parse.
+pub fn parse_classes(input: &str) -> Result<Vec<Class<'_>>, SmaliError> {
+    // comments and blank lines after the last class
+    let trailer = || {
+        (
+            repeat::<_, _, (), _, _>(0.., preceded(multispace0, comment())),
+            multispace0,
+            eof,
+        )
+    };
+    parse_complete(
+        input,
+        "class",
+        terminated(
+            repeat(0.., preceded(not(trailer()), cut_err(parse_class()))),
+            trailer(),
+        ),
+    )
+}
+
 impl Hash for Class<'_> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.meta.name.hash(state);
@@ -101,20 +301,440 @@ impl Hash for Class<'_> {
 }
 
 impl<'a> Class<'a> {
-    /// Creates a smali document string from the current class
+    /// An empty class `name` extending `super_class`, without modifiers or members
+    pub fn new(name: ObjectIdentifier<'a>, super_class: ObjectIdentifier<'a>) -> Self {
+        Class {
+            header_comments: vec![],
+            meta: ClassMeta {
+                name,
+                modifiers: vec![],
+                source: None,
+                super_class,
+            },
+            implements: vec![],
+            directives: vec![],
+            annotations: vec![],
+            fields: vec![],
+            methods: vec![],
+        }
+    }
+
+    /// Copies any borrowed text, detaching the class from the parsed source
+    pub fn into_owned(self) -> Class<'static> {
+        Class {
+            header_comments: self
+                .header_comments
+                .into_iter()
+                .map(|c| Cow::Owned(c.into_owned()))
+                .collect(),
+            meta: ClassMeta {
+                name: self.meta.name.into_owned(),
+                modifiers: self.meta.modifiers,
+                source: self.meta.source.map(|s| Cow::Owned(s.into_owned())),
+                super_class: self.meta.super_class.into_owned(),
+            },
+            implements: self
+                .implements
+                .into_iter()
+                .map(ObjectIdentifier::into_owned)
+                .collect(),
+            directives: self
+                .directives
+                .into_iter()
+                .map(|(p, d)| (p, Cow::Owned(d.into_owned())))
+                .collect(),
+            annotations: self
+                .annotations
+                .into_iter()
+                .map(Annotation::into_owned)
+                .collect(),
+            fields: self.fields.into_iter().map(Field::into_owned).collect(),
+            methods: self.methods.into_iter().map(Method::into_owned).collect(),
+        }
+    }
+
+    /// Creates a smali document string from the current class, see [`write_class`].
+    /// Members are grouped the way baksmali writes them, so parsing the result back
+    /// may list [`fields`](Class::fields) and [`methods`](Class::methods) in another
+    /// order.
     ///
     /// # Examples
     ///
     /// ```no_run
-    ///  use std::path::Path;
-    ///  use smali::types::SmaliClass;
+    ///  use std::fs;
+    ///  use smali::{class::parse_class, types::SmaliClass};
+    ///  use winnow::Parser;
     ///
-    ///  let c = SmaliClass::read_from_file(Path::new("smali/com/cool/Class.smali")).expect("Uh oh, does the file exist?");
+    ///  let smali = fs::read_to_string("smali/com/cool/Class.smali").expect("Uh oh, does the file exist?");
+    ///  let c: SmaliClass = parse_class().parse_next(&mut smali.as_str()).unwrap();
     ///  println!("{}", c.to_smali());
     ///
     /// ```
     pub fn to_smali(&self) -> String {
-        write_class(self)
+        let mut out = String::new();
+        self.to_smali_into(&mut out);
+        out
+    }
+
+    /// Appends the smali text to `buf` instead of allocating a new string; a batch
+    /// re-emitter can `clear()` and reuse one buffer for every class.
+    pub fn to_smali_into(&self, buf: &mut String) {
+        write_class_into(self, &FormatStyle::default(), buf);
+    }
+
+    /// The class's modifiers as dex `access_flags`, e.g. `0x11` for `public final`
+    pub fn access_flags(&self) -> u32 {
+        modifiers_to_flags(&self.meta.modifiers)
+    }
+
+    /// Whether this is a Java enum: marked `enum` and extending `Ljava/lang/Enum;`
+    pub fn is_enum(&self) -> bool {
+        self.meta.modifiers.contains(&Modifier::Enum)
+            && self.meta.super_class.class_name == "java/lang/Enum"
+    }
+
+    /// Whether this class directly implements `id`, comparing class names only, so
+    /// `Ljava/lang/Comparable;` matches `Ljava/lang/Comparable<Ljava/lang/String;>;`
+    pub fn implements_interface(&self, id: &ObjectIdentifier) -> bool {
+        self.implements
+            .iter()
+            .any(|i| i.class_name == id.class_name)
+    }
+
+    /// Whether `id` is this class's direct superclass, comparing class names only
+    pub fn extends(&self, id: &ObjectIdentifier) -> bool {
+        self.meta.super_class.class_name == id.class_name
+    }
+
+    /// Methods dalvik dispatches directly: static, private and constructor methods
+    pub fn direct_methods(&self) -> impl Iterator<Item = &Method<'a>> {
+        self.methods.iter().filter(|m| m.is_direct())
+    }
+
+    /// Methods dalvik dispatches through the vtable, i.e. everything not direct
+    pub fn virtual_methods(&self) -> impl Iterator<Item = &Method<'a>> {
+        self.methods.iter().filter(|m| !m.is_direct())
+    }
+
+    /// Puts the members in a canonical order for reproducible output: static fields
+    /// before instance fields and direct methods before virtual ones, each group
+    /// sorted by [`Field::sort_key`] and [`Method::sort_key`].
+    pub fn sort_members(&mut self) {
+        let fields = sort_tracking(&mut self.fields, |f| (!f.is_static(), f.sort_key()));
+        let methods = sort_tracking(&mut self.methods, |m| (!m.is_direct(), m.sort_key()));
+        for (position, _) in &mut self.directives {
+            match position {
+                DirectivePosition::Field(i) => *i = fields.get(*i).copied().unwrap_or(*i),
+                DirectivePosition::Method(i) => *i = methods.get(*i).copied().unwrap_or(*i),
+                _ => {}
+            }
+        }
+    }
+
+    /// Compares this class against `other`, a newer version of it.
+    pub fn diff(&self, other: &Class) -> ClassDiff {
+        let mut diff = ClassDiff {
+            modifiers_changed: self.meta.modifiers != other.meta.modifiers,
+            super_class_changed: self.meta.super_class != other.meta.super_class,
+            interfaces_changed: self.implements != other.implements,
+            ..Default::default()
+        };
+
+        for f in &self.fields {
+            let key = field_key(f);
+            match other.fields.iter().find(|o| field_key(o) == key) {
+                None => diff.removed_fields.push(key),
+                Some(o) if o != f => diff.changed_fields.push(key),
+                Some(_) => {}
+            }
+        }
+        for f in &other.fields {
+            let key = field_key(f);
+            if !self.fields.iter().any(|o| field_key(o) == key) {
+                diff.added_fields.push(key);
+            }
+        }
+
+        for m in &self.methods {
+            let key = method_key(m);
+            let Some(o) = other.methods.iter().find(|o| method_key(o) == key) else {
+                diff.removed_methods.push(key);
+                continue;
+            };
+            let header_changed = m.modifiers != o.modifiers
+                || m.hidden_api != o.hidden_api
+                || m.annotations != o.annotations
+                || m.params != o.params
+                || (m.locals, m.registers) != (o.locals, o.registers);
+            let changed_ops = edit_distance(&m.ops, &o.ops);
+            if header_changed || changed_ops > 0 {
+                diff.changed_methods.push(MethodDiff {
+                    method: key,
+                    header_changed,
+                    changed_ops,
+                });
+            }
+        }
+        for m in &other.methods {
+            let key = method_key(m);
+            if !self.methods.iter().any(|o| method_key(o) == key) {
+                diff.added_methods.push(key);
+            }
+        }
+
+        diff
+    }
+
+    /// Adds the interfaces, annotations, fields and methods of `other`, another part of
+    /// the same class. Members `self` already has identically are skipped. Fails
+    /// without changing `self` if the class names, superclasses or modifiers differ, or
+    /// both define a field, method or annotation differently.
+    pub fn merge(&mut self, other: Class<'a>) -> Result<(), SmaliError> {
+        let name = self.meta.name.as_jni_type();
+        if other.meta.name != self.meta.name {
+            return Err(SmaliError {
+                details: format!("cannot merge {} into {name}", other.meta.name.as_jni_type()),
+            });
+        }
+        let conflict = |what: &str, key: String| SmaliError {
+            details: format!("{name}: conflicting definitions of {what} {key}"),
+        };
+        if other.meta.super_class != self.meta.super_class {
+            return Err(SmaliError {
+                details: format!(
+                    "{name}: conflicting superclasses {} and {}",
+                    self.meta.super_class.as_jni_type(),
+                    other.meta.super_class.as_jni_type()
+                ),
+            });
+        }
+        if other.access_flags() != self.access_flags() {
+            return Err(SmaliError {
+                details: format!(
+                    "{name}: conflicting modifiers `{}` and `{}`",
+                    write_modifiers(&self.meta.modifiers).trim_end(),
+                    write_modifiers(&other.meta.modifiers).trim_end()
+                ),
+            });
+        }
+        for f in &other.fields {
+            let key = field_key(f);
+            if let Some(mine) = self.fields.iter().find(|o| field_key(o) == key)
+                && mine != f
+            {
+                return Err(conflict("field", key));
+            }
+        }
+        for m in &other.methods {
+            let key = method_key(m);
+            if let Some(mine) = self.methods.iter().find(|o| method_key(o) == key)
+                && mine != m
+            {
+                return Err(conflict("method", key));
+            }
+        }
+        for a in &other.annotations {
+            if let Some(mine) = self
+                .annotations
+                .iter()
+                .find(|o| o.annotation_type == a.annotation_type)
+                && mine != a
+            {
+                return Err(conflict("annotation", a.annotation_type.to_jni()));
+            }
+        }
+
+        // where each of `other`'s items ends up, to move its directives along
+        let implements = merge_into(&mut self.implements, other.implements);
+        let annotations = merge_into(&mut self.annotations, other.annotations);
+        let fields = merge_into(&mut self.fields, other.fields);
+        let methods = merge_into(&mut self.methods, other.methods);
+        // positions past the end of `other`'s lists stay past the end of ours
+        let moved = |at: &[usize], i: usize, len: usize| at.get(i).copied().unwrap_or(len);
+        for (position, d) in other.directives {
+            let position = match position {
+                DirectivePosition::Header => DirectivePosition::Header,
+                DirectivePosition::Implements(i) => {
+                    DirectivePosition::Implements(moved(&implements, i, self.implements.len()))
+                }
+                DirectivePosition::Annotation(i) => {
+                    DirectivePosition::Annotation(moved(&annotations, i, self.annotations.len()))
+                }
+                DirectivePosition::Field(i) => {
+                    DirectivePosition::Field(moved(&fields, i, self.fields.len()))
+                }
+                DirectivePosition::Method(i) => {
+                    DirectivePosition::Method(moved(&methods, i, self.methods.len()))
+                }
+            };
+            if !self.directives.contains(&(position, d.clone())) {
+                self.directives.push((position, d));
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames the method `old`, or only its overload with the descriptor of `sig`, and
+    /// every invoke of or annotation reference to it from within this class. Calls from
+    /// other classes are left to a whole-program pass.
+    pub fn rename_method(&mut self, old: &str, sig: Option<&MethodSignature>, new: &str) {
+        let descriptor = sig.map(MethodSignature::descriptor);
+        let matches = |m: &MethodParameter| {
+            m.ident == old && descriptor.as_ref().is_none_or(|d| *d == m.ms.descriptor())
+        };
+        let class_name = self.meta.name.class_name.clone();
+        let ours = |class: &TypeSignature| matches!(class, TypeSignature::Object(o) if o.class_name == class_name);
+        for m in &mut self.methods {
+            if matches(&m.param) {
+                m.param.ident = Cow::Owned(new.to_string());
+            }
+            for (_, op) in m.instructions_mut() {
+                if let DexOp::Invoke {
+                    method: Some(target),
+                    ..
+                } = op
+                    && ours(&target.class)
+                    && matches(&target.param)
+                {
+                    target.param.ident = Cow::Owned(new.to_string());
+                }
+            }
+        }
+        self.for_each_annotation_value_mut(|value| {
+            if let AnnotationValue::Method(target) = value
+                && ours(&target.class)
+                && matches(&target.param)
+            {
+                target.param.ident = Cow::Owned(new.to_string());
+            }
+        });
+    }
+
+    /// Renames the field `old`, or only the one of type `ty`, and every access to or
+    /// `.enum` reference to it from within this class
+    pub fn rename_field(&mut self, old: &str, ty: Option<&TypeSignature>, new: &str) {
+        let matches = |f: &TypeParameter| f.ident == old && ty.is_none_or(|ty| *ty == f.ts);
+        for f in &mut self.fields {
+            if matches(&f.param) {
+                f.param.ident = Cow::Owned(new.to_string());
+            }
+        }
+        let class_name = self.meta.name.class_name.clone();
+        for m in &mut self.methods {
+            for (_, op) in m.instructions_mut() {
+                if let DexOp::DynamicFieldAccess { field, .. }
+                | DexOp::StaticFieldAccess { field, .. } = op
+                    && field.class.class_name == class_name
+                    && matches(&field.param)
+                {
+                    field.param.ident = Cow::Owned(new.to_string());
+                }
+            }
+        }
+        self.for_each_annotation_value_mut(|value| {
+            if let AnnotationValue::Enum(field) = value
+                && field.class.class_name == class_name
+                && matches(&field.param)
+            {
+                field.param.ident = Cow::Owned(new.to_string());
+            }
+        });
+    }
+
+    /// Calls `visit` on every annotation value of the class and its members, nested
+    /// ones included
+    fn for_each_annotation_value_mut(&mut self, mut visit: impl FnMut(&mut AnnotationValue<'a>)) {
+        let mut annotations: Vec<&mut Annotation<'a>> = self.annotations.iter_mut().collect();
+        for f in &mut self.fields {
+            annotations.extend(&mut f.annotations);
+        }
+        for m in &mut self.methods {
+            annotations.extend(&mut m.annotations);
+            annotations.extend(m.params.iter_mut().flat_map(|p| &mut p.annotations));
+        }
+        let mut values: Vec<&mut AnnotationValue<'a>> = annotations
+            .into_iter()
+            .flat_map(|a| a.elements.iter_mut().map(|e| &mut e.value))
+            .collect();
+        while let Some(value) = values.pop() {
+            visit(value);
+            match value {
+                AnnotationValue::Array(items) => values.extend(items),
+                AnnotationValue::SubAnnotation(s) => {
+                    values.extend(s.elements.iter_mut().map(|e| &mut e.value))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Every other class this class refers to: its superclass and interfaces, field
+    /// types, method signatures, types used by instructions, caught exceptions and
+    /// annotation types. Identifiers are erased to their raw class and primitive types
+    /// are left out.
+    pub fn dependencies(&self) -> BTreeSet<ObjectIdentifier<'a>> {
+        let mut types: Vec<&ObjectIdentifier<'a>> = vec![&self.meta.super_class];
+        types.extend(&self.implements);
+        let mut annotations: Vec<&Annotation<'a>> = self.annotations.iter().collect();
+        for f in &self.fields {
+            types.extend(f.param.ts.object_types());
+            annotations.extend(&f.annotations);
+        }
+        for m in &self.methods {
+            types.extend(m.param.ms.object_types());
+            types.extend(m.ops.iter().flat_map(Op::referenced_types));
+            annotations.extend(&m.annotations);
+            annotations.extend(m.params.iter().flat_map(|p| &p.annotations));
+        }
+        let mut deps: BTreeSet<ObjectIdentifier<'a>> =
+            types.into_iter().map(|o| o.erased()).collect();
+        for a in annotations {
+            annotation_dependencies(a, &mut deps);
+        }
+        deps.retain(|o| o.class_name != self.meta.name.class_name);
+        deps
+    }
+
+    /// Runs every validator over the class and returns all diagnostics at once:
+    /// duplicate field and method signatures, and for each method its body,
+    /// register declarations, locals count, and branch, catch and switch labels.
+    pub fn verify(&self) -> Result<(), Vec<SmaliError>> {
+        let mut errors = vec![];
+        let mut seen = BTreeSet::new();
+        for f in &self.fields {
+            let key = field_key(f);
+            if !seen.insert(key.clone()) {
+                errors.push(SmaliError {
+                    details: format!("{key}: duplicate field"),
+                });
+            }
+        }
+        let mut seen = BTreeSet::new();
+        for m in &self.methods {
+            let key = method_key(m);
+            if !seen.insert(key.clone()) {
+                errors.push(SmaliError {
+                    details: format!("{key}: duplicate method"),
+                });
+            }
+        }
+        for m in &self.methods {
+            let checks = [
+                m.validate_body(),
+                m.check_register_directives(),
+                m.check_locals(),
+                m.validate_branch_targets(),
+                m.validate_catch_labels(),
+                m.validate_switch_targets(),
+                m.validate_move_results(),
+            ];
+            errors.extend(checks.into_iter().filter_map(Result::err));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 
     /// Writes the current SmaliClass to the specified file path as a smali document
@@ -123,12 +743,16 @@ impl<'a> Class<'a> {
     ///
     /// ```no_run
     ///  use std::path::Path;
-    ///  use smali::types::SmaliClass;
+    ///  use std::fs;
+    ///  use smali::{class::parse_class, types::SmaliClass};
+    ///  use winnow::Parser;
     ///
-    ///  let c = SmaliClass::read_from_file(Path::new("smali/com/cool/Class.smali")).expect("Uh oh, does the file exist?");
+    ///  let smali = fs::read_to_string("smali/com/cool/Class.smali").expect("Uh oh, does the file exist?");
+    ///  let c: SmaliClass = parse_class().parse_next(&mut smali.as_str()).unwrap();
     ///  c.write_to_file(Path::new("smali_classes2/com/cool/Class.smali")).unwrap();
     ///
     /// ```
+    #[cfg(feature = "std")]
     pub fn write_to_file(&self, path: &Path) -> Result<(), SmaliError> {
         let smali = self.to_smali();
         if let Err(e) = fs::write(path, smali) {
@@ -141,68 +765,249 @@ impl<'a> Class<'a> {
     }
 }
 
+/// Semantic differences between two versions of a class, see [`Class::diff`].
+///
+/// Fields are keyed as `name:Type` and methods as `name(Args)Ret`.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ClassDiff {
+    pub modifiers_changed: bool,
+    pub super_class_changed: bool,
+    pub interfaces_changed: bool,
+    pub added_fields: Vec<String>,
+    pub removed_fields: Vec<String>,
+    /// Fields whose modifiers, initial value or annotations differ
+    pub changed_fields: Vec<String>,
+    pub added_methods: Vec<String>,
+    pub removed_methods: Vec<String>,
+    pub changed_methods: Vec<MethodDiff>,
+}
+
+/// A method present in both classes whose declaration or body differs
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MethodDiff {
+    pub method: String,
+    /// Modifiers, annotations or register declarations differ
+    pub header_changed: bool,
+    /// Least number of ops inserted, removed or replaced to turn one body into the other
+    pub changed_ops: usize,
+}
+
+impl ClassDiff {
+    /// Whether the two classes are semantically identical
+    pub fn is_empty(&self) -> bool {
+        *self == ClassDiff::default()
+    }
+}
+
+fn annotation_dependencies<'a>(a: &Annotation<'a>, deps: &mut BTreeSet<ObjectIdentifier<'a>>) {
+    deps.extend(
+        a.annotation_type
+            .object_types()
+            .into_iter()
+            .map(|o| o.erased()),
+    );
+    let mut values: Vec<&AnnotationValue<'a>> = a.elements.iter().map(|e| &e.value).collect();
+    while let Some(value) = values.pop() {
+        match value {
+            AnnotationValue::Array(items) => values.extend(items),
+            AnnotationValue::SubAnnotation(s) => annotation_dependencies(s, deps),
+            AnnotationValue::Enum(f) => {
+                deps.insert(f.class.erased());
+            }
+            AnnotationValue::Method(m) => deps.extend(
+                m.class
+                    .object_types()
+                    .into_iter()
+                    .chain(m.param.ms.object_types())
+                    .map(|o| o.erased()),
+            ),
+            // class literals such as the entries of `MemberClasses` are kept as raw text
+            AnnotationValue::Any(raw) => {
+                if let Ok(ts) = parse_typesignature().parse(raw.as_ref()) {
+                    deps.extend(ts.object_types().into_iter().map(|o| ObjectIdentifier {
+                        class_name: Cow::Owned(o.erased().class_name.into_owned()),
+                        type_arguments: None,
+                        suffix: None,
+                    }));
+                }
+            }
+            AnnotationValue::String(_)
+            | AnnotationValue::Int { .. }
+            | AnnotationValue::Float { .. } => {}
+        }
+    }
+}
+
+fn field_key(f: &Field) -> String {
+    format!("{}:{}", f.param.ident, f.param.ts)
+}
+
+fn method_key(m: &Method) -> String {
+    let (name, descriptor) = m.sort_key();
+    format!("{name}{descriptor}")
+}
+
+/// Writes a class the way baksmali does. Fields are grouped into static then instance
+/// ones and methods into direct then virtual ones, each group in its original order,
+/// so `parse(write(c))` may list them in another order than `c`.
 pub fn write_class(dex: &Class) -> String {
-    let mut out = format!(
-        ".class {}{}\n",
+    write_class_with(dex, &FormatStyle::default())
+}
+
+/// Writes a class laid out according to `style`
+pub fn write_class_with(dex: &Class, style: &FormatStyle) -> String {
+    let mut out = String::new();
+    write_class_into(dex, style, &mut out);
+    out
+}
+
+/// Appends the smali text of a class to `out`, so one buffer can be cleared and
+/// reused across many classes. Members are grouped as [`write_class`] describes.
+pub fn write_class_into(dex: &Class, style: &FormatStyle, out: &mut String) {
+    let start = out.len();
+    // writing to a `String` can't fail
+    for c in &dex.header_comments {
+        let _ = writeln!(out, "#{c}");
+    }
+    if !dex.header_comments.is_empty() {
+        out.push('\n');
+    }
+    let _ = writeln!(
+        out,
+        ".class {}{}",
         write_modifiers(&dex.meta.modifiers),
         dex.meta.name.as_jni_type()
     );
-    out.push_str(&format!(".super {}\n", dex.meta.super_class.as_jni_type()));
+    let _ = writeln!(out, ".super {}", dex.meta.super_class.as_jni_type());
     if let Some(s) = &dex.meta.source {
-        out.push_str(&format!(".source \"{s}\"\n"));
+        let _ = writeln!(out, ".source \"{s}\"");
     }
+    write_directives(out, dex, "\n", |p| p == DirectivePosition::Header);
 
     if !dex.implements.is_empty() {
         out.push_str("\n# interfaces\n");
-        for i in &dex.implements {
+        for (n, i) in dex.implements.iter().enumerate() {
             out.push_str(".implements ");
             out.push_str(&i.as_jni_type());
             out.push('\n');
+            write_directives(out, dex, "\n", |p| p == DirectivePosition::Implements(n));
         }
     }
+    let len = dex.implements.len();
+    write_directives(
+        out,
+        dex,
+        "\n",
+        |p| matches!(p, DirectivePosition::Implements(n) if n >= len),
+    );
 
     if !dex.annotations.is_empty() {
         out.push_str("\n# annotations\n");
-        for a in &dex.annotations {
+        for (n, a) in dex.annotations.iter().enumerate() {
             out.push_str(&write_annotation(a, false, false));
             out.push('\n');
+            write_directives(out, dex, "\n\n", |p| p == DirectivePosition::Annotation(n));
         }
     }
+    let len = dex.annotations.len();
+    write_directives(
+        out,
+        dex,
+        "\n\n",
+        |p| matches!(p, DirectivePosition::Annotation(n) if n >= len),
+    );
 
-    if !dex.fields.is_empty() {
-        out.push_str("\n# fields\n");
-        for f in &dex.fields {
-            out.push_str(&format!(
-                ".field {}{}:{}",
+    // baksmali's sections, each keeping the members in source order
+    for (is_static, banner) in [(true, "static fields"), (false, "instance fields")] {
+        let mut fields = dex
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.is_static() == is_static)
+            .peekable();
+        if fields.peek().is_some() {
+            let _ = writeln!(out, "\n# {banner}");
+        }
+        for (n, f) in fields {
+            let _ = write!(
+                out,
+                ".field {}{}{}:{}",
                 write_modifiers(&f.modifiers),
+                write_hidden_api_restrictions(&f.hidden_api),
                 f.param.ident,
                 f.param.ts.to_jni()
-            ));
+            );
             if let Some(iv) = &f.initial_value {
-                out.push_str(&format!(" = {iv}"));
+                let _ = write!(out, " = {iv}");
             }
             out.push('\n');
-            if !f.annotations.is_empty() {
+            if !f.annotations.is_empty() || !f.directives.is_empty() {
                 for a in &f.annotations {
                     out.push_str(&write_annotation(a, false, true));
                 }
+                for d in &f.directives {
+                    let _ = writeln!(out, "    {d}");
+                }
                 out.push_str(".end field\n");
             }
             out.push('\n');
+            write_directives(out, dex, "\n\n", |p| p == DirectivePosition::Field(n));
         }
     }
+    let len = dex.fields.len();
+    write_directives(
+        out,
+        dex,
+        "\n\n",
+        |p| matches!(p, DirectivePosition::Field(n) if n >= len),
+    );
 
-    if !dex.methods.is_empty() {
-        out.push_str("\n# methods\n");
-        for m in &dex.methods {
-            out.push_str(&write_method(m));
+    // line ends are converted once, for the whole class
+    let lf = FormatStyle {
+        line_ending: LineEnding::Lf,
+        ..*style
+    };
+    for (is_direct, banner) in [(true, "direct methods"), (false, "virtual methods")] {
+        let mut methods = dex
+            .methods
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.is_direct() == is_direct)
+            .peekable();
+        if methods.peek().is_some() {
+            let _ = writeln!(out, "\n# {banner}");
+        }
+        for (n, m) in methods {
+            write_method_into(m, &lf, out);
+            write_directives(out, dex, "\n\n", |p| p == DirectivePosition::Method(n));
         }
     }
+    let len = dex.methods.len();
+    write_directives(
+        out,
+        dex,
+        "\n\n",
+        |p| matches!(p, DirectivePosition::Method(n) if n >= len),
+    );
+    style.line_ending.apply(out, start);
+}
 
-    out
+/// Writes the class's unknown directives whose position is picked by `at`, each
+/// followed by `end`
+fn write_directives(
+    out: &mut String,
+    dex: &Class,
+    end: &str,
+    at: impl Fn(DirectivePosition) -> bool,
+) {
+    for (_, d) in dex.directives.iter().filter(|(p, _)| at(*p)) {
+        out.push_str(d);
+        out.push_str(end);
+    }
 }
 
 mod tests {
+    #[cfg(feature = "std")]
     #[test]
     fn test_parse_class() {
         use super::*;
@@ -210,12 +1015,12 @@ mod tests {
 
         for dir in fs::read_dir("tests").unwrap() {
             let dir = dir.unwrap();
-            println!("{:?}", dir.file_name());
             let smali = fs::read_to_string(dir.path()).unwrap();
             let _ = parse_class().parse_next(&mut smali.as_str()).unwrap();
         }
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_read_write_class() {
         use super::*;
@@ -223,13 +1028,10 @@ mod tests {
 
         for dir in fs::read_dir("tests").unwrap() {
             let dir = dir.unwrap();
-            println!("{:?}", dir.file_name());
             let smali = fs::read_to_string(dir.path()).unwrap();
 
             let c = parse_class().parse_next(&mut smali.as_str()).unwrap();
 
-            //println!("@clas {c:?}");
-
             assert!(!c.methods.is_empty());
 
             let second_smali = c.to_smali();
@@ -243,4 +1045,1248 @@ mod tests {
             assert_eq!(c, c2);
         }
     }
+
+    #[test]
+    fn test_write_class_banners() {
+        use super::*;
+        use winnow::Parser;
+
+        let mut smali = r#".class public Lcom/example/Banners;
+.super Ljava/lang/Object;
+
+.field private static final TAG:Ljava/lang/String; = "Banners"
+
+.field private count:I
+
+.method static constructor <clinit>()V
+    .locals 0
+    return-void
+.end method
+
+.method public constructor <init>()V
+    .locals 0
+    invoke-direct {p0}, Ljava/lang/Object;-><init>()V
+    return-void
+.end method
+
+.method private reset()V
+    .locals 0
+    return-void
+.end method
+
+.method public run()V
+    .locals 0
+    return-void
+.end method
+"#;
+
+        let c = parse_class().parse_next(&mut smali).unwrap();
+        let out = c.to_smali();
+
+        let banners: Vec<&str> = out.lines().filter(|l| l.starts_with("# ")).collect();
+        assert_eq!(
+            banners,
+            vec![
+                "# static fields",
+                "# instance fields",
+                "# direct methods",
+                "# virtual methods"
+            ]
+        );
+        assert!(
+            out.find(".field private count:I").unwrap() > out.find("# instance fields").unwrap()
+        );
+        assert!(out.find("reset()V").unwrap() < out.find("# virtual methods").unwrap());
+        assert!(out.find("run()V").unwrap() > out.find("# virtual methods").unwrap());
+    }
+
+    #[test]
+    fn test_direct_and_virtual_methods() {
+        use super::*;
+        use winnow::Parser;
+
+        let mut smali = r#".class public Lcom/example/Dispatch;
+.super Ljava/lang/Object;
+
+.method public static create()Lcom/example/Dispatch;
+    .locals 1
+    const/4 v0, 0x0
+    return-object v0
+.end method
+
+.method public run()V
+    .locals 0
+    return-void
+.end method
+"#;
+
+        let c = parse_class().parse_next(&mut smali).unwrap();
+        let direct: Vec<_> = c.direct_methods().map(|m| m.param.ident.as_ref()).collect();
+        let virt: Vec<_> = c
+            .virtual_methods()
+            .map(|m| m.param.ident.as_ref())
+            .collect();
+        assert_eq!(direct, vec!["create"]);
+        assert_eq!(virt, vec!["run"]);
+    }
+
+    #[test]
+    fn test_class_diff() {
+        use super::*;
+        use winnow::Parser;
+
+        let mut old = r#".class public Lcom/example/Counter;
+.super Ljava/lang/Object;
+
+.field private count:I
+.field private name:Ljava/lang/String;
+
+.method public get()I
+    .locals 1
+    const/4 v0, 0x0
+    return v0
+.end method
+
+.method public reset()V
+    .locals 0
+    return-void
+.end method
+"#;
+        let mut new = r#".class public final Lcom/example/Counter;
+.super Ljava/lang/Object;
+.implements Ljava/lang/Runnable;
+
+.field private volatile count:I
+.field private total:J
+
+.method public get()I
+    .locals 1
+    const/4 v0, 0x1
+    return v0
+.end method
+
+.method public run()V
+    .locals 0
+    return-void
+.end method
+"#;
+        let old = parse_class().parse_next(&mut old).unwrap();
+        let new = parse_class().parse_next(&mut new).unwrap();
+
+        assert!(old.diff(&old).is_empty());
+
+        let d = old.diff(&new);
+        assert!(d.modifiers_changed);
+        assert!(!d.super_class_changed);
+        assert!(d.interfaces_changed);
+        assert_eq!(d.added_fields, vec!["total:J"]);
+        assert_eq!(d.removed_fields, vec!["name:Ljava/lang/String;"]);
+        assert_eq!(d.changed_fields, vec!["count:I"]);
+        assert_eq!(d.added_methods, vec!["run()V"]);
+        assert_eq!(d.removed_methods, vec!["reset()V"]);
+        assert_eq!(
+            d.changed_methods,
+            vec![MethodDiff {
+                method: "get()I".to_string(),
+                header_changed: false,
+                changed_ops: 1,
+            }]
+        );
+
+        // an inserted op is one change, not a shift of everything after it
+        let mut longer = old.clone();
+        longer.methods[0]
+            .splice_before(0, vec![Op::Line(1)])
+            .unwrap();
+        let d = old.diff(&longer);
+        assert_eq!(d.changed_methods[0].changed_ops, 1);
+        let mut swapped = old.clone();
+        swapped.methods[0].ops.swap(0, 1);
+        assert_eq!(old.diff(&swapped).changed_methods[0].changed_ops, 2);
+        assert_eq!(edit_distance(&[1, 2, 3, 4], &[1, 5, 3, 6, 4]), 2);
+        assert_eq!(edit_distance(&[1, 2, 3], &[]), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_verify() {
+        use super::*;
+        use winnow::Parser;
+
+        let mut smali = r#".class public Lcom/example/Broken;
+.super Ljava/lang/Object;
+
+.field private count:I
+.field private count:I
+
+.method public abstract size()I
+    .locals 0
+    return v0
+.end method
+
+.method public get(I)I
+    .locals 0
+    :try_start_0
+    if-eqz p1, :cond_0
+    packed-switch p1, :pswitch_data_0
+    const/4 v2, 0x1
+    return v2
+    .catch Ljava/lang/Exception; {:try_start_0 .. :try_end_0} :handler_0
+    :pswitch_data_0
+    .packed-switch 0x0
+        :pswitch_0
+    .end packed-switch
+.end method
+
+.method public get(I)I
+    .locals 0
+    return p1
+.end method
+"#;
+        let class = parse_class().parse_next(&mut smali).unwrap();
+        let details: Vec<String> = class
+            .verify()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.details)
+            .collect();
+        assert_eq!(
+            details,
+            [
+                "count:I: duplicate field",
+                "get(I)I: duplicate method",
+                "size()I: abstract or native method must not have a body",
+                "get(I)I: declares 0 locals but needs 3",
+                "get(I)I: undefined branch targets :cond_0",
+                "get(I)I: undefined catch labels :try_end_0, :handler_0",
+                "get(I)I: undefined switch targets :pswitch_0",
+            ]
+        );
+
+        for entry in fs::read_dir("tests").unwrap() {
+            let path = entry.unwrap().path();
+            let smali = fs::read_to_string(&path).unwrap();
+            let class = parse_class().parse(&smali).unwrap();
+            if let Err(e) = class.verify() {
+                panic!("{}: {e:?}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn test_header_comments() {
+        use super::*;
+        use winnow::Parser;
+
+        let smali = "# Copyright (C) 2024 Example Corp.\n#\n# Licensed under the Apache License, Version 2.0\n\n.class public Lcom/example/Licensed;\n.super Ljava/lang/Object;\n";
+        let c = parse_class().parse(smali).unwrap();
+        assert_eq!(
+            c.header_comments,
+            [
+                " Copyright (C) 2024 Example Corp.",
+                "",
+                " Licensed under the Apache License, Version 2.0"
+            ]
+        );
+        let out = write_class(&c);
+        assert!(out.starts_with(smali), "{out}");
+        assert_eq!(parse_class().parse(&out), Ok(c));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parse_never_panics() {
+        use super::*;
+
+        // xorshift64*, so the inputs are the same on every run
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move |bound: usize| {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as usize % bound
+        };
+        const TOKENS: &[&str] = &[
+            ".class ",
+            ".super ",
+            ".method ",
+            ".end method",
+            ".field ",
+            ".end field",
+            ".annotation ",
+            ".subannotation ",
+            ".end annotation",
+            ".end subannotation",
+            ".locals ",
+            ".registers ",
+            ".param ",
+            ".line ",
+            ".catch ",
+            ".catchall ",
+            ".array-data ",
+            ".end array-data",
+            ".packed-switch ",
+            ".end packed-switch",
+            ".sparse-switch ",
+            ".end sparse-switch",
+            ".enum ",
+            "public ",
+            "static ",
+            "Ljava/lang/Object;",
+            "[I",
+            "(II)V",
+            "<T:",
+            ">",
+            "->",
+            ":",
+            "=",
+            ",",
+            "{",
+            "}",
+            "..",
+            "\"",
+            "\\",
+            "#",
+            "\n",
+            " ",
+            "v0",
+            "p1",
+            "v65536",
+            "0x",
+            "-0x8000",
+            "0xffffffffffffffffffffL",
+            "t",
+            "s",
+            "f",
+            "d",
+            "L",
+            "NaNf",
+            "-Infinityd",
+            "const/4 ",
+            "const-wide/high16 ",
+            "invoke-virtual ",
+            "invoke-custom ",
+            "fill-array-data ",
+            "packed-switch ",
+            "goto ",
+            "if-eqz ",
+            "é",
+            "\u{0}",
+        ];
+
+        let err =
+            parse_class_complete(".class public La;\n.super Ljava/lang/Object;\n}").unwrap_err();
+        assert_eq!(err.details, "failed to parse class at line 3 (byte 44)");
+
+        let fixtures: Vec<String> = fs::read_dir("tests")
+            .unwrap()
+            .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+            .collect();
+        for _ in 0..300 {
+            let input: String = (0..next(200)).map(|_| TOKENS[next(TOKENS.len())]).collect();
+            let _ = parse_class_complete(&input);
+
+            // mutate a real class: truncate it, splice tokens in or drop a slice
+            let base = &fixtures[next(fixtures.len())];
+            let at = |n: usize| (0..=n).rev().find(|&i| base.is_char_boundary(i)).unwrap();
+            let (a, b) = (at(next(base.len())), at(next(base.len())));
+            let (a, b) = (a.min(b), a.max(b));
+            let mutated = match next(3) {
+                0 => base[..a].to_string(),
+                1 => format!("{}{}{}", &base[..a], TOKENS[next(TOKENS.len())], &base[a..]),
+                _ => format!("{}{}", &base[..a], &base[b..]),
+            };
+            let _ = parse_class_complete(&mutated);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_crlf_line_endings() {
+        use super::*;
+        use winnow::Parser;
+
+        let keep_comments = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        for entry in fs::read_dir("tests").unwrap() {
+            let path = entry.unwrap().path();
+            let lf = fs::read_to_string(&path).unwrap().replace("\r\n", "\n");
+            let crlf = lf.replace('\n', "\r\n");
+            let expected = parse_class_with(keep_comments).parse(&lf).unwrap();
+            let c = parse_class_with(keep_comments).parse(&crlf).unwrap();
+            assert_eq!(c, expected, "{}", path.display());
+        }
+
+        let smali = "# header\r\n.class public La;\r\n.super Ljava/lang/Object;\r\n\r\n.field static final X:I = 0x1\r\n\r\n.method public static run()V\r\n    .locals 1\r\n    const/4 v0, 0x1    # one\r\n    .line 3\r\n    return-void\r\n.end method\r\n";
+        let c = parse_class_with(keep_comments).parse(smali).unwrap();
+        assert_eq!(c.header_comments, [" header"]);
+        assert_eq!(c.fields[0].initial_value.as_deref(), Some("0x1"));
+        assert_eq!(
+            c.methods[0].comments.get(&0).map(|c| c.as_ref()),
+            Some("one")
+        );
+        assert!(!write_class(&c).contains('\r'));
+    }
+
+    #[test]
+    fn test_lenient_unknown_directives() {
+        use super::*;
+        use winnow::Parser;
+
+        let smali = r#".class public La;
+.super Ljava/lang/Object;
+.source "A.java"
+.foobar 1
+
+# interfaces
+.implements Ljava/lang/Runnable;
+.after-interfaces
+
+# annotations
+.annotation runtime La/Marker;
+.end annotation
+
+.after-annotations 2
+
+
+# static fields
+.field static a:I
+    .in-field
+.end field
+
+.after-a
+
+
+# instance fields
+.field b:I
+
+.after-b
+
+
+# virtual methods
+.method public run()V
+    .locals 0
+    .hiddenapi whitelist    # made up
+    return-void
+.end method
+
+.after-run
+
+"#;
+        assert!(parse_class().parse(smali).is_err());
+
+        let lenient = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let c = parse_class_with(lenient).parse(smali).unwrap();
+        assert_eq!(
+            c.directives,
+            [
+                (DirectivePosition::Header, ".foobar 1".into()),
+                (DirectivePosition::Implements(0), ".after-interfaces".into()),
+                (
+                    DirectivePosition::Annotation(0),
+                    ".after-annotations 2".into()
+                ),
+                (DirectivePosition::Field(0), ".after-a".into()),
+                (DirectivePosition::Field(1), ".after-b".into()),
+                (DirectivePosition::Method(0), ".after-run".into()),
+            ]
+        );
+        assert_eq!(c.fields[0].directives, [".in-field"]);
+        assert!(c.fields[1].directives.is_empty());
+        assert_eq!(c.implements.len(), 1);
+        assert_eq!(
+            c.methods[0].ops[0],
+            Op::UnknownDirective(".hiddenapi whitelist    # made up".into())
+        );
+        assert_eq!(write_class(&c), smali);
+
+        // directives follow their member when the members are reordered
+        let mut sorted = c.clone();
+        sorted.fields.swap(0, 1);
+        sorted.directives.truncate(3);
+        sorted
+            .directives
+            .push((DirectivePosition::Field(1), ".after-a".into()));
+        sorted.sort_members();
+        assert_eq!(sorted.fields[0].param.ident, "a");
+        assert_eq!(
+            sorted.directives[3],
+            (DirectivePosition::Field(0), ".after-a".into())
+        );
+
+        // known directives are never swallowed, even when malformed
+        let malformed = ".class public La;\n.super Ljava/lang/Object;\n.implements 1\n";
+        assert!(parse_class_with(lenient).parse(malformed).is_err());
+    }
+
+    #[test]
+    fn test_implements_order() {
+        use super::*;
+        use winnow::Parser;
+
+        let smali = r#".class public La;
+.super Ljava/lang/Object;
+
+# interfaces
+.implements Ljava/lang/Runnable;
+.implements Ljava/lang/Comparable;
+.implements Ljava/io/Serializable;
+"#;
+        let c = parse_class().parse(smali).unwrap();
+        let interfaces: Vec<_> = c.implements.iter().map(|i| i.as_jni_type()).collect();
+        assert_eq!(
+            interfaces,
+            [
+                "Ljava/lang/Runnable;",
+                "Ljava/lang/Comparable;",
+                "Ljava/io/Serializable;"
+            ]
+        );
+        assert_eq!(write_class(&c), smali);
+
+        let c = parse_class()
+            .parse(".class public La;\n.super Ljava/lang/Object;\n")
+            .unwrap();
+        assert!(!write_class(&c).contains("# interfaces"));
+    }
+
+    #[test]
+    fn test_build_from_owned_strings() {
+        use super::*;
+        use crate::{
+            field_ref::FieldRef,
+            op::Label,
+            op::dex_op::{DexOp, FieldValueType, Register, ReturnType, StaticFieldAccessType},
+        };
+        use winnow::Parser;
+
+        // everything is made from `String`s dropped by the end, so the class can't
+        // borrow from any of them
+        fn build(package: String, names: &[(String, String)]) -> Class<'static> {
+            let name = ObjectIdentifier::new(format!("{package}/Names"));
+            let string = TypeSignature::Object(Box::new(ObjectIdentifier::new(
+                "java/lang/String".to_string(),
+            )));
+            let mut c = Class::new(name.clone(), ObjectIdentifier::new("java/lang/Object"));
+            c.meta.modifiers = vec![Modifier::Public, Modifier::Final];
+            for (n, _) in names {
+                let mut f = Field::new(n.to_uppercase(), string.clone());
+                f.modifiers = vec![Modifier::Public, Modifier::Static];
+                c.fields.push(f);
+            }
+            let mut clinit = Method::new("<clinit>".to_string(), MethodSignature::from_jni("()V"));
+            clinit.modifiers = vec![Modifier::Static, Modifier::Constructor];
+            clinit.set_locals(1);
+            for (n, value) in names {
+                let field = FieldRef::new(name.clone(), n.to_uppercase(), string.clone());
+                clinit.ops.extend([
+                    Op::Op(DexOp::const_string(Register::Local(0), value)),
+                    Op::Op(DexOp::StaticFieldAccess {
+                        access_type: StaticFieldAccessType::Put,
+                        value_type: FieldValueType::Object,
+                        reg: Register::Local(0),
+                        field,
+                    }),
+                ]);
+            }
+            clinit.ops.push(Op::Label(Label::new(String::from("end"))));
+            clinit.ops.push(Op::Op(DexOp::Return {
+                return_type: ReturnType::Void,
+                src: None,
+            }));
+            c.methods.push(clinit);
+            c
+        }
+
+        let c = build(
+            "com/example".to_string(),
+            &[
+                ("alice".to_string(), "Alice".to_string()),
+                ("bob".to_string(), "Bob \"B\"".to_string()),
+            ],
+        );
+        let smali = c.to_smali();
+        assert!(smali.contains(".class public final Lcom/example/Names;"));
+        assert!(smali.contains("const-string v0, \"Bob \\\"B\\\"\""));
+        let parsed = parse_class().parse(&smali).unwrap();
+        assert!(parsed.verify().is_ok());
+        assert_eq!(parsed.into_owned(), c);
+    }
+
+    #[test]
+    fn test_implements_interface() {
+        use super::*;
+        use winnow::Parser;
+
+        let c = parse_class()
+            .parse(
+                r#".class public La;
+.super Ljava/util/AbstractList;
+.implements Ljava/lang/Comparable;
+.implements Ljava/lang/Runnable;
+
+.annotation system Ldalvik/annotation/Signature;
+    value = {
+        "Ljava/util/AbstractList<Ljava/lang/String;>;",
+        "Ljava/lang/Comparable<La;>;",
+        "Ljava/lang/Runnable;"
+    }
+.end annotation
+"#,
+            )
+            .unwrap();
+        let raw = |name: &str| ObjectIdentifier::from_java_type(name);
+        assert!(c.implements_interface(&raw("java.lang.Runnable")));
+        assert!(c.implements_interface(&raw("java.lang.Comparable")));
+        assert!(!c.implements_interface(&raw("java.io.Serializable")));
+        assert!(c.extends(&raw("java.util.AbstractList")));
+        assert!(!c.extends(&raw("java.lang.Object")));
+
+        // generic arguments on either side are ignored
+        let (_, generic) = parse_class()
+            .parse_peek(".class La;\n.super Ljava/util/AbstractList<Ljava/lang/String;>;\n.implements Ljava/lang/Comparable<La;>;\n")
+            .unwrap();
+        assert!(generic.implements_interface(&raw("java.lang.Comparable")));
+        assert!(generic.extends(&raw("java.util.AbstractList")));
+        assert!(c.implements_interface(&generic.implements[0]));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hidden_api_restrictions() {
+        use super::*;
+        use crate::modifier::HiddenApiRestriction;
+        use std::fs;
+        use winnow::Parser;
+
+        let input = fs::read_to_string("tests/ActivityThread.smali").unwrap();
+        let c = parse_class().parse(&input).unwrap();
+        let field = |name: &str| c.fields.iter().find(|f| f.param.ident == name).unwrap();
+        assert_eq!(
+            field("TAG").hidden_api,
+            [HiddenApiRestriction::GreylistMaxO]
+        );
+        assert!(field("mInitialApplication").hidden_api.is_empty());
+        let m = c
+            .methods
+            .iter()
+            .find(|m| m.param.ident == "currentActivityThread")
+            .unwrap();
+        assert_eq!(m.modifiers, [Modifier::Public, Modifier::Static]);
+        assert_eq!(
+            m.hidden_api,
+            [
+                HiddenApiRestriction::Whitelist,
+                HiddenApiRestriction::CorePlatformApi
+            ]
+        );
+        assert!(
+            c.to_smali().contains(
+                ".method public static whitelist core-platform-api currentActivityThread()"
+            )
+        );
+
+        // members named like a restriction are still read as names
+        let c = parse_class()
+            .parse(
+                r#".class public La;
+.super Ljava/lang/Object;
+
+.field public blacklist:I
+
+.method public static greylist()V
+    .locals 0
+    return-void
+.end method
+"#,
+            )
+            .unwrap();
+        assert_eq!(c.fields[0].param.ident, "blacklist");
+        assert!(c.fields[0].hidden_api.is_empty());
+        assert_eq!(c.methods[0].param.ident, "greylist");
+        assert!(c.methods[0].hidden_api.is_empty());
+    }
+
+    #[test]
+    fn test_access_flags() {
+        use super::*;
+        use winnow::Parser;
+
+        let c = parse_class()
+            .parse(
+                r#".class public final La;
+.super Ljava/lang/Object;
+
+.field private static final x:I
+
+.method public constructor <init>()V
+    .locals 0
+    return-void
+.end method
+
+.method protected varargs native run([I)V
+.end method
+"#,
+            )
+            .unwrap();
+        assert_eq!(c.access_flags(), 0x11);
+        assert_eq!(c.fields[0].access_flags(), 0x1a);
+        assert_eq!(c.methods[0].access_flags(), 0x10001);
+        assert_eq!(c.methods[1].access_flags(), 0x184);
+    }
+
+    #[test]
+    fn test_sort_members() {
+        use super::*;
+        use winnow::Parser;
+
+        let mut c = parse_class()
+            .parse(
+                r#".class public La;
+.super Ljava/lang/Object;
+
+.field private y:I
+
+.field private static b:J
+
+.field private x:I
+
+.method public run()V
+    .locals 0
+    return-void
+.end method
+
+.method public get(I)I
+    .locals 0
+    return p1
+.end method
+
+.method public get()I
+    .locals 1
+    const/4 v0, 0x0
+    return v0
+.end method
+
+.method public constructor <init>()V
+    .locals 0
+    return-void
+.end method
+"#,
+            )
+            .unwrap();
+        c.sort_members();
+        let fields: Vec<_> = c.fields.iter().map(|f| f.name()).collect();
+        assert_eq!(fields, ["b", "x", "y"]);
+        let methods: Vec<_> = c.methods.iter().map(|m| m.sort_key()).collect();
+        assert_eq!(
+            methods,
+            [
+                ("<init>".to_string(), "()V".to_string()),
+                ("get".to_string(), "()I".to_string()),
+                ("get".to_string(), "(I)I".to_string()),
+                ("run".to_string(), "()V".to_string()),
+            ]
+        );
+        let sorted = write_class(&c);
+        c.sort_members();
+        assert_eq!(write_class(&c), sorted);
+
+        // a generic signature sorts and matches by its erased descriptor
+        let generic = Method::new(
+            "id",
+            MethodSignature::from_jni("<T:Ljava/lang/Object;>(TT;)TT;"),
+        );
+        assert_eq!(
+            generic.sort_key(),
+            (
+                "id".to_string(),
+                "(Ljava/lang/Object;)Ljava/lang/Object;".to_string()
+            )
+        );
+        assert_eq!(
+            method_key(&generic),
+            "id(Ljava/lang/Object;)Ljava/lang/Object;"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        use super::*;
+        use winnow::Parser;
+
+        let parse = |smali: &'static str| parse_class().parse(smali).unwrap();
+        let mut c = parse(
+            r#".class public La;
+.super Ljava/lang/Object;
+
+.field private x:I
+
+.method public get()I
+    .locals 1
+    iget v0, p0, La;->x:I
+    return v0
+.end method
+"#,
+        );
+        let part = parse(
+            r#".class public La;
+.super Ljava/lang/Object;
+.implements Ljava/lang/Runnable;
+
+.field private x:I
+
+.field private y:J
+
+.method public run()V
+    .locals 0
+    return-void
+.end method
+"#,
+        );
+        c.merge(part.clone()).unwrap();
+        assert_eq!(c.implements.len(), 1);
+        let fields: Vec<_> = c.fields.iter().map(|f| f.name()).collect();
+        assert_eq!(fields, ["x", "y"]);
+        let methods: Vec<_> = c.methods.iter().map(|m| m.name()).collect();
+        assert_eq!(methods, ["get", "run"]);
+        // merging the same part again changes nothing
+        let merged = c.clone();
+        c.merge(part).unwrap();
+        assert_eq!(c, merged);
+
+        let e = c
+            .merge(parse(".class public Lb;\n.super Ljava/lang/Object;\n"))
+            .unwrap_err();
+        assert_eq!(e.details, "cannot merge Lb; into La;");
+
+        let e = c
+            .merge(parse(
+                ".class public La;\n.super Ljava/lang/Object;\n\n.field public x:I\n",
+            ))
+            .unwrap_err();
+        assert_eq!(e.details, "La;: conflicting definitions of field x:I");
+        assert_eq!(c, merged);
+
+        let e = c
+            .merge(parse(".class public La;\n.super Lb;\n"))
+            .unwrap_err();
+        assert_eq!(
+            e.details,
+            "La;: conflicting superclasses Ljava/lang/Object; and Lb;"
+        );
+        let e = c
+            .merge(parse(
+                ".class public final La;\n.super Ljava/lang/Object;\n",
+            ))
+            .unwrap_err();
+        assert_eq!(
+            e.details,
+            "La;: conflicting modifiers `public` and `public final`"
+        );
+        assert_eq!(c, merged);
+
+        // directives follow their member, and ones past the end stay there
+        let mut c = parse(".class public La;\n.super Ljava/lang/Object;\n\n.field private x:I\n");
+        let mut part = parse(
+            ".class public La;\n.super Ljava/lang/Object;\n\n.field private y:J\n\n.field private x:I\n",
+        );
+        part.directives = vec![
+            (DirectivePosition::Field(0), ".after-y".into()),
+            (DirectivePosition::Field(1), ".after-x".into()),
+            (DirectivePosition::Field(7), ".last".into()),
+        ];
+        c.merge(part).unwrap();
+        assert_eq!(
+            c.directives,
+            [
+                (DirectivePosition::Field(1), ".after-y".into()),
+                (DirectivePosition::Field(0), ".after-x".into()),
+                (DirectivePosition::Field(2), ".last".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_members() {
+        use super::*;
+        use winnow::Parser;
+
+        let smali = r#".class public La;
+.super Ljava/lang/Object;
+
+.field private a:I
+
+.method private b()I
+    .locals 1
+    iget v0, p0, La;->a:I
+    return v0
+.end method
+
+.method private b(I)I
+    .locals 0
+    return p1
+.end method
+
+.method public run(Lc;)I
+    .locals 1
+    invoke-direct {p0}, La;->b()I
+    move-result v0
+    invoke-direct {p0, v0}, La;->b(I)I
+    invoke-virtual {p1}, Lc;->b()I
+    iput v0, p0, La;->a:I
+    iput v0, p1, Lc;->a:I
+    return v0
+.end method
+"#;
+        let mut c = parse_class().parse(smali).unwrap();
+        c.rename_method("b", Some(&MethodSignature::from_jni("()I")), "count");
+        c.rename_field("a", None, "total");
+        let names: Vec<_> = c.methods.iter().map(|m| m.name()).collect();
+        assert_eq!(names, ["count", "b", "run"]);
+        assert_eq!(c.fields[0].name(), "total");
+        let run: Vec<String> = c.methods[2]
+            .instructions()
+            .map(|(_, op)| op.to_string())
+            .collect();
+        assert_eq!(
+            run,
+            [
+                "invoke-direct {p0}, La;->count()I",
+                "move-result v0",
+                "invoke-direct {p0, v0}, La;->b(I)I",
+                "invoke-virtual {p1}, Lc;->b()I",
+                "iput v0, p0, La;->total:I",
+                "iput v0, p1, Lc;->a:I",
+                "return v0",
+            ]
+        );
+        assert_eq!(
+            c.methods[0].instructions().next().unwrap().1.to_string(),
+            "iget v0, p0, La;->total:I"
+        );
+
+        // without a signature every overload is renamed
+        c.rename_method("b", None, "c");
+        c.rename_method("count", None, "c");
+        assert!(c.methods[..2].iter().all(|m| m.name() == "c"));
+        assert_eq!(c.verify().map_err(|e| e.len()), Ok(()));
+
+        // fields are told apart by type, and annotations refer to members too
+        let smali = r#".class public La;
+.super Ljava/lang/Object;
+
+.annotation runtime La/Tag;
+    handler = La;->b()I
+    values = {
+        .enum La;->a:I,
+        .enum La;->a:J
+    }
+.end annotation
+
+.field private a:I
+
+.field private a:J
+
+.method private b()I
+    .locals 2
+    iget v0, p0, La;->a:I
+    iget-wide v0, p0, La;->a:J
+    return v0
+.end method
+"#;
+        let mut c = parse_class().parse(smali).unwrap();
+        c.rename_field("a", Some(&TypeSignature::Long), "wide");
+        c.rename_method("b", None, "c");
+        let written = write_class(&c);
+        for renamed in [
+            "handler = La;->c()I",
+            ".enum La;->a:I,",
+            ".enum La;->wide:J",
+            ".field private a:I",
+            ".field private wide:J",
+            "iget v0, p0, La;->a:I",
+            "iget-wide v0, p0, La;->wide:J",
+        ] {
+            assert!(written.contains(renamed), "{renamed} in {written}");
+        }
+    }
+
+    #[test]
+    fn test_dependencies() {
+        use super::*;
+        use winnow::Parser;
+
+        let smali = r#".class public Lcom/example/Repo;
+.super Lcom/example/Base;
+.implements Ljava/io/Closeable;
+
+.annotation system Ldalvik/annotation/MemberClasses;
+    value = {
+        Lcom/example/Repo$Entry;
+    }
+.end annotation
+
+.field private cache:Ljava/util/Map;
+.field private ids:[J
+.field private self:Lcom/example/Repo;
+
+.method public load(Ljava/lang/String;)[Lcom/example/Item;
+    .locals 2
+    .annotation runtime Lcom/example/Traced;
+        level = .enum Lcom/example/Level;->DEBUG:Lcom/example/Level;
+    .end annotation
+    :try_start_0
+    new-instance v0, Ljava/util/ArrayList;
+    invoke-direct {v0}, Ljava/util/ArrayList;-><init>()V
+    const-class v1, Lcom/example/Item;
+    sget-object v1, Lcom/example/Config;->DEFAULT:Lcom/example/Settings;
+    check-cast v0, Ljava/util/List;
+    invoke-static {v0}, Lcom/example/Util;->toArray(Ljava/util/List;)[Lcom/example/Item;
+    move-result-object v0
+    :try_end_0
+    .catch Ljava/io/IOException; {:try_start_0 .. :try_end_0} :catch_0
+    return-object v0
+    :catch_0
+    const/4 v0, 0x0
+    return-object v0
+.end method
+"#;
+        let c = parse_class().parse(smali).unwrap();
+        let deps: Vec<String> = c.dependencies().iter().map(|o| o.as_jni_type()).collect();
+        assert_eq!(
+            deps,
+            [
+                "Lcom/example/Base;",
+                "Lcom/example/Config;",
+                "Lcom/example/Item;",
+                "Lcom/example/Level;",
+                "Lcom/example/Repo$Entry;",
+                "Lcom/example/Settings;",
+                "Lcom/example/Traced;",
+                "Lcom/example/Util;",
+                "Ldalvik/annotation/MemberClasses;",
+                "Ljava/io/Closeable;",
+                "Ljava/io/IOException;",
+                "Ljava/lang/String;",
+                "Ljava/util/ArrayList;",
+                "Ljava/util/List;",
+                "Ljava/util/Map;",
+            ]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_crlf_output() {
+        use super::*;
+        use winnow::Parser;
+
+        let style = FormatStyle {
+            line_ending: LineEnding::Crlf,
+            ..Default::default()
+        };
+        for dir in fs::read_dir("tests").unwrap() {
+            let smali = fs::read_to_string(dir.unwrap().path()).unwrap();
+            let c = parse_class().parse(smali.as_str()).unwrap();
+            let lf = write_class(&c);
+            let crlf = write_class_with(&c, &style);
+            assert_eq!(crlf, lf.replace('\n', "\r\n"));
+            assert_eq!(crlf.matches('\n').count(), crlf.matches("\r\n").count());
+            assert_eq!(parse_class().parse(crlf.as_str()), Ok(c));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_to_smali_into() {
+        use super::*;
+        use winnow::Parser;
+
+        let mut buf = String::new();
+        for dir in fs::read_dir("tests").unwrap() {
+            let smali = fs::read_to_string(dir.unwrap().path()).unwrap();
+            let c = parse_class().parse(smali.as_str()).unwrap();
+            buf.clear();
+            c.to_smali_into(&mut buf);
+            assert_eq!(buf, write_class(&c));
+        }
+
+        let mut buf = "# prefix\n".to_string();
+        let c = parse_class()
+            .parse(".class LA;\n.super Ljava/lang/Object;\n")
+            .unwrap();
+        c.to_smali_into(&mut buf);
+        assert_eq!(buf, format!("# prefix\n{}", c.to_smali()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parse_classes() {
+        use super::*;
+
+        let mut stream = String::new();
+        let mut expected = vec![];
+        for dir in fs::read_dir("tests").unwrap() {
+            let smali = fs::read_to_string(dir.unwrap().path()).unwrap();
+            expected.push(parse_class_complete(&smali).unwrap().into_owned());
+            stream.push_str(&smali);
+            stream.push_str("\n# next\n\n");
+        }
+        assert_eq!(parse_classes(&stream).unwrap(), expected);
+        assert_eq!(parse_classes("  \n# nothing\n").unwrap(), vec![]);
+
+        let stream = ".class La;\n.super Ljava/lang/Object;\n\n.class Lb;\n.sup La;\n";
+        let e = parse_classes(stream).unwrap_err();
+        assert_eq!(e.details, "failed to parse class at line 5 (byte 49)");
+    }
+
+    #[test]
+    fn test_class_modifiers() {
+        use super::*;
+        use winnow::Parser;
+
+        for (input, modifiers) in [
+            (".class La;\n.super Ljava/lang/Object;\n", vec![]),
+            (
+                ".class public interface abstract Lcom/example/Api;\n.super Ljava/lang/Object;\n",
+                vec![Modifier::Public, Modifier::Interface, Modifier::Abstract],
+            ),
+            (
+                ".class abstract interface La/b;\n.super Ljava/lang/Object;\n",
+                vec![Modifier::Abstract, Modifier::Interface],
+            ),
+            (
+                ".class final synthetic Lx$1;\n.super Ljava/lang/Object;\n",
+                vec![Modifier::Final, Modifier::Synthetic],
+            ),
+        ] {
+            let c = parse_class().parse(input).unwrap();
+            assert_eq!(c.meta.modifiers, modifiers, "{input}");
+            assert_eq!(write_class(&c), input);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_back_to_back_methods() {
+        use super::*;
+        use winnow::Parser;
+
+        // minified smali without blank lines between members
+        let smali = fs::read_to_string("tests/Minified.smali").unwrap();
+        let c = parse_class().parse(smali.as_str()).unwrap();
+        assert_eq!(c.fields.len(), 2);
+        let names: Vec<_> = c.methods.iter().map(|m| m.name()).collect();
+        assert_eq!(names, ["<init>", "a", "b"]);
+        let written = c.to_smali();
+        assert_eq!(parse_class().parse(written.as_str()), Ok(c));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_group_banners() {
+        use super::*;
+        use winnow::Parser;
+
+        // the `# ... fields` and `# ... methods` banners and the members under them
+        let outline = |smali: &str| -> Vec<String> {
+            smali
+                .lines()
+                .filter(|l| {
+                    l.starts_with("# ") || l.starts_with(".field") || l.starts_with(".method")
+                })
+                .map(str::to_string)
+                .collect()
+        };
+
+        let smali = fs::read_to_string("tests/Banners.smali").unwrap();
+        let c = parse_class().parse(smali.as_str()).unwrap();
+        let written = write_class(&c);
+        assert_eq!(outline(&written), outline(&smali));
+        assert_eq!(parse_class().parse(written.as_str()), Ok(c));
+    }
+
+    #[test]
+    fn test_interleaved_members_grouped() {
+        use super::*;
+        use winnow::Parser;
+
+        let c = parse_class()
+            .parse(
+                r#".class public La;
+.super Ljava/lang/Object;
+
+.field a:I
+.field static b:I
+.field c:I
+
+.method public constructor <init>()V
+    .locals 0
+    return-void
+.end method
+
+.method public run()V
+    .locals 0
+    return-void
+.end method
+
+.method private helper()V
+    .locals 0
+    return-void
+.end method
+
+.method public stop()V
+    .locals 0
+    return-void
+.end method
+"#,
+            )
+            .unwrap();
+        let written = c.to_smali();
+        let outline: Vec<&str> = written
+            .lines()
+            .filter(|l| l.starts_with("# ") || l.starts_with(".field") || l.starts_with(".method"))
+            .collect();
+        assert_eq!(
+            outline,
+            [
+                "# static fields",
+                ".field static b:I",
+                "# instance fields",
+                ".field a:I",
+                ".field c:I",
+                "# direct methods",
+                ".method public constructor <init>()V",
+                ".method private helper()V",
+                "# virtual methods",
+                ".method public run()V",
+                ".method public stop()V",
+            ]
+        );
+
+        // so the members come back in the written order, not the parsed one
+        let reparsed = parse_class().parse(written.as_str()).unwrap();
+        let names = |c: &Class| -> Vec<String> {
+            c.fields
+                .iter()
+                .map(|f| f.name().to_string())
+                .chain(c.methods.iter().map(|m| m.name().to_string()))
+                .collect()
+        };
+        assert_eq!(
+            names(&c),
+            ["a", "b", "c", "<init>", "run", "helper", "stop"]
+        );
+        assert_eq!(
+            names(&reparsed),
+            ["b", "a", "c", "<init>", "helper", "run", "stop"]
+        );
+        assert_ne!(reparsed, c);
+        assert_eq!(reparsed.to_smali(), written);
+    }
 }
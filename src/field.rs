@@ -1,17 +1,25 @@
-use std::borrow::Cow;
+use alloc::borrow::Cow;
 
 use winnow::{
     ModalParser, Parser,
-    combinator::{delimited, opt, preceded, repeat},
-    error::InputError,
+    combinator::{empty, opt, preceded},
     token::{literal, one_of, take_while},
 };
 
 use crate::{
-    annotation::{Annotation, parse_annotation},
-    modifier::{Modifier, parse_modifiers},
-    signature::type_signature::{TypeParameter, parse_type_parameter},
-    ws,
+    ParseFailure, ParseOptions, SmaliError,
+    annotation::{Annotation, parse_annotation_with},
+    class::Class,
+    fit_bits,
+    modifier::{
+        HiddenApiRestriction, Modifier, modifiers_to_flags, parse_hidden_api_restrictions,
+        parse_modifiers,
+    },
+    op::parse_unknown_directive,
+    parse_int_lit,
+    prelude::*,
+    signature::type_signature::{TypeParameter, TypeSignature, parse_type_parameter_with},
+    unescape_string, ws,
 };
 
 /// Struct representing a Java field
@@ -20,35 +28,226 @@ use crate::{
 pub struct Field<'a> {
     /// Any modifiers
     pub modifiers: Vec<Modifier>,
+    /// Hidden API restrictions of a framework field, empty for any other
+    pub hidden_api: Vec<HiddenApiRestriction>,
     /// Type signature of the field
     pub param: TypeParameter<'a>,
     /// If an initialiser is included
     pub initial_value: Option<Cow<'a, str>>,
     /// Field level annotations
     pub annotations: Vec<Annotation<'a>>,
+    /// Directives inside the field block that the crate doesn't model, each kept as its
+    /// whole line when parsing with [`ParseOptions::lenient`](crate::ParseOptions::lenient). They are written after
+    /// the annotations.
+    pub directives: Vec<Cow<'a, str>>,
 }
 
-pub fn parse_field<'a>() -> impl ModalParser<&'a str, Field<'a>, InputError<&'a str>> {
-    delimited(
+/// A field's initial value, typed by the field's declared type
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldValue<'a> {
+    Boolean(bool),
+    Byte(i8),
+    Short(i16),
+    Char(char),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    /// A string literal, still escaped as in the source
+    String(Cow<'a, str>),
+    Null,
+    /// Any other value, e.g. of an object type other than `String`
+    Other(Cow<'a, str>),
+}
+
+impl<'a> Field<'a> {
+    /// A field `name` of type `ty`, without modifiers or an initial value
+    pub fn new(name: impl Into<Cow<'a, str>>, ty: TypeSignature<'a>) -> Self {
+        Field {
+            modifiers: vec![],
+            hidden_api: vec![],
+            param: TypeParameter {
+                ident: name.into(),
+                ts: ty,
+            },
+            initial_value: None,
+            annotations: vec![],
+            directives: vec![],
+        }
+    }
+
+    /// Copies any borrowed text, detaching the field from the parsed source
+    pub fn into_owned(self) -> Field<'static> {
+        Field {
+            modifiers: self.modifiers,
+            hidden_api: self.hidden_api,
+            param: self.param.into_owned(),
+            initial_value: self.initial_value.map(|v| Cow::Owned(v.into_owned())),
+            annotations: self
+                .annotations
+                .into_iter()
+                .map(Annotation::into_owned)
+                .collect(),
+            directives: self
+                .directives
+                .into_iter()
+                .map(|d| Cow::Owned(d.into_owned()))
+                .collect(),
+        }
+    }
+
+    /// Parses the initial value according to the field's type, consuming the postfix
+    /// baksmali writes for it: `t` for bytes, `s` for shorts, `L` for longs and `f`
+    /// for floats. Doubles are written without one, but a `d` is accepted.
+    pub fn value(&self) -> Result<Option<FieldValue<'_>>, SmaliError> {
+        let Some(raw) = self.initial_value.as_deref() else {
+            return Ok(None);
+        };
+        let raw = raw.trim();
+        let invalid = || SmaliError {
+            details: format!(
+                "{}: invalid {} value {raw}",
+                self.param.ident,
+                self.param.ts.to_jni()
+            ),
+        };
+        let int = |postfix: &[char], bits: u32| {
+            let digits = raw.strip_suffix(postfix).unwrap_or(raw);
+            let value = parse_int_lit::<i64>()
+                .parse(digits)
+                .map_err(|_| invalid())?;
+            fit_bits(value, bits).map_err(|_| invalid())
+        };
+        let value = match &self.param.ts {
+            TypeSignature::Bool => match raw {
+                "true" => FieldValue::Boolean(true),
+                "false" => FieldValue::Boolean(false),
+                _ => return Err(invalid()),
+            },
+            TypeSignature::Byte => FieldValue::Byte(int(&['t', 'T'], 8)? as i8),
+            TypeSignature::Short => FieldValue::Short(int(&['s', 'S'], 16)? as i16),
+            TypeSignature::Int => FieldValue::Int(int(&[], 32)? as i32),
+            TypeSignature::Long => FieldValue::Long(int(&['L', 'l'], 64)?),
+            TypeSignature::Char => FieldValue::Char(parse_char_lit(raw).ok_or_else(invalid)?),
+            TypeSignature::Float => FieldValue::Float(
+                raw.strip_suffix(['f', 'F'])
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(invalid)?,
+            ),
+            TypeSignature::Double => FieldValue::Double(
+                raw.strip_suffix(['d', 'D'])
+                    .unwrap_or(raw)
+                    .parse()
+                    .map_err(|_| invalid())?,
+            ),
+            _ if raw == "null" => FieldValue::Null,
+            TypeSignature::Object(o) if o.class_name == "java/lang/String" => FieldValue::String(
+                raw.strip_prefix('"')
+                    .and_then(|s| s.strip_suffix('"'))
+                    .ok_or_else(invalid)?
+                    .into(),
+            ),
+            _ => FieldValue::Other(raw.into()),
+        };
+        Ok(Some(value))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.param.ident
+    }
+
+    pub fn type_signature(&self) -> &TypeSignature<'a> {
+        &self.param.ts
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.modifiers.contains(&Modifier::Static)
+    }
+
+    /// The field's modifiers as dex `access_flags`
+    pub fn access_flags(&self) -> u32 {
+        modifiers_to_flags(&self.modifiers)
+    }
+
+    /// The name and type descriptor, e.g. `("count", "I")`, which order the field in
+    /// [`Class::sort_members`]
+    pub fn sort_key(&self) -> (String, String) {
+        (self.param.ident.to_string(), self.param.ts.to_jni())
+    }
+
+    /// Whether this is one of the constants of the enum `class`: a static final
+    /// `enum` field of the enum's own type. The synthetic `$VALUES` array is not, nor
+    /// is a plain constant such as `static final Color DEFAULT = RED`.
+    pub fn is_enum_constant(&self, class: &Class) -> bool {
+        class.is_enum()
+            && self.is_static()
+            && self.modifiers.contains(&Modifier::Final)
+            && self.modifiers.contains(&Modifier::Enum)
+            && self.param.ts.to_jni() == class.meta.name.as_jni_type()
+    }
+}
+
+/// Parses a quoted char literal such as `'a'`, `'\n'` or `'\u00e9'`
+fn parse_char_lit(raw: &str) -> Option<char> {
+    let inner = raw.strip_prefix('\'')?.strip_suffix('\'')?;
+    let decoded = unescape_string(inner).ok()?;
+    let mut chars = decoded.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+pub fn parse_field<'a>() -> impl ModalParser<&'a str, Field<'a>, ParseFailure> {
+    parse_field_with(ParseOptions::default())
+}
+
+/// Parses a field as `options` say, e.g. keeping directives it doesn't know
+pub fn parse_field_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, Field<'a>, ParseFailure> {
+    let mut header = preceded(
         ws(literal(".field")),
         (
             parse_modifiers(),
-            parse_type_parameter(),
+            parse_hidden_api_restrictions(),
+            parse_type_parameter_with(options),
             opt(preceded(
                 ws(one_of('=')),
                 // TODO: This can be any type, needed fixes
-                take_while(0.., |c| c != '\n').map(Cow::Borrowed),
+                take_while(0.., |c| c != '\n' && c != '\r').map(Cow::Borrowed),
             )),
-            repeat(0.., parse_annotation()),
         ),
-        ws(opt(literal(".end field"))),
-    )
-    .map(|(modifiers, param, i, annotations)| Field {
-        modifiers,
-        param,
-        initial_value: i,
-        annotations,
-    })
+    );
+    move |input: &mut &'a str| {
+        let (modifiers, hidden_api, param, initial_value) = header.parse_next(input)?;
+        let mut annotations = vec![];
+        let mut directives = vec![];
+        // where the field ends if there is no `.end field`: after its last annotation
+        let (mut end, mut kept) = (*input, 0);
+        loop {
+            if let Some(a) = opt(parse_annotation_with(options)).parse_next(input)? {
+                annotations.push(a);
+                (end, kept) = (*input, directives.len());
+            } else if let Some(d) = opt(parse_unknown_directive(options)).parse_next(input)? {
+                directives.push(Cow::Borrowed(d));
+            } else {
+                break;
+            }
+        }
+        if opt(ws(literal(".end field"))).parse_next(input)?.is_none() {
+            // any directives after that belong to the class
+            *input = end;
+            directives.truncate(kept);
+            ws(empty).parse_next(input)?;
+        }
+        Ok(Field {
+            modifiers,
+            hidden_api,
+            param,
+            initial_value,
+            annotations,
+            directives,
+        })
+    }
 }
 
 mod tests {
@@ -63,8 +262,102 @@ mod tests {
         let f = parse_field()
             .parse_next(&mut ".field private final body:Lokhttp3/ResponseBody;")
             .unwrap();
-        assert_eq!(f.param.ident, "body".to_string());
+        assert_eq!(f.param.ident, "body");
         assert_eq!(f.modifiers.len(), 2);
         assert_eq!(f.param.ts.to_jni(), "Lokhttp3/ResponseBody;");
     }
+
+    #[test]
+    fn test_enum_constants() {
+        use crate::{class::parse_class, prelude::*, signature::type_signature::TypeSignature};
+        use winnow::Parser;
+
+        let smali = r#".class public final enum Lcom/example/Color;
+.super Ljava/lang/Enum;
+
+.field private static final synthetic $VALUES:[Lcom/example/Color;
+.field public static final enum RED:Lcom/example/Color;
+.field public static final enum GREEN:Lcom/example/Color;
+.field public static DEFAULT:Lcom/example/Color;
+.field public static final FALLBACK:Lcom/example/Color;
+.field private final rgb:I
+"#;
+        let c = parse_class().parse(smali).unwrap();
+        assert!(c.is_enum());
+        let constants: Vec<_> = c
+            .fields
+            .iter()
+            .filter(|f| f.is_enum_constant(&c))
+            .map(|f| f.name())
+            .collect();
+        assert_eq!(constants, ["RED", "GREEN"]);
+        assert_eq!(c.fields[5].name(), "rgb");
+        assert_eq!(c.fields[5].type_signature(), &TypeSignature::Int);
+
+        let c = parse_class()
+            .parse(".class public final Lcom/example/Color;\n.super Ljava/lang/Object;\n.field public static final RED:Lcom/example/Color;\n")
+            .unwrap();
+        assert!(!c.is_enum());
+        assert!(!c.fields[0].is_enum_constant(&c));
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn test_field_values() {
+        use crate::field::{FieldValue, parse_field};
+        use winnow::Parser;
+
+        for (smali, value) in [
+            (".field static final D:D = 3.14", FieldValue::Double(3.14)),
+            (
+                ".field static final E:D = -1.0E-10d",
+                FieldValue::Double(-1.0e-10),
+            ),
+            (
+                ".field static final J:J = 0x100000000L",
+                FieldValue::Long(0x1_0000_0000),
+            ),
+            (
+                ".field static final MIN:J = -0x8000000000000000L",
+                FieldValue::Long(i64::MIN),
+            ),
+            (".field static final F:F = 1.5f", FieldValue::Float(1.5)),
+            (
+                ".field static final INF:F = -Infinityf",
+                FieldValue::Float(f32::NEG_INFINITY),
+            ),
+            (".field static final I:I = 0x42", FieldValue::Int(0x42)),
+            (".field static final M:I = 0xffffffff", FieldValue::Int(-1)),
+            (".field static final S:S = -0x2s", FieldValue::Short(-2)),
+            (".field static final B:B = 0x7ft", FieldValue::Byte(127)),
+            (".field static final Z:Z = true", FieldValue::Boolean(true)),
+            (".field static final C:C = '\\u00e9'", FieldValue::Char('é')),
+            (".field static final NL:C = '\\n'", FieldValue::Char('\n')),
+            (
+                ".field static final TAG:Ljava/lang/String; = \"Main\"",
+                FieldValue::String("Main".into()),
+            ),
+            (
+                ".field static final O:Ljava/lang/Object; = null",
+                FieldValue::Null,
+            ),
+        ] {
+            let f = parse_field().parse(smali).unwrap();
+            assert_eq!(f.value().unwrap(), Some(value), "{smali}");
+        }
+
+        for smali in [
+            ".field static final B:B = 0x100t",
+            ".field static final I:I = 1.5",
+            ".field static final F:F = 1.5",
+            ".field static final Z:Z = 1",
+            ".field static final C:C = 'ab'",
+        ] {
+            let f = parse_field().parse(smali).unwrap();
+            assert!(f.value().is_err(), "{smali}");
+        }
+
+        let f = parse_field().parse(".field private count:I").unwrap();
+        assert_eq!(f.value().unwrap(), None);
+    }
 }
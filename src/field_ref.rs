@@ -1,9 +1,12 @@
-use std::fmt;
-use winnow::{ModalParser, Parser, combinator::terminated, error::InputError, token::literal};
+use alloc::borrow::Cow;
+use core::{fmt, str::FromStr};
+use winnow::{ModalParser, Parser, combinator::terminated, token::literal};
 
 use crate::{
-    object_identifier::{ObjectIdentifier, parse_object_identifier},
-    signature::type_signature::{TypeParameter, parse_type_parameter},
+    ParseFailure, ParseOptions, SmaliError,
+    object_identifier::{ObjectIdentifier, parse_object_identifier_with},
+    parse_complete,
+    signature::type_signature::{TypeParameter, TypeSignature, parse_type_parameter_with},
 };
 
 /// A symbolic reference to a field.
@@ -14,6 +17,39 @@ pub struct FieldRef<'a> {
     pub param: TypeParameter<'a>,
 }
 
+impl<'a> FieldRef<'a> {
+    /// A reference to the field `name` of type `ty` declared in `class`
+    pub fn new(
+        class: ObjectIdentifier<'a>,
+        name: impl Into<Cow<'a, str>>,
+        ty: TypeSignature<'a>,
+    ) -> Self {
+        FieldRef {
+            class,
+            param: TypeParameter {
+                ident: name.into(),
+                ts: ty,
+            },
+        }
+    }
+
+    /// Copies any borrowed text, detaching the reference from the parsed source
+    pub fn into_owned(self) -> FieldRef<'static> {
+        FieldRef {
+            class: self.class.into_owned(),
+            param: self.param.into_owned(),
+        }
+    }
+}
+
+/// Parses a reference such as `Lcom/example/MyClass;->myField:I`
+impl FromStr for FieldRef<'static> {
+    type Err = SmaliError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(s, "field reference", parse_field_ref()).map(FieldRef::into_owned)
+    }
+}
+
 impl fmt::Display for FieldRef<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Example: Lcom/example/MyClass;->myField:I
@@ -22,15 +58,51 @@ impl fmt::Display for FieldRef<'_> {
             "{}->{}:{}",
             self.class,
             self.param.ident,
-            self.param.ts.to_jni()
+            self.param.ts.descriptor()
         )
     }
 }
 
-pub fn parse_field_ref<'a>() -> impl ModalParser<&'a str, FieldRef<'a>, InputError<&'a str>> {
+pub fn parse_field_ref<'a>() -> impl ModalParser<&'a str, FieldRef<'a>, ParseFailure> {
+    parse_field_ref_with(ParseOptions::default())
+}
+
+/// Parses a field reference with the nesting limit of `options`
+pub fn parse_field_ref_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, FieldRef<'a>, ParseFailure> {
     (
-        terminated(parse_object_identifier(), literal("->")),
-        parse_type_parameter(),
+        terminated(parse_object_identifier_with(options), literal("->")),
+        parse_type_parameter_with(options),
     )
         .map(|(class, param)| FieldRef { class, param })
 }
+
+mod tests {
+    #[test]
+    fn field_ref_from_str() {
+        use super::*;
+        use crate::prelude::*;
+
+        let r: FieldRef = "Lcom/example/MyClass;->myField:I".parse().unwrap();
+        assert_eq!(
+            r,
+            FieldRef::new(
+                ObjectIdentifier::from_java_type("com.example.MyClass"),
+                "myField",
+                TypeSignature::Int
+            )
+        );
+        assert_eq!(r.to_string(), "Lcom/example/MyClass;->myField:I");
+        assert!(
+            "Lcom/example/MyClass;->myField"
+                .parse::<FieldRef>()
+                .is_err()
+        );
+        assert!(
+            "Lcom/example/MyClass;->myField:I trailing"
+                .parse::<FieldRef>()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! Options controlling how smali is written out
+
+use crate::{op::write_signed_hex, prelude::*};
+
+/// Layout choices for the smali writers. The default matches baksmali's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatStyle {
+    /// How `packed-switch` and `sparse-switch` payload keys are written
+    pub switch_keys: KeyRadix,
+    /// What ends each line
+    pub line_ending: LineEnding,
+}
+
+/// Radix for writing integer keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyRadix {
+    /// `0x10`, with negative keys signed, e.g. `-0x1`
+    #[default]
+    Hex,
+    /// Signed decimal, e.g. `-1`
+    Decimal,
+}
+
+impl KeyRadix {
+    pub(crate) fn format(self, key: i32) -> String {
+        match self {
+            KeyRadix::Hex => {
+                let mut s = String::new();
+                // writing to a `String` can't fail
+                let _ = write_signed_hex(&mut s, key.into());
+                s
+            }
+            KeyRadix::Decimal => key.to_string(),
+        }
+    }
+}
+
+/// Line terminator for the written smali
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`, to match files checked out on Windows
+    Crlf,
+}
+
+impl LineEnding {
+    /// Rewrites the `\n` line ends of `out[start..]`. The writers emit `\n` only as a
+    /// line end, so this is safe to run on their output.
+    pub(crate) fn apply(self, out: &mut String, start: usize) {
+        if self == LineEnding::Crlf {
+            let lines = out.split_off(start);
+            out.push_str(&lines.replace('\n', "\r\n"));
+        }
+    }
+}
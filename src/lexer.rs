@@ -0,0 +1,326 @@
+//! A tokenizer for syntax highlighting: splits smali text into classified spans
+//! without building the AST, and never fails on malformed input.
+
+use core::{ops::Range, str::FromStr};
+
+use winnow::Parser;
+
+use crate::{
+    comment, modifier::Modifier, object_identifier::parse_object_identifier, parse_string_lit,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// `.method`, `.locals`, `.end`, ...
+    Directive,
+    /// The instruction mnemonic starting a line, e.g. `invoke-virtual`
+    Opcode,
+    Register,
+    Label,
+    /// A string or char literal, quotes included
+    String,
+    Comment,
+    /// A type descriptor such as `I`, `[B` or `Ljava/lang/String;`
+    Type,
+    Number,
+    /// Modifiers, annotation visibilities and `true`/`false`/`null`
+    Keyword,
+    /// Method, field and parameter names and any other bare word
+    Identifier,
+    /// `{`, `}`, `(`, `)`, `,`, `->`, `..`, `=`, `:` and `@`
+    Punctuation,
+    /// Anything else, one character at a time
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte range of the token in the input
+    pub span: Range<usize>,
+}
+
+/// Splits `input` into tokens, skipping whitespace. Every other byte of the input
+/// belongs to exactly one token.
+pub fn tokenize(input: &str) -> impl Iterator<Item = Token> + '_ {
+    Tokens {
+        input,
+        pos: 0,
+        line_start: true,
+        member: false,
+        types: 0,
+    }
+}
+
+struct Tokens<'a> {
+    input: &'a str,
+    pos: usize,
+    /// Nothing but whitespace since the last newline
+    line_start: bool,
+    /// The previous token was a member name, so `(` or `:` opens its descriptor
+    member: bool,
+    /// How many upcoming words are types: a field type, or the parameters and return
+    /// type of a method descriptor (`usize::MAX` until its `)`)
+    types: usize,
+}
+
+const KEYWORDS: [&str; 7] = [
+    "true",
+    "false",
+    "null",
+    "system",
+    "build",
+    "runtime",
+    "declared-synchronized",
+];
+
+/// Length of the bare word at the start of `s`, which ends at whitespace, a delimiter
+/// or `->`
+fn word_len(s: &str) -> usize {
+    s.char_indices()
+        .find(|&(i, c)| {
+            c.is_whitespace() || "{}(),:=#\"'@;".contains(c) || s[i..].starts_with("->")
+        })
+        .map_or(s.len(), |(i, _)| i)
+}
+
+impl Tokens<'_> {
+    /// Length of the type descriptor at the start of `s`
+    fn type_len(s: &str) -> Option<usize> {
+        let dims = s.len() - s.trim_start_matches('[').len();
+        let rest = &s[dims..];
+        let len = match rest.chars().next()? {
+            'Z' | 'B' | 'C' | 'S' | 'I' | 'J' | 'F' | 'D' | 'V' => 1,
+            'L' => {
+                let class = parse_object_identifier()
+                    .take()
+                    .parse_next(&mut &rest[..])
+                    .ok()?;
+                if class.contains(char::is_whitespace) {
+                    return None;
+                }
+                class.len()
+            }
+            _ => return None,
+        };
+        Some(dims + len)
+    }
+
+    /// Length of the quoted literal at the start of `s`, or of the rest of the line
+    /// if it is never closed
+    fn quoted_len(s: &str) -> usize {
+        if s.starts_with('"') {
+            if let Ok(lit) = parse_string_lit().take().parse_next(&mut &s[..]) {
+                return lit.len();
+            }
+        } else {
+            let mut escaped = false;
+            for (i, c) in s.char_indices().skip(1) {
+                match c {
+                    '\\' if !escaped => escaped = true,
+                    '\'' if !escaped => return i + 1,
+                    '\n' => return i,
+                    _ => escaped = false,
+                }
+            }
+        }
+        s.find('\n').unwrap_or(s.len())
+    }
+
+    fn next_kind(&mut self, rest: &str) -> (TokenKind, usize) {
+        let line_start = core::mem::replace(&mut self.line_start, false);
+        let member = core::mem::replace(&mut self.member, false);
+        let c = rest.chars().next().unwrap_or_default();
+
+        if c == '#' {
+            let len = comment()
+                .take()
+                .parse_next(&mut &rest[..])
+                .map_or(1, str::len);
+            return (TokenKind::Comment, len);
+        }
+        if c == '"' || c == '\'' {
+            return (TokenKind::String, Self::quoted_len(rest));
+        }
+        if self.types > 0 {
+            if c == ')' {
+                self.types = 1;
+                return (TokenKind::Punctuation, 1);
+            }
+            if let Some(len) = Self::type_len(rest) {
+                if self.types != usize::MAX {
+                    self.types -= 1;
+                }
+                return (TokenKind::Type, len);
+            }
+            self.types = 0;
+        }
+        if rest.starts_with("->") || rest.starts_with("..") {
+            return (TokenKind::Punctuation, 2);
+        }
+        if c == ':' {
+            // `name:I` of a field, or `"name":I` of a `.local`, as opposed to a label
+            let before = &self.input[..self.pos];
+            if member
+                || !line_start && !before.ends_with(|c: char| c.is_whitespace() || "{,".contains(c))
+            {
+                self.types = 1;
+                return (TokenKind::Punctuation, 1);
+            }
+            return (TokenKind::Label, 1 + word_len(&rest[1..]));
+        }
+        if c == '(' && member {
+            self.types = usize::MAX;
+            return (TokenKind::Punctuation, 1);
+        }
+        if "{}(),=@".contains(c) {
+            return (TokenKind::Punctuation, 1);
+        }
+        if c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return (TokenKind::Directive, 1 + word_len(&rest[1..]));
+        }
+        if c == '-' || c.is_ascii_digit() {
+            let len = word_len(rest);
+            if len > 0 && (c != '-' || rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
+                return (TokenKind::Number, len);
+            }
+        }
+        if (c == 'L' || c == '[')
+            && let Some(len) = Self::type_len(rest)
+        {
+            return (TokenKind::Type, len);
+        }
+        let len = word_len(rest);
+        if len == 0 {
+            return (TokenKind::Unknown, c.len_utf8());
+        }
+        let word = &rest[..len];
+        let after = &rest[len..];
+        let kind = if after.starts_with(['(', ':']) {
+            self.member = true;
+            TokenKind::Identifier
+        } else if line_start && !after.trim_start().starts_with('=') {
+            TokenKind::Opcode
+        } else if word.len() > 1
+            && word.starts_with(['v', 'p'])
+            && word[1..].bytes().all(|b| b.is_ascii_digit())
+        {
+            TokenKind::Register
+        } else if Modifier::from_str(word).is_ok() || KEYWORDS.contains(&word) {
+            TokenKind::Keyword
+        } else {
+            TokenKind::Identifier
+        };
+        (kind, len)
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let rest = &self.input[self.pos..];
+        let trimmed = rest.trim_start();
+        if rest[..rest.len() - trimmed.len()].contains('\n') {
+            self.line_start = true;
+            self.member = false;
+            self.types = 0;
+        }
+        self.pos += rest.len() - trimmed.len();
+        if trimmed.is_empty() {
+            return None;
+        }
+        let (kind, len) = self.next_kind(trimmed);
+        let start = self.pos;
+        self.pos += len;
+        Some(Token {
+            kind,
+            span: start..self.pos,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_tokenize_instruction() {
+        let line = "    invoke-virtual {p0, v1}, Lfoo/Bar;->baz(I[Ljava/lang/String;)V # call";
+        let tokens: Vec<(TokenKind, &str)> =
+            tokenize(line).map(|t| (t.kind, &line[t.span])).collect();
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Opcode, "invoke-virtual"),
+                (TokenKind::Punctuation, "{"),
+                (TokenKind::Register, "p0"),
+                (TokenKind::Punctuation, ","),
+                (TokenKind::Register, "v1"),
+                (TokenKind::Punctuation, "}"),
+                (TokenKind::Punctuation, ","),
+                (TokenKind::Type, "Lfoo/Bar;"),
+                (TokenKind::Punctuation, "->"),
+                (TokenKind::Identifier, "baz"),
+                (TokenKind::Punctuation, "("),
+                (TokenKind::Type, "I"),
+                (TokenKind::Type, "[Ljava/lang/String;"),
+                (TokenKind::Punctuation, ")"),
+                (TokenKind::Type, "V"),
+                (TokenKind::Comment, "# call"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_lines() {
+        let input = ".field private static count:I = 0x10\n:cond_0\n    if-eqz v0, :cond_0\n    .local v0, \"s\":Ljava/lang/String;\n";
+        let kinds: Vec<(TokenKind, &str)> =
+            tokenize(input).map(|t| (t.kind, &input[t.span])).collect();
+        assert_eq!(
+            kinds,
+            [
+                (TokenKind::Directive, ".field"),
+                (TokenKind::Keyword, "private"),
+                (TokenKind::Keyword, "static"),
+                (TokenKind::Identifier, "count"),
+                (TokenKind::Punctuation, ":"),
+                (TokenKind::Type, "I"),
+                (TokenKind::Punctuation, "="),
+                (TokenKind::Number, "0x10"),
+                (TokenKind::Label, ":cond_0"),
+                (TokenKind::Opcode, "if-eqz"),
+                (TokenKind::Register, "v0"),
+                (TokenKind::Punctuation, ","),
+                (TokenKind::Label, ":cond_0"),
+                (TokenKind::Directive, ".local"),
+                (TokenKind::Register, "v0"),
+                (TokenKind::Punctuation, ","),
+                (TokenKind::String, "\"s\""),
+                (TokenKind::Punctuation, ":"),
+                (TokenKind::Type, "Ljava/lang/String;"),
+            ]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_tokenize_covers_input() {
+        use std::fs;
+
+        for entry in fs::read_dir("tests").unwrap() {
+            let input = fs::read_to_string(entry.unwrap().path()).unwrap();
+            let mut end = 0;
+            for token in tokenize(&input) {
+                assert!(input[end..token.span.start].trim().is_empty());
+                assert!(token.span.start < token.span.end);
+                assert_ne!(token.kind, TokenKind::Unknown, "{:?}", &input[token.span]);
+                end = token.span.end;
+            }
+            assert!(input[end..].trim().is_empty());
+        }
+        // malformed input still yields tokens rather than failing
+        assert!(tokenize("\"unterminated ;; \u{1F600}").count() > 0);
+    }
+}
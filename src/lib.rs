@@ -1,13 +1,19 @@
 /* Struct to represent a java object type identifer e.g. java.lang.Object */
 /* They are stored in the smali native (also JNI) format e.g. Ljava/lang/Object; */
 
-use std::fmt::{self, Debug};
+// without the `std` feature only `alloc` is needed, e.g. for WASM or embedded targets
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use core::fmt::{self, Debug};
 
 use winnow::{
-    ModalParser, Parser,
+    ModalParser, ModalResult, Parser,
     ascii::{multispace0, take_escaped},
     combinator::{alt, delimited, opt, preceded},
-    error::InputError,
+    error::{ErrMode, FromExternalError, ParserError},
+    stream::Stream,
     token::{literal, none_of, one_of, take_while},
 };
 
@@ -15,6 +21,8 @@ pub mod annotation;
 pub mod class;
 pub mod field;
 pub mod field_ref;
+pub mod format;
+pub mod lexer;
 pub mod method;
 pub mod method_ref;
 pub mod modifier;
@@ -23,6 +31,54 @@ pub mod op;
 pub mod param;
 pub mod signature;
 
+/// The `alloc` types the std prelude would otherwise provide
+pub(crate) mod prelude {
+    pub(crate) use alloc::{
+        boxed::Box,
+        format,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+}
+
+use prelude::*;
+
+/// Re-exports of the main types under the names used throughout the docs
+pub mod types {
+    pub use crate::{
+        class::Class as SmaliClass,
+        object_identifier::ObjectIdentifier,
+        signature::{method_signature::MethodSignature, type_signature::TypeSignature},
+    };
+}
+
+/// The error of the crate's parsers. A failure that aborts the whole parse carries
+/// the reason e.g. [`parse_class_complete`](class::parse_class_complete) reports, any
+/// other only says that an alternative didn't match.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ParseFailure {
+    pub reason: Option<String>,
+}
+
+impl<I: Stream> ParserError<I> for ParseFailure {
+    type Inner = Self;
+
+    fn from_input(_input: &I) -> Self {
+        ParseFailure::default()
+    }
+
+    fn into_inner(self) -> Result<Self::Inner, Self> {
+        Ok(self)
+    }
+}
+
+impl<I, E> FromExternalError<I, E> for ParseFailure {
+    fn from_external_error(_input: &I, _e: E) -> Self {
+        ParseFailure::default()
+    }
+}
+
 /* Custom error for our command helper */
 #[derive(Debug)]
 pub struct SmaliError {
@@ -43,9 +99,9 @@ impl fmt::Display for SmaliError {
     }
 }
 
-pub fn ws<'a, O, F>(inner: F) -> impl ModalParser<&'a str, O, InputError<&'a str>>
+pub fn ws<'a, O, F>(inner: F) -> impl ModalParser<&'a str, O, ParseFailure>
 where
-    F: ModalParser<&'a str, O, InputError<&'a str>>,
+    F: ModalParser<&'a str, O, ParseFailure>,
 {
     delimited(
         multispace0,
@@ -60,13 +116,14 @@ where
     )
 }
 
-pub fn comment<'a>() -> impl ModalParser<&'a str, &'a str, InputError<&'a str>> {
-    preceded(one_of('#'), take_while(0.., |c| c != '\n'))
+pub fn comment<'a>() -> impl ModalParser<&'a str, &'a str, ParseFailure> {
+    // stop at `\r` too, so CRLF line endings don't end up in the comment
+    preceded(one_of('#'), take_while(0.., |c| c != '\n' && c != '\r'))
 }
 
 /// Parses a string literal that may be empty.
 /// For example, it can parse `""` as well as `"builder"`.
-pub fn parse_string_lit<'a>() -> impl ModalParser<&'a str, &'a str, InputError<&'a str>> {
+pub fn parse_string_lit<'a>() -> impl ModalParser<&'a str, &'a str, ParseFailure> {
     delimited(
         (multispace0, one_of('"')),
         alt((
@@ -81,9 +138,90 @@ pub fn parse_string_lit<'a>() -> impl ModalParser<&'a str, &'a str, InputError<&
     )
 }
 
-pub fn parse_int_lit<'a, T>() -> impl ModalParser<&'a str, T, InputError<&'a str>>
+/// Decodes the escapes of a string literal's contents as [`parse_string_lit`] returns
+/// them, e.g. `caf\u00e9\n` to `café` and a newline. A `\uXXXX` surrogate pair such
+/// as `\ud83d\ude00` becomes the one character it encodes, here 😀.
+pub fn unescape_string(s: &str) -> Result<String, SmaliError> {
+    let invalid = |what: &str| SmaliError {
+        details: format!("{what} in string \"{s}\""),
+    };
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    let unit = |chars: &mut core::str::Chars| {
+        let hex = chars
+            .as_str()
+            .get(..4)
+            .filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| invalid("invalid \\u escape"))?;
+        let unit = u16::from_str_radix(hex, 16).map_err(|_| invalid("invalid \\u escape"))?;
+        *chars = chars.as_str()[4..].chars();
+        Ok::<_, SmaliError>(unit)
+    };
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some(c @ ('\'' | '"' | '\\')) => c,
+            Some('u') => {
+                let mut units = vec![unit(&mut chars)?];
+                if (0xd800..0xdc00).contains(&units[0]) && chars.as_str().starts_with("\\u") {
+                    let mut rest = chars.clone();
+                    rest.next();
+                    rest.next();
+                    if let Ok(low @ 0xdc00..0xe000) = unit(&mut rest) {
+                        units.push(low);
+                        chars = rest;
+                    }
+                }
+                char::decode_utf16(units)
+                    .next()
+                    .and_then(Result::ok)
+                    .ok_or_else(|| invalid("unpaired surrogate"))?
+            }
+            _ => return Err(invalid("invalid escape")),
+        });
+    }
+    Ok(out)
+}
+
+/// Escapes `s` to be written between the quotes of a string literal the way baksmali
+/// does: printable ASCII stays as it is and anything else becomes `\uXXXX`, with
+/// characters outside the BMP written as a surrogate pair.
+pub fn escape_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\'' | '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            _ => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    out.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Parses a decimal or `0x` hex integer literal, optionally suffixed with `L`. A hex
+/// literal too big for `T` may also be its unsigned bit pattern, e.g. `0xff` for an
+/// `i8` -1.
+pub fn parse_int_lit<'a, T>() -> impl ModalParser<&'a str, T, ParseFailure>
 where
-    T: num_traits::Num + std::str::FromStr + TryFrom<i64>,
+    T: num_traits::Num + core::str::FromStr + TryFrom<i64>,
     <T as TryFrom<i64>>::Error: Debug,
 {
     (
@@ -100,6 +238,104 @@ where
     )
         .try_map(|(sign, (base, digits), _)| match sign {
             Some(_) => T::from_str_radix(&format!("-{digits}"), base),
-            None => T::from_str_radix(digits, base),
+            None => T::from_str_radix(digits, base).or_else(|e| match base {
+                16 => hex_bit_pattern(digits).ok_or(e),
+                _ => Err(e),
+            }),
+        })
+}
+
+/// The value of the hex `digits` read as the bits of a `T`, e.g. `ffffffff` is an
+/// int -1. baksmali writes some literals that way, such as `0x8000000000000000L`.
+fn hex_bit_pattern<T: TryFrom<i64>>(digits: &str) -> Option<T> {
+    let bits = core::mem::size_of::<T>() * 8;
+    let raw = u64::from_str_radix(digits, 16).ok()?;
+    if bits > 64 || (bits < 64 && raw >> bits != 0) {
+        return None;
+    }
+    let shift = 64 - bits;
+    T::try_from(((raw << shift) as i64) >> shift).ok()
+}
+
+/// Checks that `value` fits in `bits` bits, either as a signed value or as an
+/// unsigned bit pattern like `0xfft`, and returns it sign-extended
+pub(crate) fn fit_bits(value: i64, bits: u32) -> Result<i64, SmaliError> {
+    let shift = 64 - bits;
+    let extended = (value << shift) >> shift;
+    if extended == value || (value >= 0 && value >> bits == 0) {
+        Ok(extended)
+    } else {
+        Err(SmaliError {
+            details: format!("{value} does not fit in {bits} bits"),
         })
+    }
+}
+
+/// Default for [`ParseOptions::max_depth`]
+pub const DEFAULT_MAX_DEPTH: usize = 255;
+
+/// The most dimensions dex allows an array type, e.g. 2 for `[[I`. They are checked
+/// against this instead of spending the [`ParseOptions::max_depth`] budget.
+pub const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+/// How the `_with` parsers read their input, e.g.
+/// `parse_class_with(ParseOptions { keep_comments: true, ..Default::default() })`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Keep the comments trailing instructions, see [`Method::comments`](method::Method::comments)
+    pub keep_comments: bool,
+    /// How deeply type signatures and annotation values may nest. Deeper input fails
+    /// to parse instead of overflowing the stack. Array dimensions don't count, see
+    /// [`MAX_ARRAY_DIMENSIONS`].
+    pub max_depth: usize,
+    /// Keep directives the parsers don't know verbatim instead of failing on them,
+    /// e.g. a `.foobar 1` added by a newer smali. They are written back unchanged.
+    pub lenient: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            keep_comments: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            lenient: false,
+        }
+    }
+}
+
+/// Aborts the whole parse, carrying why so that [`parse_complete`] can report it
+/// instead of a bare position. Rewind the input first to report where the failing
+/// item starts.
+pub(crate) fn fail(details: String) -> ErrMode<ParseFailure> {
+    ErrMode::Cut(ParseFailure {
+        reason: Some(details),
+    })
+}
+
+/// Parses all of `input`, describing a failure by its line, e.g.
+/// "failed to parse class at line 3 (byte 42)"
+pub(crate) fn parse_complete<'a, O>(
+    input: &'a str,
+    what: &str,
+    mut parser: impl ModalParser<&'a str, O, ParseFailure>,
+) -> Result<O, SmaliError> {
+    parser.parse(input).map_err(|e| {
+        let offset = e.offset();
+        let line = input[..offset].matches('\n').count() + 1;
+        let details = match &e.inner().reason {
+            Some(reason) => format!("{reason} at line {line} (byte {offset})"),
+            None => format!("failed to parse {what} at line {line} (byte {offset})"),
+        };
+        SmaliError { details }
+    })
+}
+
+/// Checks a recursive parser entered `depth` levels deep, cutting the whole parse
+/// once the nesting passes [`ParseOptions::max_depth`] so no alternative retries the
+/// same input. The depth is passed down the recursion, so every parse counts its own.
+pub(crate) fn enter_nested(options: ParseOptions, depth: usize) -> ModalResult<(), ParseFailure> {
+    if depth >= options.max_depth {
+        return Err(fail(format!("nesting deeper than {}", options.max_depth)));
+    }
+    Ok(())
 }
@@ -1,16 +1,40 @@
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
+use core::fmt::Write;
+
+#[cfg(feature = "odex")]
+use crate::op::odex::OdexOp;
 use crate::{
-    annotation::{Annotation, parse_annotation, write_annotation},
-    modifier::{Modifier, parse_modifiers, write_modifiers},
-    op::{Op, parse_op},
-    param::{Param, parse_param, write_param},
+    ParseFailure, ParseOptions, SmaliError,
+    annotation::{Annotation, parse_annotation_with, write_annotation},
+    fail,
+    format::FormatStyle,
+    modifier::{
+        HiddenApiRestriction, Modifier, modifiers_to_flags, parse_hidden_api_restrictions,
+        parse_modifiers, write_hidden_api_restrictions, write_modifiers,
+    },
+    object_identifier::ObjectIdentifier,
+    op::{
+        CatchDirective, Label, Op,
+        dex_op::{DexOp, GotoType, OneRegMoveType, Register},
+        parse_op_with_comment,
+    },
+    param::{Param, parse_param_with, write_param},
     parse_int_lit,
-    signature::method_signature::{MethodParameter, parse_method_parameter},
+    prelude::*,
+    signature::{
+        method_signature::{
+            MethodParameter, MethodSignature, parse_method_parameter_with, parse_methodsignature,
+        },
+        type_signature::TypeSignature,
+    },
     ws,
 };
 use winnow::{
     ModalParser, Parser,
-    combinator::{delimited, opt, preceded, repeat},
-    error::InputError,
+    combinator::{alt, delimited, opt, preceded, repeat},
     token::literal,
 };
 
@@ -20,58 +44,998 @@ use winnow::{
 pub struct Method<'a> {
     /// Method modifiers
     pub modifiers: Vec<Modifier>,
+    /// Hidden API restrictions of a framework method, empty for any other
+    pub hidden_api: Vec<HiddenApiRestriction>,
 
     pub param: MethodParameter<'a>,
     /// Number of local variables required by the operations
     pub locals: Option<u32>,
     /// Number of registers required by the operations
-    //pub registers: Option<u32>,
+    pub registers: Option<u32>,
     /// Method params
     pub params: Vec<Param<'a>>,
     /// Any method level annotations
     pub annotations: Vec<Annotation<'a>>,
     /// Method operations
     pub ops: Vec<Op<'a>>,
+    /// Comments trailing an instruction, keyed by the index of the op in `ops`.
+    /// Only filled when parsing with [`ParseOptions::keep_comments`](crate::ParseOptions::keep_comments).
+    pub comments: BTreeMap<usize, Cow<'a, str>>,
+}
+
+/// How [`Method::normalize_registers`] should name the parameter registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterStyle {
+    /// Every register is written as an absolute `vN`
+    AllV,
+    /// Parameter registers are written as `pN`, locals as `vN`
+    ParamsAsP,
 }
 
-pub fn parse_method<'a>() -> impl ModalParser<&'a str, Method<'a>, InputError<&'a str>> {
-    delimited(
+impl<'a> Method<'a> {
+    /// A method `name` with signature `sig`, without modifiers, `.locals` or ops. Give it
+    /// a body, or the `abstract` or `native` modifier, before writing it.
+    pub fn new(name: impl Into<Cow<'a, str>>, sig: MethodSignature<'a>) -> Self {
+        Method {
+            modifiers: vec![],
+            hidden_api: vec![],
+            param: MethodParameter {
+                ident: name.into(),
+                ms: sig,
+            },
+            locals: None,
+            registers: None,
+            params: vec![],
+            annotations: vec![],
+            ops: vec![],
+            comments: BTreeMap::new(),
+        }
+    }
+
+    /// Copies any borrowed text, detaching the method from the parsed source
+    pub fn into_owned(self) -> Method<'static> {
+        Method {
+            modifiers: self.modifiers,
+            hidden_api: self.hidden_api,
+            param: self.param.into_owned(),
+            locals: self.locals,
+            registers: self.registers,
+            params: self.params.into_iter().map(Param::into_owned).collect(),
+            annotations: self
+                .annotations
+                .into_iter()
+                .map(Annotation::into_owned)
+                .collect(),
+            ops: self.ops.into_iter().map(Op::into_owned).collect(),
+            comments: self
+                .comments
+                .into_iter()
+                .map(|(i, c)| (i, Cow::Owned(c.into_owned())))
+                .collect(),
+        }
+    }
+
+    /// A deep copy that owns all of its text, so it can be stored, modified and added
+    /// to another class after the source it was parsed from is gone.
+    pub fn to_owned(&self) -> Method<'static> {
+        self.clone().into_owned()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.param.ident
+    }
+
+    pub fn return_type(&self) -> &TypeSignature<'a> {
+        &self.param.ms.result
+    }
+
+    pub fn arg_types(&self) -> &[TypeSignature<'a>] {
+        &self.param.ms.args
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.modifiers.contains(&Modifier::Static)
+    }
+
+    /// The method's modifiers as dex `access_flags`
+    pub fn access_flags(&self) -> u32 {
+        modifiers_to_flags(&self.modifiers)
+    }
+
+    /// The checked exceptions listed by the method's `Ldalvik/annotation/Throws;`
+    /// annotation, empty if it has none
+    pub fn throws(&self) -> Vec<ObjectIdentifier<'_>> {
+        self.annotations
+            .iter()
+            .filter(|a| {
+                matches!(&a.annotation_type, TypeSignature::Object(o)
+                    if o.class_name == "dalvik/annotation/Throws")
+            })
+            .filter_map(|a| a.element_array("value"))
+            .flatten()
+            .filter_map(|v| match v.as_type()? {
+                TypeSignature::Object(o) => Some(*o),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The name and descriptor, e.g. `("get", "(I)Ljava/lang/Object;")`, which
+    /// identify the method within its class and order it in
+    /// [`Class::sort_members`](crate::class::Class::sort_members)
+    pub fn sort_key(&self) -> (String, String) {
+        (self.param.ident.to_string(), self.param.ms.descriptor())
+    }
+
+    /// Whether the method has no instructions, as abstract and native methods must
+    pub fn body_is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// The executable instructions with their index in `ops`, skipping labels, line
+    /// numbers, catch ranges, payloads and other directives.
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, &DexOp<'a>)> {
+        self.ops.iter().enumerate().filter_map(|(i, op)| match op {
+            Op::Op(d) => Some((i, d)),
+            _ => None,
+        })
+    }
+
+    /// Mutable counterpart of [`Method::instructions`]
+    pub fn instructions_mut(&mut self) -> impl Iterator<Item = (usize, &mut DexOp<'a>)> {
+        self.ops
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, op)| match op {
+                Op::Op(d) => Some((i, d)),
+                _ => None,
+            })
+    }
+
+    /// The `.catch`/`.catchall` handlers active at `ops[op_index]`, i.e. those whose
+    /// try range starts at or before it and ends after it. Catches whose bounds aren't
+    /// defined in this method are skipped.
+    ///
+    /// With nested try blocks the innermost, shortest range comes first, so the
+    /// handlers are in the order the runtime tries them. Catches of the same range
+    /// keep their order.
+    pub fn catches_at(&self, op_index: usize) -> Vec<&CatchDirective<'a>> {
+        let label_index = |label: &Label| {
+            self.ops
+                .iter()
+                .position(|op| matches!(op, Op::Label(l) if l == label))
+        };
+        let mut catches: Vec<_> = self
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Catch(
+                    c @ (CatchDirective::Catch { try_range, .. }
+                    | CatchDirective::CatchAll { try_range, .. }),
+                ) => Some((c, try_range)),
+                _ => None,
+            })
+            .filter_map(
+                |(c, range)| match (label_index(&range.start), label_index(&range.end)) {
+                    (Some(start), Some(end)) if (start..end).contains(&op_index) => {
+                        Some((c, end - start))
+                    }
+                    _ => None,
+                },
+            )
+            .collect();
+        catches.sort_by_key(|&(_, len)| len);
+        catches.into_iter().map(|(c, _)| c).collect()
+    }
+
+    /// Checks that abstract and native methods have no body, and that every other
+    /// method has one ending in a `return`, `throw` or `goto`.
+    pub fn validate_body(&self) -> Result<(), SmaliError> {
+        let name = format!("{}{}", self.param.ident, self.param.ms.to_jni());
+        let bodyless = self
+            .modifiers
+            .iter()
+            .any(|m| matches!(m, Modifier::Abstract | Modifier::Native));
+        if bodyless {
+            if !self.body_is_empty() || self.locals.is_some() || self.registers.is_some() {
+                return Err(SmaliError {
+                    details: format!("{name}: abstract or native method must not have a body"),
+                });
+            }
+            return Ok(());
+        }
+        // payloads and other directives may follow the last instruction, as may the
+        // `nop` baksmali emits to align a payload
+        let last = self.ops.iter().rev().find_map(|op| match op {
+            Op::Op(DexOp::Nop) => None,
+            Op::Op(op) => Some(op),
+            _ => None,
+        });
+        match last {
+            Some(DexOp::Return { .. } | DexOp::Throw { .. } | DexOp::Goto { .. }) => Ok(()),
+            Some(op) => Err(SmaliError {
+                details: format!(
+                    "{name}: body ends with `{op}` instead of a return, throw or goto"
+                ),
+            }),
+            None => Err(SmaliError {
+                details: format!("{name}: concrete method has no body"),
+            }),
+        }
+    }
+
+    /// Checks that every label a switch jumps to is defined in this method: the
+    /// payload of each `packed-switch`/`sparse-switch` and each payload target.
+    pub fn validate_switch_targets(&self) -> Result<(), SmaliError> {
+        self.check_labels_defined("switch targets", |op| match op {
+            Op::Op(op @ DexOp::Switch { .. }) => op.target_labels(),
+            Op::PackedSwitch(_) | Op::SparseSwitch(_) => op.labels(),
+            _ => vec![],
+        })
+    }
+
+    /// Checks that the target of every `goto` and `if-*` is defined in this method.
+    pub fn validate_branch_targets(&self) -> Result<(), SmaliError> {
+        self.check_labels_defined("branch targets", |op| match op {
+            Op::Op(DexOp::Switch { .. }) => vec![],
+            Op::Op(op) => op.target_labels(),
+            _ => vec![],
+        })
+    }
+
+    /// Checks that the try range bounds and handler of every `.catch`/`.catchall`
+    /// are defined in this method.
+    pub fn validate_catch_labels(&self) -> Result<(), SmaliError> {
+        self.check_labels_defined("catch labels", |op| match op {
+            Op::Catch(_) => op.labels(),
+            _ => vec![],
+        })
+    }
+
+    /// Checks that every `move-result*` directly follows an invoke or
+    /// `filled-new-array` whose result it can take: `move-result-wide` after a `J` or
+    /// `D`, `move-result-object` after an object or array and `move-result` after any
+    /// other non-void result. Labels, debug markers and `.catch` are skipped over.
+    pub fn validate_move_results(&self) -> Result<(), SmaliError> {
+        let name = format!("{}{}", self.param.ident, self.param.ms.to_jni());
+        let mut prev: Option<&DexOp> = None;
+        for op in &self.ops {
+            let op = match op {
+                Op::Label(_)
+                | Op::Line(_)
+                | Op::Prologue
+                | Op::Epilogue
+                | Op::Catch(_)
+                | Op::UnknownDirective(_) => continue,
+                Op::Op(op) => op,
+                _ => {
+                    prev = None;
+                    continue;
+                }
+            };
+            if let DexOp::MoveOneReg { move_type, .. } = op
+                && *move_type != OneRegMoveType::Exception
+            {
+                let problem = match prev.map(|p| (p, MoveResult::of(p))) {
+                    None | Some((_, MoveResult::Nothing)) => {
+                        Some("does not follow an invoke or filled-new-array".to_string())
+                    }
+                    Some((p, MoveResult::Void)) => {
+                        Some(format!("follows `{p}`, which returns void"))
+                    }
+                    Some((p, MoveResult::Move(expected))) if expected != *move_type => {
+                        Some(format!("follows `{p}`, which needs `{expected}`"))
+                    }
+                    Some((_, MoveResult::Move(_) | MoveResult::Unknown)) => None,
+                };
+                if let Some(problem) = problem {
+                    return Err(SmaliError {
+                        details: format!("{name}: `{op}` {problem}"),
+                    });
+                }
+            }
+            prev = Some(op);
+        }
+        Ok(())
+    }
+
+    /// Reports the labels picked out of the ops by `refs` that no `:label` defines
+    fn check_labels_defined<'s, F>(&'s self, what: &str, refs: F) -> Result<(), SmaliError>
+    where
+        F: Fn(&'s Op<'a>) -> Vec<&'s Label<'a>>,
+    {
+        let defined: BTreeSet<&str> = self
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Label(l) => Some(l.0.as_ref()),
+                _ => None,
+            })
+            .collect();
+        let mut missing: Vec<&str> = vec![];
+        for l in self.ops.iter().flat_map(refs) {
+            if !defined.contains(l.0.as_ref()) && !missing.contains(&l.0.as_ref()) {
+                missing.push(&l.0);
+            }
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(SmaliError {
+                details: format!(
+                    "{}{}: undefined {what} :{}",
+                    self.param.ident,
+                    self.param.ms.to_jni(),
+                    missing.join(", :")
+                ),
+            })
+        }
+    }
+
+    /// Whether dalvik treats this as a direct method: static, private or a constructor.
+    /// All other methods are virtual.
+    pub fn is_direct(&self) -> bool {
+        self.is_static()
+            || self.modifiers.contains(&Modifier::Private)
+            || self.modifiers.contains(&Modifier::Constructor)
+            || self.param.ident == "<init>"
+            || self.param.ident == "<clinit>"
+    }
+
+    /// Number of registers taken by the arguments, counting wide arguments twice
+    /// and the implicit `this` of instance methods once.
+    pub fn param_register_count(&self) -> u32 {
+        self.param.ms.register_arity(self.is_static()) as u32
+    }
+
+    /// Declares the frame with `.locals`, dropping any `.registers` declaration
+    pub fn set_locals(&mut self, locals: u32) {
+        self.locals = Some(locals);
+        self.registers = None;
+    }
+
+    /// Declares the frame with `.registers`, dropping any `.locals` declaration
+    pub fn set_registers(&mut self, registers: u32) {
+        self.registers = Some(registers);
+        self.locals = None;
+    }
+
+    /// Checks that the method doesn't declare both `.registers` and `.locals`
+    pub fn check_register_directives(&self) -> Result<(), SmaliError> {
+        match (self.registers, self.locals) {
+            (Some(registers), Some(locals)) => Err(SmaliError {
+                details: format!(
+                    "{}{}: declares both .registers {registers} and .locals {locals}, only one is allowed",
+                    self.param.ident,
+                    self.param.ms.to_jni()
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of non-parameter registers, taken from `.locals` or derived from `.registers`
+    pub fn local_register_count(&self) -> Option<u32> {
+        self.locals.or_else(|| {
+            self.registers
+                .map(|r| r.saturating_sub(self.param_register_count()))
+        })
+    }
+
+    /// Total number of registers, taken from `.registers` or derived from `.locals`
+    pub fn register_count(&self) -> Option<u32> {
+        self.registers
+            .or_else(|| self.locals.map(|l| l + self.param_register_count()))
+    }
+
+    /// Rewrites every register used by the method's ops and `.param` directives.
+    pub fn remap_registers<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Register) -> Register,
+    {
+        for p in &mut self.params {
+            p.register = f(p.register);
+        }
+        for op in &mut self.ops {
+            if let Op::Op(op) = op {
+                for r in op.registers_mut() {
+                    *r = f(*r);
+                }
+            }
+        }
+    }
+
+    /// Converts parameter registers between their `pN` and absolute `vN` names.
+    ///
+    /// Parameters occupy the last registers of the frame, so `pN` is `v(locals + N)`.
+    /// Methods without a `.locals` or `.registers` declaration are left untouched, as
+    /// are those with a `pN` that would land above `v65535`.
+    pub fn normalize_registers(&mut self, style: RegisterStyle) {
+        let Some(locals) = self.local_register_count() else {
+            return;
+        };
+        let (Ok(locals), Ok(params)) = (
+            u16::try_from(locals),
+            u16::try_from(self.param_register_count()),
+        ) else {
+            return;
+        };
+        if style == RegisterStyle::AllV {
+            let highest_param = self
+                .params
+                .iter()
+                .map(|p| p.register)
+                .chain(self.ops.iter().flat_map(|op| match op {
+                    Op::Op(op) => op.registers(),
+                    _ => vec![],
+                }))
+                .filter_map(|r| match r {
+                    Register::Parameter(n) => Some(n),
+                    Register::Local(_) => None,
+                })
+                .max();
+            if highest_param.is_some_and(|n| locals.checked_add(n).is_none()) {
+                return;
+            }
+        }
+        self.remap_registers(|r| match (style, r) {
+            (RegisterStyle::AllV, Register::Parameter(n)) => Register::Local(locals + n),
+            (RegisterStyle::ParamsAsP, Register::Local(n))
+                if n >= locals && n - locals < params =>
+            {
+                Register::Parameter(n - locals)
+            }
+            _ => r,
+        });
+    }
+
+    /// Renames the label `from` to `to`, updating its definition and every reference:
+    /// branch targets, catch ranges and handlers, and switch payload targets.
+    pub fn rename_label(&mut self, from: &Label, to: Label<'a>) {
+        for op in &mut self.ops {
+            for l in op.labels_mut() {
+                if l.0 == from.0 {
+                    *l = to.clone();
+                }
+            }
+        }
+    }
+
+    /// Whether `other` is the same method up to label names and instruction comments.
+    /// Labels are matched by the order they are defined in, so `:cond_1` here equals
+    /// `:cond_0` there if both are, say, the second label of their method.
+    pub fn structurally_eq(&self, other: &Method) -> bool {
+        self.modifiers == other.modifiers
+            && self.hidden_api == other.hidden_api
+            && self.param == other.param
+            && (self.locals, self.registers) == (other.locals, other.registers)
+            && self.params == other.params
+            && self.annotations == other.annotations
+            && canonical_ops(self) == canonical_ops(other)
+    }
+
+    /// Returns a label of the form `prefix_N` that is not yet used in this method.
+    pub fn fresh_label(&self, prefix: &str) -> Label<'static> {
+        fresh_label_among(&self.ops, prefix)
+    }
+
+    /// Highest local `vN` register used by the ops, counting the upper half of wide pairs
+    pub fn max_register(&self) -> Option<u32> {
+        self.ops.iter().filter_map(Op::max_local_register).max()
+    }
+
+    /// Number of non-parameter registers the ops actually need.
+    ///
+    /// `vN` registers that fall in the parameter window of the declared frame are
+    /// parameters; any other `vN` is a local.
+    fn required_locals(&self) -> u32 {
+        let mut m = self.clone();
+        m.normalize_registers(RegisterStyle::ParamsAsP);
+        m.max_register().map_or(0, |r| r + 1)
+    }
+
+    /// Checks that the declared `.locals` (or `.registers`) covers every register the ops use.
+    pub fn check_locals(&self) -> Result<(), SmaliError> {
+        if self.ops.is_empty() {
+            return Ok(());
+        }
+        let required = self.required_locals();
+        let declared = self.local_register_count().unwrap_or(0);
+        if declared < required {
+            return Err(SmaliError {
+                details: format!(
+                    "{}{}: declares {declared} locals but needs {required}",
+                    self.param.ident,
+                    self.param.ms.to_jni()
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Sets `.locals` (and `.registers`, if declared) to what the ops actually need.
+    ///
+    /// Parameters are switched to `pN` naming first so they stay valid as the frame changes.
+    pub fn fix_locals(&mut self) {
+        if self.ops.is_empty() {
+            return;
+        }
+        self.normalize_registers(RegisterStyle::ParamsAsP);
+        let required = self.required_locals();
+        let params = self.param_register_count();
+        match (self.locals, self.registers) {
+            (None, Some(_)) => self.registers = Some(required + params),
+            (_, registers) => {
+                self.locals = Some(required);
+                self.registers = registers.map(|_| required + params);
+            }
+        }
+    }
+
+    /// Renumbers the local registers to a contiguous `v0..vN` in their original order
+    /// and shrinks `.locals`/`.registers` to match. Parameters are switched to `pN`
+    /// naming and otherwise left alone; wide pairs and register ranges stay contiguous.
+    pub fn compact_registers(&mut self) {
+        let Some(locals) = self.local_register_count() else {
+            return;
+        };
+        self.normalize_registers(RegisterStyle::ParamsAsP);
+        let locals = u16::try_from(locals).unwrap_or(u16::MAX);
+        let mut used = BTreeSet::new();
+        for (_, op) in self.instructions() {
+            let wide = op.wide_registers();
+            for r in op.registers() {
+                if let Register::Local(n) = r {
+                    used.insert(n);
+                    if wide.contains(&r) {
+                        used.insert(n + 1);
+                    }
+                }
+            }
+            // the registers inside a range are used too, and must stay next to each other
+            if let DexOp::Invoke {
+                range: Some(range), ..
+            }
+            | DexOp::FilledNewArrayRange {
+                registers: range, ..
+            } = op
+            {
+                match (range.start, range.end) {
+                    (Register::Local(start), Register::Local(end)) => used.extend(start..=end),
+                    (Register::Local(start), Register::Parameter(_)) => used.extend(start..locals),
+                    _ => {}
+                }
+            }
+        }
+        let slots: Vec<u16> = used.into_iter().collect();
+        self.remap_registers(|r| match r {
+            Register::Local(n) => Register::Local(slots.binary_search(&n).map_or(n, |i| i as u16)),
+            r => r,
+        });
+        self.fix_locals();
+    }
+
+    /// Inserts `ops` before the op at `index`.
+    ///
+    /// Labels defined by the snippet that clash with labels of this method are renamed,
+    /// and the snippet's local registers are moved above the existing locals so they
+    /// can't clobber live values; `.locals`/`.registers` grow to match. Parameters are
+    /// switched to `pN` naming first so they stay valid as the frame grows.
+    ///
+    /// The caller is responsible for the control flow being correct afterwards, e.g.
+    /// that the snippet falls through and doesn't jump into the middle of a try block.
+    /// Fails without changing the method if `index` is past the end of the ops or the
+    /// moved registers would pass `v65535`.
+    pub fn splice_before(&mut self, index: usize, mut ops: Vec<Op<'a>>) -> Result<(), SmaliError> {
+        let name = || format!("{}{}", self.param.ident, self.param.ms.to_jni());
+        if index > self.ops.len() {
+            return Err(SmaliError {
+                details: format!(
+                    "{}: can't splice at {index}, past its {} ops",
+                    name(),
+                    self.ops.len()
+                ),
+            });
+        }
+        let max = ops.iter().filter_map(Op::max_local_register).max();
+        let offset = self.local_register_count().unwrap_or(0);
+        if let Some(max) = max
+            && max
+                .checked_add(offset)
+                .is_none_or(|moved| moved > u32::from(u16::MAX))
+        {
+            return Err(SmaliError {
+                details: format!(
+                    "{}: v{max} of the spliced ops can't move above {offset} locals",
+                    name()
+                ),
+            });
+        }
+
+        let defined: Vec<Label<'a>> = ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Label(l) => Some(l.clone()),
+                _ => None,
+            })
+            .collect();
+        for label in defined {
+            let clashes = self
+                .ops
+                .iter()
+                .any(|op| op.labels().iter().any(|l| l.0 == label.0));
+            if clashes {
+                let prefix = match label.0.rsplit_once('_') {
+                    Some((prefix, n)) if n.chars().all(|c| c.is_ascii_digit()) => prefix,
+                    _ => &label.0,
+                };
+                let all: Vec<Op> = self.ops.iter().chain(ops.iter()).cloned().collect();
+                let fresh = fresh_label_among(&all, prefix);
+                for op in &mut ops {
+                    for l in op.labels_mut() {
+                        if l.0 == label.0 {
+                            *l = fresh.clone();
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(max) = max {
+            self.normalize_registers(RegisterStyle::ParamsAsP);
+            // checked above, every register fits when the highest does
+            let offset = offset as u16;
+            for op in &mut ops {
+                if let Op::Op(op) = op {
+                    for r in op.registers_mut() {
+                        if let Register::Local(n) = r {
+                            *n += offset;
+                        }
+                    }
+                }
+            }
+            let extra = max + 1;
+            match (self.locals, self.registers) {
+                (_, Some(registers)) => {
+                    self.registers = Some(registers + extra);
+                    self.locals = self.locals.map(|l| l + extra);
+                }
+                (locals, None) => self.locals = Some(locals.unwrap_or(0) + extra),
+            }
+        }
+
+        self.comments = core::mem::take(&mut self.comments)
+            .into_iter()
+            .map(|(i, c)| {
+                if i >= index {
+                    (i + ops.len(), c)
+                } else {
+                    (i, c)
+                }
+            })
+            .collect();
+        self.ops.splice(index..index, ops);
+        Ok(())
+    }
+
+    /// The code unit offset of each op in `ops`, where a label or other directive is
+    /// at the offset of the instruction following it. Payloads are aligned to an even
+    /// offset as the assembler pads them with a `nop`.
+    pub fn instruction_offsets(&self) -> Vec<u32> {
+        let mut offset = 0;
+        self.ops
+            .iter()
+            .map(|op| {
+                if matches!(
+                    op,
+                    Op::ArrayData(_) | Op::PackedSwitch(_) | Op::SparseSwitch(_)
+                ) {
+                    offset += offset % 2;
+                }
+                let at = offset;
+                offset += op.code_units();
+                at
+            })
+            .collect()
+    }
+
+    /// Gives every `goto` the smallest encoding its branch distance fits in: 8 bits
+    /// for `goto`, 16 for `goto/16` and 32 for `goto/32`. Only the last can branch to
+    /// itself. Growing a goto moves the code after it, so this repeats until no goto
+    /// needs to grow. Gotos to undefined labels are left alone.
+    pub fn finalize_gotos(&mut self) {
+        let defined: BTreeSet<String> = self
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Label(l) => Some(l.0.to_string()),
+                _ => None,
+            })
+            .collect();
+        for op in &mut self.ops {
+            if let Op::Op(DexOp::Goto { goto_type, offset }) = op
+                && defined.contains(offset.0.as_ref())
+            {
+                *goto_type = GotoType::Normal;
+            }
+        }
+        loop {
+            let offsets = self.instruction_offsets();
+            let labels: BTreeMap<&str, u32> = self
+                .ops
+                .iter()
+                .zip(&offsets)
+                .filter_map(|(op, at)| match op {
+                    Op::Label(l) => Some((l.0.as_ref(), *at)),
+                    _ => None,
+                })
+                .collect();
+            let mut grown = Vec::new();
+            for (i, op) in self.ops.iter().enumerate() {
+                let Op::Op(DexOp::Goto { goto_type, offset }) = op else {
+                    continue;
+                };
+                let Some(&target) = labels.get(offset.0.as_ref()) else {
+                    continue;
+                };
+                let distance = i64::from(target) - i64::from(offsets[i]);
+                let fits = match goto_type {
+                    GotoType::Normal => distance != 0 && i8::try_from(distance).is_ok(),
+                    GotoType::Size16 => distance != 0 && i16::try_from(distance).is_ok(),
+                    GotoType::Size32 => true,
+                };
+                if !fits {
+                    grown.push(i);
+                }
+            }
+            if grown.is_empty() {
+                return;
+            }
+            for i in grown {
+                if let Op::Op(DexOp::Goto { goto_type, .. }) = &mut self.ops[i] {
+                    *goto_type = match goto_type {
+                        GotoType::Normal => GotoType::Size16,
+                        _ => GotoType::Size32,
+                    };
+                }
+            }
+        }
+    }
+
+    /// The values of the method's `.line` directives in order
+    pub fn lines(&self) -> impl Iterator<Item = u32> + '_ {
+        self.ops.iter().filter_map(|op| match op {
+            Op::Line(l) => Some(*l),
+            _ => None,
+        })
+    }
+
+    /// Regenerates the line table after code was moved around: a `.line` repeating
+    /// the one before it is dropped, and the rest are numbered `start`, `start + 1`, ...
+    /// in order.
+    pub fn renumber_lines(&mut self, start: u32) {
+        let mut previous = None;
+        let mut next = start;
+        let mut removed = 0;
+        let mut comments = BTreeMap::new();
+        let ops = core::mem::take(&mut self.ops);
+        for (i, op) in ops.into_iter().enumerate() {
+            if let Op::Line(l) = op {
+                if previous == Some(l) {
+                    removed += 1;
+                    continue;
+                }
+                previous = Some(l);
+                self.ops.push(Op::Line(next));
+                next = next.saturating_add(1);
+                continue;
+            }
+            if let Some(c) = self.comments.remove(&i) {
+                comments.insert(i - removed, c);
+            }
+            self.ops.push(op);
+        }
+        self.comments = comments;
+    }
+
+    /// Appends `ops` to the end of the method, see [`Method::splice_before`].
+    pub fn append_ops(&mut self, ops: Vec<Op<'a>>) -> Result<(), SmaliError> {
+        self.splice_before(self.ops.len(), ops)
+    }
+}
+
+/// The ops of `m` with each label renamed after the order it is defined in. The new
+/// names start with `#`, which no parsed label can, so undefined labels keep theirs.
+fn canonical_ops<'a>(m: &Method<'a>) -> Vec<Op<'a>> {
+    let mut order: BTreeMap<&str, usize> = BTreeMap::new();
+    for op in &m.ops {
+        if let Op::Label(l) = op {
+            let next = order.len();
+            order.entry(&l.0).or_insert(next);
+        }
+    }
+    let mut ops = m.ops.clone();
+    for l in ops.iter_mut().flat_map(Op::labels_mut) {
+        if let Some(i) = order.get(l.0.as_ref()) {
+            *l = Label(Cow::Owned(format!("#{i}")));
+        }
+    }
+    ops
+}
+
+fn fresh_label_among(ops: &[Op], prefix: &str) -> Label<'static> {
+    let used: BTreeSet<&str> = ops
+        .iter()
+        .flat_map(|op| op.labels())
+        .map(|l| l.0.as_ref())
+        .collect();
+    let name = (0..)
+        .map(|n| format!("{prefix}_{n}"))
+        .find(|name| !used.contains(name.as_str()))
+        .unwrap();
+    Label(Cow::Owned(name))
+}
+
+pub fn parse_method<'a>() -> impl ModalParser<&'a str, Method<'a>, ParseFailure> {
+    parse_method_with(ParseOptions::default())
+}
+
+/// Anything that can appear in a method after its frame declaration
+enum MethodItem<'a> {
+    Param(Param<'a>),
+    Annotation(Annotation<'a>),
+    Op(Op<'a>, Option<&'a str>),
+}
+
+/// Parses a method as `options` say, e.g. keeping the comments trailing its instructions
+pub fn parse_method_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, Method<'a>, ParseFailure> {
+    let mut method = delimited(
         ws(literal(".method")),
         (
             parse_modifiers(),
-            parse_method_parameter(),
-            //opt(preceded(ws(literal(".registers")), ws(parse_int_lit::<u32>()))),
+            parse_hidden_api_restrictions(),
+            parse_method_parameter_with(options),
+            opt(preceded(
+                ws(literal(".registers")),
+                ws(parse_int_lit::<u32>()),
+            )),
             opt(preceded(ws(literal(".locals")), ws(parse_int_lit::<u32>()))),
-            repeat(0.., parse_param()),
-            repeat(0.., parse_annotation()),
-            opt(ws(literal(".prologue"))),
-            repeat(0.., parse_op()),
+            // baksmali groups `.param`s and annotations ahead of the body, but other
+            // tools interleave them, so accept them in any order
+            repeat(
+                0..,
+                alt((
+                    parse_param_with(options).map(MethodItem::Param),
+                    parse_annotation_with(options).map(MethodItem::Annotation),
+                    parse_op_with_comment(options).map(|(op, c)| MethodItem::Op(op, c)),
+                )),
+            ),
         ),
         ws(literal(".end method")),
     )
-    .map(
-        |(modifiers, param, locals, params, annotations, _, ops)| Method {
+    .map(|(modifiers, hidden_api, param, registers, locals, items)| {
+        let items: Vec<MethodItem> = items;
+        let mut params = vec![];
+        let mut annotations = vec![];
+        let mut ops = vec![];
+        let mut comments = BTreeMap::new();
+        for item in items {
+            match item {
+                MethodItem::Param(p) => params.push(p),
+                MethodItem::Annotation(a) => annotations.push(a),
+                MethodItem::Op(op, comment) => {
+                    if let Some(c) = comment {
+                        comments.insert(ops.len(), Cow::Borrowed(c));
+                    }
+                    ops.push(op);
+                }
+            }
+        }
+        Method {
             modifiers,
+            hidden_api,
             param,
             locals,
-            //registers,
+            registers,
             params,
             annotations,
             ops,
-        },
-    )
+            comments,
+        }
+    });
+    move |input: &mut &'a str| {
+        let start = input.trim_start();
+        let m: Method<'a> = method.parse_next(input)?;
+        if let Err(e) = m.check_register_directives() {
+            // report the method's position, not its end
+            *input = start;
+            return Err(fail(e.details));
+        }
+        Ok(m)
+    }
+}
+
+/// What an instruction leaves behind for a following `move-result*`
+enum MoveResult {
+    Nothing,
+    Void,
+    /// A value whose type isn't spelled out, e.g. from a call site or inline method
+    Unknown,
+    Move(OneRegMoveType),
+}
+
+impl MoveResult {
+    fn of(op: &DexOp) -> Self {
+        let result = |ts: &TypeSignature| match ts {
+            TypeSignature::Void => MoveResult::Void,
+            ts if ts.is_wide() => MoveResult::Move(OneRegMoveType::ResultWide),
+            TypeSignature::Int
+            | TypeSignature::Bool
+            | TypeSignature::Byte
+            | TypeSignature::Char
+            | TypeSignature::Short
+            | TypeSignature::Float => MoveResult::Move(OneRegMoveType::Result),
+            _ => MoveResult::Move(OneRegMoveType::ResultObject),
+        };
+        match op {
+            // the proto of `invoke-polymorphic` is the real signature, the method
+            // being a signature-polymorphic `MethodHandle.invoke`. A malformed one says
+            // nothing about the result.
+            DexOp::Invoke {
+                proto: Some(proto), ..
+            } => match parse_methodsignature().parse(proto.as_ref()) {
+                Ok(ms) => result(&ms.result),
+                Err(_) => MoveResult::Unknown,
+            },
+            DexOp::Invoke {
+                method: Some(m), ..
+            } => result(&m.param.ms.result),
+            DexOp::Invoke { .. } => MoveResult::Unknown,
+            DexOp::FilledNewArray { .. } | DexOp::FilledNewArrayRange { .. } => {
+                MoveResult::Move(OneRegMoveType::ResultObject)
+            }
+            #[cfg(feature = "odex")]
+            DexOp::Odex(OdexOp::ExecuteInline { .. } | OdexOp::ExecuteInlineRange { .. }) => {
+                MoveResult::Unknown
+            }
+            #[cfg(feature = "odex")]
+            DexOp::Odex(OdexOp::InvokeObjectInitRange { .. }) => MoveResult::Void,
+            _ => MoveResult::Nothing,
+        }
+    }
 }
 
 pub fn write_method(method: &Method) -> String {
-    let mut out = format!(".method {}", write_modifiers(&method.modifiers));
-    out.push_str(&format!(
-        "{}{}\n",
+    write_method_with(method, &FormatStyle::default())
+}
+
+/// Writes a method laid out according to `style`
+pub fn write_method_with(method: &Method, style: &FormatStyle) -> String {
+    let mut out = String::new();
+    write_method_into(method, style, &mut out);
+    out
+}
+
+/// Appends the smali text of a method to `out`, as [`write_method_with`] returns it
+pub fn write_method_into(method: &Method, style: &FormatStyle, out: &mut String) {
+    let start = out.len();
+    // writing to a `String` can't fail
+    let _ = writeln!(
+        out,
+        ".method {}{}{}{}",
+        write_modifiers(&method.modifiers),
+        write_hidden_api_restrictions(&method.hidden_api),
         method.param.ident,
         method.param.ms.to_jni()
-    ));
+    );
     if !method.ops.is_empty() {
+        if let Some(registers) = method.registers {
+            let _ = writeln!(out, "    .registers {registers}");
+        }
         if let Some(locals) = method.locals {
-            out.push_str(&format!("    .locals {locals}\n"));
+            let _ = writeln!(out, "    .locals {locals}");
         }
     }
 
@@ -85,34 +1049,46 @@ pub fn write_method(method: &Method) -> String {
         out.push_str(&write_annotation(a, false, true));
     }
 
-    for i in &method.ops {
+    for (idx, i) in method.ops.iter().enumerate() {
         match i {
             Op::Line(l) => {
-                out.push_str(&format!("    .line {l:}\n"));
+                let _ = writeln!(out, "    .line {l}");
             }
             Op::Label(l) => {
-                out.push_str(&format!("    {l}\n"));
-            }
-            Op::Op(s) => {
-                out.push_str(&format!("    {s}\n"));
+                let _ = writeln!(out, "    {l}");
             }
+            Op::Prologue => out.push_str("    .prologue\n"),
+            Op::Epilogue => out.push_str("    .epilogue\n"),
+            Op::Op(s) => match method.comments.get(&idx) {
+                Some(c) => {
+                    let _ = writeln!(out, "    {s}    # {c}");
+                }
+                None => {
+                    let _ = writeln!(out, "    {s}");
+                }
+            },
             Op::Catch(c) => {
-                out.push_str(&format!("    {c}\n"));
+                let _ = writeln!(out, "    {c}");
             }
             Op::ArrayData(ad) => {
-                out.push_str(&format!("    {ad}\n"));
+                for line in ad.to_string().lines() {
+                    let _ = writeln!(out, "    {line}");
+                }
             }
             Op::PackedSwitch(ps) => {
-                out.push_str(&format!("    {ps}\n"));
+                let _ = writeln!(out, "    {}", ps.write_with(style));
             }
             Op::SparseSwitch(ss) => {
-                out.push_str(&format!("    {ss}\n"));
+                let _ = writeln!(out, "    {}", ss.write_with(style));
+            }
+            Op::UnknownDirective(d) => {
+                let _ = writeln!(out, "    {d}");
             }
         }
     }
 
     out.push_str(".end method\n\n");
-    out
+    style.line_ending.apply(out, start);
 }
 
 mod tests {
@@ -149,10 +1125,25 @@ mod tests {
 
 "#;
 
-        let m = parse_method().parse_next(&mut smali).unwrap();
-        println!("{}", write_method(&m))
-    }
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(m.name(), "c");
+        assert_eq!(m.return_type(), &TypeSignature::Void);
+        assert_eq!(m.arg_types().len(), 1);
+        assert_eq!(m.arg_types()[0].to_jni(), "Landroid/view/Display;");
+
+        assert_eq!(m.instructions().count(), 21);
+        assert_eq!(m.ops.len(), 23);
+        let (i, goto) = m.instructions().nth(16).unwrap();
+        assert!(matches!(goto, DexOp::Goto { .. }));
+        assert!(matches!(m.ops[i + 1], Op::Label(_)));
 
+        for (_, op) in m.instructions_mut() {
+            if let DexOp::CheckCast { dest, .. } = op {
+                *dest = Register::Local(3);
+            }
+        }
+        assert!(write_method(&m).contains("    check-cast v3, Lf6g;\n"));
+    }
 
     #[test]
     fn test_method_with_param_annotation() {
@@ -183,7 +1174,6 @@ mod tests {
     "#;
 
         let method = parse_method().parse_next(&mut smali).unwrap();
-        println!("{method:?}");
 
         assert_eq!(method.param.ident, "isInitialized");
         assert_eq!(method.annotations.len(), 1); // Signature annotation
@@ -191,4 +1181,964 @@ mod tests {
         assert_eq!(method.locals, Some(1));
         assert_eq!(method.modifiers.len(), 3); // private, static, final
     }
+
+    #[test]
+    fn test_normalize_registers() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public static sum(IJ)J
+    .locals 2
+    int-to-long v0, p0
+    add-long/2addr v0, p1
+    return-wide v0
+.end method
+"#;
+
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(m.param_register_count(), 3);
+        assert_eq!(m.register_count(), Some(5));
+
+        m.normalize_registers(RegisterStyle::AllV);
+        let out = write_method(&m);
+        assert!(out.contains("int-to-long v0, v2\n"));
+        assert!(out.contains("add-long/2addr v0, v3\n"));
+
+        m.normalize_registers(RegisterStyle::ParamsAsP);
+        let out = write_method(&m);
+        assert!(out.contains("int-to-long v0, p0\n"));
+        assert!(out.contains("add-long/2addr v0, p1\n"));
+
+        // `p1` would be `v65536`, past the last register
+        let mut smali = ".method public static f(II)V\n    .locals 65535\n    move v0, p0\n    move v1, p1\n    return-void\n.end method\n";
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        let unchanged = m.clone();
+        m.normalize_registers(RegisterStyle::AllV);
+        assert_eq!(m, unchanged);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_bodyless_methods_round_trip() {
+        use crate::class::parse_class;
+        use winnow::Parser;
+
+        let smali = std::fs::read_to_string("tests/NativeBridge.smali").unwrap();
+        let c = parse_class().parse_next(&mut smali.as_str()).unwrap();
+
+        let bodyless: Vec<_> = c.methods.iter().filter(|m| m.ops.is_empty()).collect();
+        assert_eq!(bodyless.len(), 4);
+        for m in bodyless {
+            assert_eq!(m.locals, None);
+            let out = super::write_method(m);
+            assert!(!out.contains(".locals"));
+            assert!(out.ends_with(".end method\n\n"));
+            if m.params.is_empty() {
+                // No body: the header is followed directly by .end method
+                assert!(smali.contains(out.trim_end()));
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_interspersed_params() {
+        use crate::class::parse_class;
+        use winnow::Parser;
+
+        let smali = std::fs::read_to_string("tests/InterspersedParams.smali").unwrap();
+        let c = parse_class().parse_next(&mut smali.as_str()).unwrap();
+        let m = &c.methods[1];
+        let names: Vec<_> = m.params.iter().map(|p| p.name.as_deref()).collect();
+        assert_eq!(names, vec![Some("name"), Some("count")]);
+        assert_eq!(m.params[1].annotations.len(), 1);
+        assert_eq!(m.annotations.len(), 2);
+        assert_eq!(m.ops.len(), 5);
+    }
+
+    #[test]
+    fn test_param_before_method_annotation() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public run(Ljava/lang/String;I)V
+    .locals 0
+    .param p1, "name"    # Ljava/lang/String;
+    .annotation runtime Ljava/lang/Deprecated;
+    .end annotation
+    .param p2
+    .annotation system Ldalvik/annotation/Throws;
+        value = {
+            Ljava/io/IOException;
+        }
+    .end annotation
+    return-void
+.end method
+"#;
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(m.params.len(), 2);
+        assert!(m.params.iter().all(|p| p.annotations.is_empty()));
+        let types: Vec<_> = m
+            .annotations
+            .iter()
+            .map(|a| a.annotation_type.to_jni())
+            .collect();
+        assert_eq!(
+            types,
+            ["Ljava/lang/Deprecated;", "Ldalvik/annotation/Throws;"]
+        );
+        assert_eq!(m.ops.len(), 1);
+        let written = write_method(&m);
+        assert_eq!(parse_method().parse(written.as_str()), Ok(m));
+    }
+
+    #[test]
+    fn test_throws() {
+        use super::*;
+        use winnow::Parser;
+        let smali = r#".method public read()I
+    .locals 1
+    .annotation system Ldalvik/annotation/Throws;
+        value = {
+            Ljava/io/IOException;,
+            Ljava/lang/InterruptedException;
+        }
+    .end annotation
+    const/4 v0, 0x0
+    return v0
+.end method
+"#;
+        let m = parse_method().parse(smali).unwrap();
+        let throws: Vec<_> = m.throws().iter().map(|o| o.as_jni_type()).collect();
+        assert_eq!(
+            throws,
+            ["Ljava/io/IOException;", "Ljava/lang/InterruptedException;"]
+        );
+
+        let smali = ".method public run()V\n    .locals 0\n    return-void\n.end method\n";
+        assert!(parse_method().parse(smali).unwrap().throws().is_empty());
+    }
+
+    #[test]
+    fn test_finalize_gotos() {
+        use super::*;
+        use winnow::Parser;
+        // 42 three-unit consts put :end 128 units past the first goto, one too far
+        // for an 8-bit offset
+        let smali = format!(
+            ".method public static run()V
+    .locals 1
+    :top
+    goto :end
+    :start
+    goto/32 :start_1
+    :start_1
+{}    :end
+    goto/16 :top
+.end method
+",
+            "    const v0, 0x1\n".repeat(42)
+        );
+        let mut m = parse_method().parse(smali.as_str()).unwrap();
+        assert_eq!(m.instruction_offsets()[..6], [0, 0, 1, 1, 4, 4]);
+        m.finalize_gotos();
+        let gotos: Vec<_> = m
+            .instructions()
+            .filter_map(|(_, op)| match op {
+                DexOp::Goto { goto_type, .. } => Some(goto_type.to_string()),
+                _ => None,
+            })
+            .collect();
+        // growing the first goto pushes the last one out of reach of :top, and the
+        // one to the very next instruction shrinks
+        assert_eq!(gotos, ["goto/16", "goto", "goto/16"]);
+        assert_eq!(m.instruction_offsets().last(), Some(&129));
+
+        let mut again = m.clone();
+        again.finalize_gotos();
+        assert_eq!(again, m);
+    }
+
+    #[test]
+    fn test_renumber_lines() {
+        use super::*;
+        use winnow::Parser;
+        let keep_comments = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let smali = r#".method public run()V
+    .locals 1
+    .line 12
+    const/4 v0, 0x0    # zero
+    .line 12
+    invoke-static {v0}, La;->b(I)V
+    .line 40
+    .line 40
+    invoke-static {v0}, La;->c(I)V    # c
+    .line 7
+    return-void
+.end method
+"#;
+        let mut m = parse_method_with(keep_comments).parse(smali).unwrap();
+        assert_eq!(m.lines().collect::<Vec<_>>(), [12, 12, 40, 40, 7]);
+        m.renumber_lines(1);
+        assert_eq!(m.lines().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(m.ops.len(), 7);
+        assert_eq!(m.comments.get(&1).map(|c| c.as_ref()), Some("zero"));
+        assert_eq!(m.comments.get(&4).map(|c| c.as_ref()), Some("c"));
+
+        let renumbered = m.clone();
+        m.renumber_lines(1);
+        assert_eq!(m, renumbered);
+    }
+
+    #[test]
+    fn test_structurally_eq() {
+        use super::*;
+        use winnow::Parser;
+        let keep_comments = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let method = |cond: &str, try_start: &str, comment: &str| {
+            let smali = format!(
+                r#".method public a(I)I
+    .locals 1
+    :{try_start}
+    if-eqz p1, :{cond}    {comment}
+    const/4 v0, 0x1
+    return v0
+    :{cond}
+    const/4 v0, 0x0
+    return v0
+    :try_end_0
+    .catch Ljava/lang/Exception; {{:{try_start} .. :try_end_0}} :{cond}
+.end method
+"#
+            );
+            parse_method_with(keep_comments)
+                .parse(smali.as_str())
+                .unwrap()
+                .to_owned()
+        };
+
+        let a = method("cond_0", "try_start_0", "");
+        let b = method("cond_7", "try_start_3", "# jump");
+        assert_ne!(a, b);
+        assert!(a.structurally_eq(&b));
+        assert!(b.structurally_eq(&a));
+
+        // same labels, but the branch jumps back to the first one
+        let mut c = a.clone();
+        let Op::Op(DexOp::Condition { offset, .. }) = &mut c.ops[1] else {
+            panic!("{:?}", c.ops[1]);
+        };
+        *offset = Label(Cow::Borrowed("try_start_0"));
+        assert!(!a.structurally_eq(&c));
+    }
+
+    #[test]
+    fn test_payload_mid_method() {
+        use super::*;
+        use winnow::Parser;
+        // the payload sits right after the instruction using it rather than at the end
+        let smali = r#".method public static values()[I
+    .locals 1
+    const/4 v0, 3
+    new-array v0, v0, [I
+    fill-array-data v0, :array_0
+    goto :goto_0
+    :array_0
+    .array-data 4
+        0x1
+        0x2
+        -0x1
+    .end array-data
+    :goto_0
+    return-object v0
+.end method
+
+"#;
+        let m = parse_method().parse(smali).unwrap();
+        assert!(matches!(m.ops[4], Op::Label(_)));
+        assert!(matches!(m.ops[5], Op::ArrayData(_)));
+        assert_eq!(write_method(&m), smali);
+    }
+
+    #[test]
+    fn test_rename_label() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public a(I)I
+    .locals 1
+    :try_start_0
+    if-eqz p1, :cond_0
+    packed-switch p1, :pswitch_data_0
+    :cond_0
+    const/4 v0, 0x0
+    :try_end_0
+    .catch Ljava/lang/Exception; {:try_start_0 .. :try_end_0} :cond_0
+    return v0
+    :pswitch_data_0
+    .packed-switch 0x0
+        :cond_0
+    .end packed-switch
+.end method
+"#;
+
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        let fresh = m.fresh_label("cond");
+        assert_eq!(fresh.0, "cond_1");
+
+        m.rename_label(&Label(Cow::Borrowed("cond_0")), fresh);
+        let out = write_method(&m);
+        assert!(!out.contains(":cond_0"));
+        assert_eq!(out.matches(":cond_1").count(), 4);
+        assert_eq!(m.fresh_label("cond").0, "cond_0");
+    }
+
+    #[test]
+    fn test_catches_at() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public a(Ljava/lang/Object;)I
+    .locals 1
+    const/4 v0, 0x0
+    :try_start_0
+    invoke-virtual {p1}, Ljava/lang/Object;->hashCode()I
+    move-result v0
+    :try_end_0
+    .catch Ljava/lang/Exception; {:try_start_0 .. :try_end_0} :catch_0
+    :catch_0
+    return v0
+.end method
+"#;
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        let covered: Vec<usize> = m
+            .instructions()
+            .filter(|(i, _)| !m.catches_at(*i).is_empty())
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(covered, [2, 3]);
+        let catches = m.catches_at(2);
+        assert!(matches!(
+            catches[..],
+            [CatchDirective::Catch { exception, .. }] if exception.as_java_type() == "java.lang.Exception"
+        ));
+        assert!(m.catches_at(0).is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_nested_catches() {
+        use super::*;
+        use crate::class::parse_class;
+        use winnow::Parser;
+
+        let smali = std::fs::read_to_string("tests/NestedTry.smali").unwrap();
+        let c = parse_class().parse(smali.as_str()).unwrap();
+        let m = &c.methods[1];
+        let handlers = |op_index| -> Vec<String> {
+            m.catches_at(op_index)
+                .iter()
+                .map(|c| match c {
+                    CatchDirective::Catch {
+                        exception, handler, ..
+                    } => format!("{} {handler}", exception.as_java_type()),
+                    CatchDirective::CatchAll { handler, .. } => format!("all {handler}"),
+                })
+                .collect()
+        };
+        let read = m
+            .instructions()
+            .find(|(_, op)| op.to_string().contains("->read()I"))
+            .unwrap()
+            .0;
+        assert_eq!(
+            handlers(read),
+            [
+                "java.io.EOFException :catch_1",
+                "java.io.IOException :catch_0",
+                "java.io.IOException :catch_0",
+                "all :catchall_0"
+            ]
+        );
+        let available = m.instructions().nth(1).unwrap().0;
+        assert_eq!(
+            handlers(available),
+            ["java.io.IOException :catch_0", "all :catchall_0"]
+        );
+        assert!(handlers(0).is_empty());
+    }
+
+    #[test]
+    fn test_splice_before() {
+        use super::*;
+        use crate::op::parse_op;
+        use winnow::Parser;
+        let mut smali = r#".method public check(I)V
+    .locals 1
+    if-eqz p1, :cond_0
+    const/4 v0, 0x1
+    :cond_0
+    return-void
+.end method
+"#;
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+
+        let mut snippet = r#"
+    const-string v0, "enter"
+    invoke-static {v0}, Lcom/example/Trace;->log(Ljava/lang/String;)V
+    if-eqz v0, :cond_0
+    :cond_0
+"#;
+        let ops: Vec<Op> = repeat(0.., parse_op()).parse_next(&mut snippet).unwrap();
+        m.splice_before(0, ops.clone()).unwrap();
+
+        assert_eq!(m.locals, Some(2));
+        assert_eq!(m.max_register(), Some(1));
+        let out = write_method(&m);
+        assert!(out.contains("    .locals 2\n    const-string v1, \"enter\"\n"));
+        assert!(out.contains("invoke-static {v1}, Lcom/example/Trace;->log"));
+        assert!(out.contains("if-eqz v1, :cond_1\n    :cond_1\n"));
+        assert!(out.contains("if-eqz p1, :cond_0\n    const/4 v0, 1\n    :cond_0\n"));
+
+        // the snippet's registers must still fit once moved above the locals
+        let before = m.clone();
+        m.locals = Some(u32::from(u16::MAX) + 1);
+        let crowded = m.clone();
+        assert_eq!(
+            m.splice_before(0, ops).unwrap_err().details,
+            "check(I)V: v0 of the spliced ops can't move above 65536 locals"
+        );
+        assert_eq!(m, crowded);
+        // including the upper half of a wide pair
+        m.locals = Some(u32::from(u16::MAX));
+        let wide = vec![parse_op().parse("const-wide/16 v0, 0x0").unwrap()];
+        assert_eq!(
+            m.splice_before(0, wide).unwrap_err().details,
+            "check(I)V: v1 of the spliced ops can't move above 65535 locals"
+        );
+        let edge = parse_op().parse("const-wide/16 v65535, 0x0").unwrap();
+        assert_eq!(edge.max_local_register(), Some(65536));
+
+        m = before;
+        let len = m.ops.len();
+        assert_eq!(
+            m.splice_before(len + 1, vec![Op::Line(2)])
+                .unwrap_err()
+                .details,
+            format!("check(I)V: can't splice at {}, past its {len} ops", len + 1)
+        );
+        assert!(m.append_ops(vec![Op::Line(2)]).is_ok());
+    }
+
+    #[test]
+    fn test_leading_line() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public static run()V
+    .locals 0
+    .line 0x10
+    .prologue
+    return-void
+.end method
+"#;
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert!(matches!(m.ops[0], Op::Line(16)));
+        assert_eq!(
+            write_method(&m),
+            ".method public static run()V\n    .locals 0\n    .line 16\n    .prologue\n    return-void\n.end method\n\n"
+        );
+    }
+
+    #[test]
+    fn test_check_locals() {
+        use super::*;
+        use crate::op::parse_op;
+        use winnow::Parser;
+        let mut smali = r#".method public static sum(JI)J
+    .locals 1
+    int-to-long v0, p2
+    add-long/2addr v0, p0
+    return-wide v0
+.end method
+"#;
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        let err = m.check_locals().unwrap_err();
+        assert_eq!(err.details, "sum(JI)J: declares 1 locals but needs 2");
+
+        m.fix_locals();
+        assert_eq!(m.locals, Some(2));
+        assert!(m.check_locals().is_ok());
+
+        // with `.registers` the parameters live in the top vN registers
+        let mut smali = r#".method public static id(I)I
+    .registers 2
+    move v0, v1
+    return v0
+.end method
+"#;
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        assert!(m.check_locals().is_ok());
+        m.ops
+            .insert(0, parse_op().parse_next(&mut "const/4 v2, 0x0").unwrap());
+        assert!(m.check_locals().is_err());
+        m.fix_locals();
+        assert_eq!(m.registers, Some(4));
+        assert_eq!(m.locals, None);
+        assert!(write_method(&m).contains("const/4 v2, 0\n    move v0, p0\n"));
+    }
+
+    #[test]
+    fn test_instruction_comments() {
+        use super::*;
+        use winnow::Parser;
+        let keep_comments = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let smali = r##".method public static id()I
+    .locals 2
+    const v0, 0x7f0a0001    # R.id.foo
+    # standalone comment
+    if-eqz v0, :cond_0    # +4
+    :cond_0
+    const-string v1, "#not a comment"
+    return v0
+.end method
+"##;
+        let m = parse_method().parse_next(&mut &*smali).unwrap();
+        assert!(m.comments.is_empty());
+
+        let mut m = parse_method_with(keep_comments)
+            .parse_next(&mut &*smali)
+            .unwrap();
+        assert_eq!(m.comments.get(&0).map(|c| c.as_ref()), Some("R.id.foo"));
+        assert_eq!(m.comments.get(&1).map(|c| c.as_ref()), Some("+4"));
+        assert_eq!(m.comments.len(), 2);
+        let out = write_method(&m);
+        assert!(out.contains("    const v0, 2131361793    # R.id.foo\n"));
+        assert!(out.contains("    if-eqz v0, :cond_0    # +4\n"));
+        assert_eq!(
+            parse_method_with(keep_comments).parse_next(&mut out.as_str()),
+            Ok(m.clone())
+        );
+
+        // comments follow their instruction when ops are spliced in front
+        m.splice_before(0, vec![Op::Line(1)]).unwrap();
+        assert_eq!(m.comments.get(&1).map(|c| c.as_ref()), Some("R.id.foo"));
+    }
+
+    #[test]
+    fn test_registers_and_locals_conflict() {
+        use super::*;
+        use winnow::Parser;
+        let conflicting = r#".method public static run()V
+    .registers 2
+    .locals 1
+    return-void
+.end method
+"#;
+        assert!(parse_method().parse(conflicting).is_err());
+
+        let class = format!(".class La;\n.super Ljava/lang/Object;\n\n{conflicting}");
+        let e = crate::class::parse_class_complete(&class).unwrap_err();
+        // without `std` the reason is dropped and only the position reported
+        assert_eq!(
+            e.details,
+            "run()V: declares both .registers 2 and .locals 1, only one is allowed at line 4 (byte 38)"
+        );
+
+        let mut smali = r#".method public static run()V
+    .registers 2
+    return-void
+.end method
+"#;
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        m.locals = Some(1);
+        assert_eq!(
+            m.check_register_directives().unwrap_err().details,
+            "run()V: declares both .registers 2 and .locals 1, only one is allowed"
+        );
+
+        m.set_locals(1);
+        assert_eq!((m.registers, m.locals), (None, Some(1)));
+        m.set_registers(3);
+        assert_eq!((m.registers, m.locals), (Some(3), None));
+        assert!(m.check_register_directives().is_ok());
+    }
+
+    #[test]
+    fn test_annotated_register_directives() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public static run()V
+    .locals 4  # v3 holds the cursor
+    const/4 v3, 0x0
+    return-void
+.end method
+"#;
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(m.locals, Some(4));
+        assert_eq!(m.ops.len(), 2);
+
+        let mut smali = r#".method public static run()V
+    .registers 0x10 # hex, as some tools write it
+    return-void
+.end method
+"#;
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(m.registers, Some(16));
+
+        let mut smali =
+            ".method public static run()V\n    .locals 0x4\n    return-void\n.end method\n";
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(m.locals, Some(4));
+    }
+
+    #[test]
+    fn test_validate_body() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public abstract run()V
+    .locals 0
+    return-void
+.end method
+"#;
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(
+            m.validate_body().unwrap_err().details,
+            "run()V: abstract or native method must not have a body"
+        );
+
+        let mut smali = ".method public native run()V\n.end method\n";
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert!(m.body_is_empty());
+        assert!(m.validate_body().is_ok());
+
+        let mut smali = ".method public run()V\n.end method\n";
+        let m = parse_method().parse_next(&mut smali).unwrap();
+        assert!(m.validate_body().is_err());
+
+        let mut smali = r#".method public run()I
+    .locals 1
+    const/4 v0, 0x0
+    :goto_0
+    return v0
+    :array_0
+    .array-data 4
+        0x1
+    .end array-data
+.end method
+"#;
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        assert!(m.validate_body().is_ok());
+        m.ops.remove(2);
+        assert_eq!(
+            m.validate_body().unwrap_err().details,
+            "run()I: body ends with `const/4 v0, 0` instead of a return, throw or goto"
+        );
+    }
+
+    #[test]
+    fn test_validate_move_results() {
+        use super::*;
+        use winnow::Parser;
+        let check = |body: &str| {
+            let smali = format!(
+                ".method public static run()V\n    .locals 2\n{body}    return-void\n.end method\n"
+            );
+            let m = parse_method().parse(&smali).unwrap();
+            m.validate_move_results().map_err(|e| e.details)
+        };
+
+        assert!(
+            check(
+                r#"    invoke-static {}, La;->count()J
+    .line 3
+    :after
+    move-result-wide v0
+    filled-new-array {v0}, [I
+    move-result-object v0
+"#
+            )
+            .is_ok()
+        );
+        assert_eq!(
+            check(
+                r#"    invoke-static {}, La;->count()I
+    const/4 v1, 0x0
+    move-result v0
+"#
+            ),
+            Err("run()V: `move-result v0` does not follow an invoke or filled-new-array".into())
+        );
+        assert_eq!(
+            check(
+                r#"    invoke-static {}, La;->name()Ljava/lang/String;
+    move-result v0
+"#
+            ),
+            Err("run()V: `move-result v0` follows `invoke-static {}, La;->name()Ljava/lang/String;`, which needs `move-result-object`".into())
+        );
+        assert_eq!(
+            check(
+                r#"    invoke-static {}, La;->run()V
+    move-result v0
+"#
+            ),
+            Err("run()V: `move-result v0` follows `invoke-static {}, La;->run()V`, which returns void".into())
+        );
+        assert_eq!(
+            check("    move-result-object v0\n"),
+            Err(
+                "run()V: `move-result-object v0` does not follow an invoke or filled-new-array"
+                    .into()
+            )
+        );
+
+        // a malformed `invoke-polymorphic` proto leaves the result unknown
+        let smali = ".method public static run()V\n    .locals 2\n    invoke-polymorphic {v0, v1}, Ljava/lang/invoke/MethodHandle;->invoke([Ljava/lang/Object;)Ljava/lang/Object;, (I)I\n    move-result v0\n    return-void\n.end method\n";
+        let mut m = parse_method().parse(smali).unwrap();
+        assert!(m.validate_move_results().is_ok());
+        let Op::Op(DexOp::Invoke { proto, .. }) = &mut m.ops[0] else {
+            panic!("{:?}", m.ops[0]);
+        };
+        *proto = Some("(I".into());
+        assert!(m.validate_move_results().is_ok());
+    }
+
+    #[test]
+    fn test_validate_switch_targets() {
+        use super::*;
+        use winnow::Parser;
+        let mut smali = r#".method public static pick(I)I
+    .locals 1
+    packed-switch p0, :pswitch_data_0
+    const/4 v0, 0x0
+    return v0
+    :pswitch_0
+    const/4 v0, 0x1
+    return v0
+    :pswitch_data_0
+    .packed-switch 0x0
+        :pswitch_0
+        :pswitch_1
+    .end packed-switch
+.end method
+"#;
+        let mut m = parse_method().parse_next(&mut smali).unwrap();
+        assert_eq!(
+            m.validate_switch_targets().unwrap_err().details,
+            "pick(I)I: undefined switch targets :pswitch_1"
+        );
+
+        m.ops
+            .insert(5, Op::Label(Label(Cow::Borrowed("pswitch_1"))));
+        assert!(m.validate_switch_targets().is_ok());
+
+        m.rename_label(
+            &Label(Cow::Borrowed("pswitch_data_0")),
+            Label(Cow::Borrowed("pswitch_data_1")),
+        );
+        m.ops
+            .retain(|op| !matches!(op, Op::Label(l) if l.0 == "pswitch_data_1"));
+        assert!(m.validate_switch_targets().is_err());
+    }
+
+    #[test]
+    fn test_debug_markers() {
+        use super::*;
+        use winnow::Parser;
+        let smali = r#".method public static clamp(I)I
+    .locals 1
+    .prologue
+    .line 10
+    if-gez p0, :cond_0
+    const/4 v0, 0x0
+    .epilogue
+    return v0
+    :cond_0
+    .line 12
+    .epilogue
+    return p0
+.end method
+
+"#;
+        let m = parse_method().parse(smali).unwrap();
+        assert_eq!(m.ops[0], Op::Prologue);
+        let markers: Vec<_> = m
+            .ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| matches!(op, Op::Prologue | Op::Epilogue))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(markers, [0, 4, 8]);
+        assert!(m.validate_body().is_ok());
+
+        let written = write_method(&m);
+        assert_eq!(parse_method().parse(&written), Ok(m));
+        assert!(written.contains("    const/4 v0, 0\n    .epilogue\n    return v0\n"));
+    }
+
+    #[test]
+    fn test_decimal_switch_keys() {
+        use super::*;
+        use crate::format::KeyRadix;
+        use winnow::Parser;
+        let smali = r#".method public static pick(I)I
+    .locals 0
+    sparse-switch p0, :sswitch_data_0
+    return p0
+    :sswitch_0
+    const/4 p0, -0x1
+    return p0
+    :sswitch_1
+    const/4 p0, 0x1
+    return p0
+    :sswitch_data_0
+    .sparse-switch
+        -0x80000000 -> :sswitch_0
+        0x1 -> :sswitch_1
+        0x7fffffff -> :sswitch_1
+    .end sparse-switch
+.end method
+"#;
+        let m = parse_method().parse(smali).unwrap();
+        let style = FormatStyle {
+            switch_keys: KeyRadix::Decimal,
+            ..Default::default()
+        };
+        let out = write_method_with(&m, &style);
+        assert!(
+            out.contains(
+                "    -2147483648 -> :sswitch_0\n    1 -> :sswitch_1\n    2147483647 -> :sswitch_1\n"
+            ),
+            "{out}"
+        );
+        assert_eq!(parse_method().parse(&out), Ok(m.clone()));
+        // hex stays the default, negative keys keep their sign
+        let hex = write_method(&m);
+        assert!(hex.contains("    -0x80000000 -> :sswitch_0\n"), "{hex}");
+        assert!(hex.contains("0x7fffffff -> :sswitch_1"));
+        assert_eq!(parse_method().parse(&hex), Ok(m.clone()));
+
+        // appending converts the line ends of the method only
+        let mut buf = String::from("# head\n");
+        let crlf = FormatStyle {
+            line_ending: crate::format::LineEnding::Crlf,
+            ..Default::default()
+        };
+        write_method_into(&m, &crlf, &mut buf);
+        assert_eq!(buf, format!("# head\n{}", write_method_with(&m, &crlf)));
+
+        let Some(Op::PackedSwitch(ps)) = parse_method()
+            .parse(".method static a(I)V\n    .locals 0\n    .packed-switch -0x2\n        :a\n    .end packed-switch\n.end method\n")
+            .unwrap()
+            .ops
+            .pop()
+        else {
+            panic!();
+        };
+        assert_eq!(
+            ps.write_with(&style),
+            ".packed-switch -2\n    :a\n.end packed-switch"
+        );
+    }
+
+    #[test]
+    fn test_to_owned() {
+        use super::*;
+        use crate::class::{parse_class, write_class};
+        use winnow::Parser;
+
+        let copy = {
+            let source = String::from(
+                ".method public static get()I\n    .locals 1\n    const/4 v0, 0x1\n    return v0\n.end method\n",
+            );
+            let m = parse_method().parse(source.as_str()).unwrap();
+            m.to_owned()
+        };
+        let mut renamed = copy.clone();
+        renamed.param.ident = Cow::Owned("getOne".to_string());
+
+        let mut class = {
+            let source = String::from(".class public LFoo;\n.super Ljava/lang/Object;\n");
+            parse_class().parse(source.as_str()).unwrap().into_owned()
+        };
+        class.methods.push(renamed);
+        let out = write_class(&class);
+        assert!(out.contains(".method public static getOne()I"), "{out}");
+        assert_eq!(copy.param.ident, "get");
+    }
+
+    #[test]
+    fn test_back_to_back_methods() {
+        use super::*;
+        use winnow::Parser;
+
+        let smali = ".method a()V\n    .locals 0\n    return-void\n.end method\n.method b()V\n    .locals 0\n    return-void\n.end method";
+        let methods: Vec<Method> = repeat(0.., parse_method()).parse(smali).unwrap();
+        let names: Vec<_> = methods.iter().map(|m| m.name()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_compact_registers() {
+        use super::*;
+        use winnow::Parser;
+
+        let smali = r#".method public static sum(IJ)J
+    .locals 12
+    const/4 v0, 0x1
+    const/4 v5, 0x2
+    add-int v9, v0, v5
+    int-to-long v10, v9
+    add-long/2addr v10, p1
+    invoke-static/range {v5 .. v6}, La;->b(II)V
+    return-wide v10
+.end method
+"#;
+        let mut m = parse_method().parse(smali).unwrap();
+        m.compact_registers();
+        assert_eq!(
+            write_method(&m),
+            r#".method public static sum(IJ)J
+    .locals 6
+    const/4 v0, 1
+    const/4 v1, 2
+    add-int v3, v0, v1
+    int-to-long v4, v3
+    add-long/2addr v4, p1
+    invoke-static/range { v1 .. v2 }, La;->b(II)V
+    return-wide v4
+.end method
+
+"#
+        );
+        m.check_locals().unwrap();
+
+        let mut m = parse_method()
+            .parse(".method static a()V\n    .locals 10\n    const/4 v0, 0x1\n    move v5, v0\n    add-int v9, v0, v5\n    return-void\n.end method\n")
+            .unwrap();
+        m.compact_registers();
+        assert_eq!(m.locals, Some(3));
+        let regs: Vec<_> = m
+            .instructions()
+            .flat_map(|(_, op)| op.registers())
+            .collect();
+        assert_eq!(regs, [0, 1, 0, 2, 0, 1].map(Register::Local),);
+
+        let mut m = parse_method()
+            .parse(".method static a(I)V\n    .registers 11\n    move v5, v10\n    return-void\n.end method\n")
+            .unwrap();
+        m.compact_registers();
+        assert_eq!(m.registers, Some(2));
+        let regs: Vec<_> = m
+            .instructions()
+            .flat_map(|(_, op)| op.registers())
+            .collect();
+        assert_eq!(regs, [Register::Local(0), Register::Parameter(0)]);
+    }
 }
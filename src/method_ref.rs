@@ -1,10 +1,14 @@
-use std::fmt;
+use alloc::borrow::Cow;
+use core::{fmt, str::FromStr};
 
-use winnow::{ModalParser, Parser, combinator::terminated, error::InputError, token::literal};
+use winnow::{ModalParser, Parser, combinator::terminated, token::literal};
 
-use crate::signature::{
-    method_signature::{MethodParameter, parse_method_parameter},
-    type_signature::{TypeSignature, parse_typesignature},
+use crate::{
+    ParseFailure, ParseOptions, SmaliError, parse_complete,
+    signature::{
+        method_signature::{MethodParameter, MethodSignature, parse_method_parameter_with},
+        type_signature::{TypeSignature, parse_typesignature_with},
+    },
 };
 
 /// A symbolic reference to a method.
@@ -15,27 +19,82 @@ pub struct MethodRef<'a> {
     pub param: MethodParameter<'a>,
 }
 
+impl<'a> MethodRef<'a> {
+    /// A reference to the method `name` with signature `sig` declared in `class`, which
+    /// is an array type for methods such as `clone` called on arrays
+    pub fn new(
+        class: TypeSignature<'a>,
+        name: impl Into<Cow<'a, str>>,
+        sig: MethodSignature<'a>,
+    ) -> Self {
+        MethodRef {
+            class,
+            param: MethodParameter {
+                ident: name.into(),
+                ms: sig,
+            },
+        }
+    }
+
+    /// Copies any borrowed text, detaching the reference from the parsed source
+    pub fn into_owned(self) -> MethodRef<'static> {
+        MethodRef {
+            class: self.class.into_owned(),
+            param: self.param.into_owned(),
+        }
+    }
+
+    /// Whether the method returns a `J` or `D`, which occupies a register pair.
+    ///
+    /// Use it to pick the move after an `Invoke`: `returns_void()` needs no move,
+    /// a wide result needs `OneRegMoveType::ResultWide`, objects and arrays need
+    /// `ResultObject` and everything else `Result`.
+    pub fn returns_wide(&self) -> bool {
+        self.param.ms.result.is_wide()
+    }
+
+    /// Whether the method returns `V`, leaving nothing for a `move-result`
+    pub fn returns_void(&self) -> bool {
+        self.param.ms.result == TypeSignature::Void
+    }
+}
+
 impl fmt::Display for MethodRef<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Example: Lkotlin/jvm/internal/Intrinsics;->checkNotNullParameter(Ljava/lang/Object;Ljava/lang/String;)V
         write!(
             f,
             "{}->{}{}",
-            self.class,
+            self.class.descriptor(),
             self.param.ident,
             self.param.ms.to_jni()
         )
     }
 }
 
+/// Parses a reference such as `Lcom/example/MyClass;->myMethod(I)V`
+impl FromStr for MethodRef<'static> {
+    type Err = SmaliError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(s, "method reference", parse_method_ref()).map(MethodRef::into_owned)
+    }
+}
+
 /// Parse a method reference of the form:
 ///    L<class>;-><method>(<args>)<ret>
 /// For example:
 ///    Lkotlin/jvm/internal/Intrinsics;->checkNotNullParameter(Ljava/lang/Object;Ljava/lang/String;)V
-pub fn parse_method_ref<'a>() -> impl ModalParser<&'a str, MethodRef<'a>, InputError<&'a str>> {
+pub fn parse_method_ref<'a>() -> impl ModalParser<&'a str, MethodRef<'a>, ParseFailure> {
+    parse_method_ref_with(ParseOptions::default())
+}
+
+/// Parses a method reference with the nesting limit of `options`
+pub fn parse_method_ref_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, MethodRef<'a>, ParseFailure> {
     (
-        terminated(parse_typesignature(), literal("->")),
-        parse_method_parameter(),
+        terminated(parse_typesignature_with(options), literal("->")),
+        parse_method_parameter_with(options),
     )
         .map(|(class, param)| MethodRef { class, param })
 }
@@ -48,4 +107,77 @@ mod tests {
         let input = "[La0h;->clone()Ljava/lang/Object;";
         let _ = parse_method_ref().parse(input).unwrap();
     }
+
+    #[test]
+    fn method_ref_special_names() {
+        use super::*;
+        use crate::prelude::*;
+        use winnow::Parser;
+        for input in [
+            "Lcom/example/Foo;-><clinit>()V",
+            "Ljava/lang/Object;-><init>()V",
+            "La/b;->a$b$0(I)La/b;",
+            "La/b;->\u{3b1}\u{3b2}_\u{1f600}([Ljava/lang/String;)V",
+            "Lkotlin/Result;->constructor-impl(Ljava/lang/Object;)Ljava/lang/Object;",
+        ] {
+            let m = parse_method_ref().parse(input).unwrap();
+            assert_eq!(m.to_string(), input);
+        }
+
+        let m = parse_method_ref()
+            .parse("Lcom/example/Foo;-><clinit>()V")
+            .unwrap();
+        assert_eq!(m.param.ident, "<clinit>");
+    }
+
+    #[test]
+    fn method_ref_generic_name() {
+        use super::*;
+        use crate::prelude::*;
+        use winnow::Parser;
+        let input = "Lcom/example/Foo;->foo<T:Ljava/lang/Object;>(TT;)V";
+        let m = parse_method_ref().parse(input).unwrap();
+        assert_eq!(m.param.ident, "foo");
+        assert_eq!(m.param.ms.type_parameters.as_ref().map(Vec::len), Some(1));
+        assert_eq!(m.param.ms.descriptor(), "(Ljava/lang/Object;)V");
+        assert_eq!(m.to_string(), input);
+    }
+
+    #[test]
+    fn method_ref_returns() {
+        use super::*;
+        use winnow::Parser;
+        let m = parse_method_ref()
+            .parse("Ljava/lang/System;->currentTimeMillis()J")
+            .unwrap();
+        assert!(m.returns_wide());
+        assert!(!m.returns_void());
+
+        let m = parse_method_ref()
+            .parse("Ljava/lang/Object;-><init>()V")
+            .unwrap();
+        assert!(!m.returns_wide());
+        assert!(m.returns_void());
+    }
+
+    #[test]
+    fn method_ref_from_str() {
+        use super::*;
+        use crate::prelude::*;
+
+        let r: MethodRef = "Lcom/example/MyClass;->baz(ILjava/lang/String;)V"
+            .parse()
+            .unwrap();
+        let expected = MethodRef::new(
+            TypeSignature::from_jni("Lcom/example/MyClass;"),
+            "baz",
+            MethodSignature::from_jni("(ILjava/lang/String;)V").into_owned(),
+        );
+        assert_eq!(r, expected);
+        assert_eq!(
+            r.to_string(),
+            "Lcom/example/MyClass;->baz(ILjava/lang/String;)V"
+        );
+        assert!("Lcom/example/MyClass;->baz".parse::<MethodRef>().is_err());
+    }
 }
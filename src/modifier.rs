@@ -1,13 +1,13 @@
-use std::str::FromStr;
+use core::str::FromStr;
 
 use winnow::{
     ModalParser, Parser,
-    combinator::{alt, repeat},
-    error::InputError,
-    token::literal,
+    ascii::space1,
+    combinator::{alt, peek, repeat, terminated},
+    token::{literal, take_while},
 };
 
-use crate::{SmaliError, ws};
+use crate::{ParseFailure, SmaliError, prelude::*, ws};
 
 /// Simple enum to represent Java method, field and class modifiers
 ///
@@ -90,9 +90,37 @@ impl Modifier {
             Self::DeclaredSynchronized => "synchronized",
         }
     }
+
+    /// The `access_flags` bit dex uses for this modifier, e.g. `0x1` for `public`
+    pub fn flag(&self) -> u32 {
+        match self {
+            Self::Public => 0x1,
+            Self::Private => 0x2,
+            Self::Protected => 0x4,
+            Self::Static => 0x8,
+            Self::Final => 0x10,
+            Self::Synchronized => 0x20,
+            Self::Volatile | Self::Bridge => 0x40,
+            Self::Transient | Self::Varargs => 0x80,
+            Self::Native => 0x100,
+            Self::Interface => 0x200,
+            Self::Abstract => 0x400,
+            Self::Strict => 0x800,
+            Self::Synthetic => 0x1000,
+            Self::Annotation => 0x2000,
+            Self::Enum => 0x4000,
+            Self::Constructor => 0x10000,
+            Self::DeclaredSynchronized => 0x20000,
+        }
+    }
 }
 
-pub fn parse_modifiers<'a>() -> impl ModalParser<&'a str, Vec<Modifier>, InputError<&'a str>> {
+/// Combines the modifiers into dex `access_flags`, e.g. `0x11` for `public final`
+pub fn modifiers_to_flags(mods: &[Modifier]) -> u32 {
+    mods.iter().fold(0, |flags, m| flags | m.flag())
+}
+
+pub fn parse_modifiers<'a>() -> impl ModalParser<&'a str, Vec<Modifier>, ParseFailure> {
     repeat(
         0..,
         ws(alt((
@@ -127,3 +155,79 @@ pub fn write_modifiers(mods: &Vec<Modifier>) -> String {
 
     out
 }
+
+/// A hidden API restriction, which baksmali writes after the modifiers of members of
+/// Android framework classes, e.g. `.method public blacklist core-platform-api foo()V`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HiddenApiRestriction {
+    Whitelist,
+    Greylist,
+    Blacklist,
+    GreylistMaxO,
+    GreylistMaxP,
+    GreylistMaxQ,
+    GreylistMaxR,
+    CorePlatformApi,
+    TestApi,
+}
+
+impl FromStr for HiddenApiRestriction {
+    type Err = SmaliError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "whitelist" => Self::Whitelist,
+            "greylist" => Self::Greylist,
+            "blacklist" => Self::Blacklist,
+            "greylist-max-o" => Self::GreylistMaxO,
+            "greylist-max-p" => Self::GreylistMaxP,
+            "greylist-max-q" => Self::GreylistMaxQ,
+            "greylist-max-r" => Self::GreylistMaxR,
+            "core-platform-api" => Self::CorePlatformApi,
+            "test-api" => Self::TestApi,
+            _ => {
+                return Err(SmaliError {
+                    details: format!("Unknown hidden api restriction {s}"),
+                });
+            }
+        })
+    }
+}
+
+impl HiddenApiRestriction {
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Whitelist => "whitelist",
+            Self::Greylist => "greylist",
+            Self::Blacklist => "blacklist",
+            Self::GreylistMaxO => "greylist-max-o",
+            Self::GreylistMaxP => "greylist-max-p",
+            Self::GreylistMaxQ => "greylist-max-q",
+            Self::GreylistMaxR => "greylist-max-r",
+            Self::CorePlatformApi => "core-platform-api",
+            Self::TestApi => "test-api",
+        }
+    }
+}
+
+/// Parses the hidden API restrictions following a member's modifiers. Each has to be
+/// followed by a space, so a member named e.g. `blacklist` isn't mistaken for one.
+pub fn parse_hidden_api_restrictions<'a>()
+-> impl ModalParser<&'a str, Vec<HiddenApiRestriction>, ParseFailure> {
+    repeat(
+        0..,
+        ws(terminated(
+            take_while(1.., |c: char| c.is_ascii_lowercase() || c == '-'),
+            peek(space1),
+        )
+        .try_map(HiddenApiRestriction::from_str)),
+    )
+}
+
+pub fn write_hidden_api_restrictions(restrictions: &[HiddenApiRestriction]) -> String {
+    let mut out = String::new();
+    for r in restrictions {
+        out.push_str(r.to_str());
+        out.push(' ');
+    }
+    out
+}
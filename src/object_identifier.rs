@@ -1,6 +1,7 @@
-use core::fmt;
-use std::{
-    borrow::Cow,
+use alloc::borrow::Cow;
+use core::{
+    cmp::Ordering,
+    fmt,
     hash::{Hash, Hasher},
 };
 
@@ -8,11 +9,14 @@ use serde::{Deserialize, Serialize};
 use winnow::{
     ModalParser, Parser,
     combinator::{delimited, opt, preceded},
-    error::InputError,
     token::{one_of, take_while},
 };
 
-use crate::signature::{parse_type_parameters, type_signature::TypeSignature};
+use crate::{
+    ParseFailure, ParseOptions,
+    prelude::*,
+    signature::{type_parameters, type_signature::TypeSignature},
+};
 
 /// Represents a Java object identifier
 ///
@@ -40,6 +44,21 @@ impl Hash for ObjectIdentifier<'_> {
     }
 }
 
+impl PartialOrd for ObjectIdentifier<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by class name first, so identifiers of the same class sort together
+impl Ord for ObjectIdentifier<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.class_name
+            .cmp(&other.class_name)
+            .then_with(|| self.as_jni_type().cmp(&other.as_jni_type()))
+    }
+}
+
 impl fmt::Display for ObjectIdentifier<'_> {
     // This trait requires `fmt` with this exact signature.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -51,7 +70,36 @@ impl fmt::Display for ObjectIdentifier<'_> {
     }
 }
 
-impl ObjectIdentifier<'_> {
+impl<'a> ObjectIdentifier<'a> {
+    /// An identifier for the slash separated class name, e.g. `com/basic/Test`
+    pub fn new(class_name: impl Into<Cow<'a, str>>) -> Self {
+        ObjectIdentifier {
+            class_name: class_name.into(),
+            type_arguments: None,
+            suffix: None,
+        }
+    }
+
+    /// Copies any borrowed text, detaching the identifier from the parsed source
+    pub fn into_owned(self) -> ObjectIdentifier<'static> {
+        ObjectIdentifier {
+            class_name: Cow::Owned(self.class_name.into_owned()),
+            type_arguments: self
+                .type_arguments
+                .map(|v| v.into_iter().map(TypeSignature::into_owned).collect()),
+            suffix: self.suffix.map(|s| Cow::Owned(s.into_owned())),
+        }
+    }
+
+    /// Creates an identifier from a dotted Java name, e.g. `com.basic.Test`
+    pub fn from_java_type(s: &str) -> ObjectIdentifier<'static> {
+        ObjectIdentifier {
+            class_name: Cow::Owned(s.replace('.', "/")),
+            type_arguments: None,
+            suffix: None,
+        }
+    }
+
     pub fn as_jni_type(&self) -> String {
         let mut s = "L".to_string();
         s.push_str(&self.class_name);
@@ -70,18 +118,70 @@ impl ObjectIdentifier<'_> {
         s
     }
 
+    /// The raw class this identifier names: type arguments dropped and any
+    /// `.Inner` suffix folded into the binary name, e.g. `Lfoo/Outer$Inner;`
+    pub fn erased(&self) -> ObjectIdentifier<'a> {
+        let class_name = match &self.suffix {
+            Some(suffix) => {
+                let mut name = self.class_name.to_string();
+                for inner in suffix.split('.') {
+                    name.push('$');
+                    name.push_str(inner.split('<').next().unwrap_or(inner));
+                }
+                Cow::Owned(name)
+            }
+            None => self.class_name.clone(),
+        };
+        ObjectIdentifier {
+            class_name,
+            type_arguments: None,
+            suffix: None,
+        }
+    }
+
+    /// The same identifier naming an inner class of it, as in generic signatures
+    /// where the inner class follows the outer type arguments: `Lfoo/Outer<TT;>.Inner;`
+    pub fn with_suffix(mut self, suffix: impl Into<Cow<'a, str>>) -> ObjectIdentifier<'a> {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    /// The dotted Java name including any inner class suffix, e.g. `foo.Outer.Inner`.
+    /// Type arguments are left out.
     pub fn as_java_type(&self) -> String {
-        self.class_name.replace('/', ".")
+        let mut s = self.class_name.replace('/', ".");
+        if let Some(suffix) = &self.suffix {
+            for inner in suffix.split('.') {
+                s.push('.');
+                s.push_str(inner.split('<').next().unwrap_or(inner));
+            }
+        }
+        s
     }
 }
 
-pub fn parse_object_identifier<'a>()
--> impl ModalParser<&'a str, ObjectIdentifier<'a>, InputError<&'a str>> {
+pub fn parse_object_identifier<'a>() -> impl ModalParser<&'a str, ObjectIdentifier<'a>, ParseFailure>
+{
+    parse_object_identifier_with(ParseOptions::default())
+}
+
+/// Parses an object type with the nesting limit of `options`
+pub fn parse_object_identifier_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, ObjectIdentifier<'a>, ParseFailure> {
+    object_identifier(options, 0)
+}
+
+/// An object type whose type arguments are `depth` levels into another type
+pub(crate) fn object_identifier<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, ObjectIdentifier<'a>, ParseFailure> {
     delimited(
         one_of('L'),
         (
             take_while(0.., |x| (x != ';') && (x != '<')),
-            opt(parse_type_parameters()),
+            opt(type_parameters(options, depth)),
             opt(preceded(one_of('.'), take_while(0.., |x| x != ';'))),
         ),
         one_of(';'),
@@ -2,33 +2,60 @@
 //! What about multispace0 and space0? Where i can set this?
 //! Is there any .smali grammar docs? Like c++
 
-use std::{
-    borrow::Cow,
+use alloc::borrow::Cow;
+use core::{
     fmt::{self, Debug},
     str::FromStr,
 };
 
 use winnow::{
     ModalParser, ModalResult, Parser,
-    ascii::{alphanumeric1, digit1, space1},
+    ascii::{digit1, multispace0, space1},
     combinator::{alt, delimited, preceded, separated, terminated},
-    error::{ErrMode, InputError},
-    token::{literal, one_of, take_while},
+    error::ErrMode,
+    token::{literal, one_of, take_until, take_while},
 };
 
 use crate::{
-    field_ref::{FieldRef, parse_field_ref},
-    method_ref::{MethodRef, parse_method_ref},
-    op::{Label, parse_label},
+    ParseFailure, ParseOptions, SmaliError, escape_string, fail,
+    field_ref::{FieldRef, parse_field_ref_with},
+    method_ref::{MethodRef, parse_method_ref_with},
+    object_identifier::ObjectIdentifier,
+    op::{Label, parse_label, write_signed_hex},
     parse_int_lit, parse_string_lit,
-    signature::type_signature::{TypeSignature, parse_typesignature},
+    prelude::*,
+    signature::{
+        method_signature::{MethodSignature, parse_methodsignature_with},
+        type_signature::{TypeSignature, parse_typesignature_with},
+    },
     ws,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg(feature = "odex")]
+use crate::op::odex::{OdexOp, parse_odex_op};
+
+/// A register operand as written in smali.
+///
+/// Registers order by name rather than by frame slot, since the slot of `pN` depends
+/// on the method's `.locals`: every `vN` sorts before every `pN`, then by number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Register {
-    Parameter(u16),
     Local(u16),
+    Parameter(u16),
+}
+
+impl Register {
+    /// The two registers holding a wide (`J` or `D`) value that starts here, `(vN, vN+1)`.
+    /// `None` for `v65535` or `p65535`, which have no register after them.
+    pub fn wide_pair(&self) -> Option<(Register, Register)> {
+        match *self {
+            Register::Local(n) => Some((Register::Local(n), Register::Local(n.checked_add(1)?))),
+            Register::Parameter(n) => Some((
+                Register::Parameter(n),
+                Register::Parameter(n.checked_add(1)?),
+            )),
+        }
+    }
 }
 
 impl fmt::Display for Register {
@@ -172,6 +199,7 @@ impl fmt::Display for ConstType {
 pub enum TwoRegMoveType {
     Normal,
     From16,
+    Move16,
     Wide,
     WideFrom16,
     Wide16,
@@ -186,6 +214,7 @@ impl FromStr for TwoRegMoveType {
         match s {
             "move" => Ok(TwoRegMoveType::Normal),
             "move/from16" => Ok(TwoRegMoveType::From16),
+            "move/16" => Ok(TwoRegMoveType::Move16),
             "move-wide" => Ok(TwoRegMoveType::Wide),
             "move-wide/from16" => Ok(TwoRegMoveType::WideFrom16),
             "move-wide/16" => Ok(TwoRegMoveType::Wide16),
@@ -202,6 +231,7 @@ impl fmt::Display for TwoRegMoveType {
         match self {
             TwoRegMoveType::Normal => write!(f, "move"),
             TwoRegMoveType::From16 => write!(f, "move/from16"),
+            TwoRegMoveType::Move16 => write!(f, "move/16"),
             TwoRegMoveType::Wide => write!(f, "move-wide"),
             TwoRegMoveType::WideFrom16 => write!(f, "move-wide/from16"),
             TwoRegMoveType::Wide16 => write!(f, "move-wide/16"),
@@ -295,7 +325,7 @@ impl FromStr for StaticFieldAccessType {
 }
 
 impl fmt::Display for StaticFieldAccessType {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             StaticFieldAccessType::Get => write!(f, "sget"),
             StaticFieldAccessType::Put => write!(f, "sput"),
@@ -322,7 +352,7 @@ impl FromStr for DynamicFieldAccessType {
 }
 
 impl fmt::Display for DynamicFieldAccessType {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             DynamicFieldAccessType::Get => write!(f, "iget"),
             DynamicFieldAccessType::Put => write!(f, "iput"),
@@ -672,9 +702,26 @@ pub enum ConstLiteralValue {
     ConstWide16(i16),
     ConstWide32(i32),
     ConstWide(i64),
+    /// The top 16 bits of the loaded long, sign-extended; written back as the
+    /// full signed literal, e.g. `-0x8000000000000000L` for `-0x8000`.
     ConstWideHigh16(i64),
 }
 
+impl ConstLiteralValue {
+    /// The number loaded into the register, with the high16 forms shifted into place,
+    /// e.g. `0x7f0a0000` for `const/high16 v0, 0x7f0a0000`
+    pub fn value(&self) -> i64 {
+        match *self {
+            ConstLiteralValue::Const4(v) => v.into(),
+            ConstLiteralValue::Const16(v) | ConstLiteralValue::ConstWide16(v) => v.into(),
+            ConstLiteralValue::Const(v) | ConstLiteralValue::ConstWide32(v) => v.into(),
+            ConstLiteralValue::ConstHigh16(v) => v << 16,
+            ConstLiteralValue::ConstWide(v) => v,
+            ConstLiteralValue::ConstWideHigh16(v) => v << 48,
+        }
+    }
+}
+
 impl fmt::Display for ConstLiteralValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -686,7 +733,8 @@ impl fmt::Display for ConstLiteralValue {
             ConstLiteralValue::ConstWide32(v) => write!(f, "{v}"),
             ConstLiteralValue::ConstWide(v) => write!(f, "0x{v:x}L"),
             ConstLiteralValue::ConstWideHigh16(v) => {
-                write!(f, "0x{:04x}000000000000", *v as u16)
+                write_signed_hex(f, *v << 48)?;
+                write!(f, "L")
             }
         }
     }
@@ -1129,10 +1177,12 @@ pub enum DexOp<'a> {
         size_reg: Register,
         class: StringOrTypeSig<'a>,
     },
+    /// `class` is the type of the array being created, e.g. `[I`, not its element type
     FilledNewArray {
         registers: Vec<Register>,
         class: StringOrTypeSig<'a>,
     },
+    /// `class` is the type of the array being created, see [`DexOp::FilledNewArray`]
     FilledNewArrayRange {
         registers: RegisterRange,
         class: StringOrTypeSig<'a>,
@@ -1152,6 +1202,8 @@ pub enum DexOp<'a> {
     Unused {
         opcode: u8,
     },
+    #[cfg(feature = "odex")]
+    Odex(OdexOp<'a>),
 }
 
 impl fmt::Display for DexOp<'_> {
@@ -1379,152 +1431,1130 @@ impl fmt::Display for DexOp<'_> {
             }
             DexOp::Throw { src } => write!(f, "throw {src}"),
             DexOp::Unused { opcode } => write!(f, "unused {opcode}"),
+            #[cfg(feature = "odex")]
+            DexOp::Odex(op) => write!(f, "{op}"),
         }
     }
 }
 
-/// Parse a register reference like "v0" or "p1", returning its number.
-pub fn parse_register<'a>() -> impl ModalParser<&'a str, Register, InputError<&'a str>> {
-    ws(
-        (alt((one_of('v'), one_of('p'))), digit1).try_map(|(t, o): (char, &str)| {
-            let num = o.parse::<u16>().map_err(|_| InputError::at(o))?;
-            Ok::<Register, InputError<&str>>(match t {
-                'v' => Register::Local(num),
-                'p' => Register::Parameter(num),
-                _ => unreachable!(),
-            })
-        }),
-    )
-}
-
-/// Parse a comma-separated list of registers inside curly braces.
-fn parse_register_list<'a>() -> impl ModalParser<&'a str, Vec<Register>, InputError<&'a str>> {
-    delimited(
-        ws(one_of('{')),
-        separated(0.., parse_register(), ws(one_of(','))),
-        ws(one_of('}')),
-    )
-}
-
-fn parse_const_high16<'a>() -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>> {
-    preceded(
-        space1,
-        (parse_register(), ws(one_of(',')), parse_int_lit::<i32>()).map(|(dest, _, value32)| {
-            let value = (value32 >> 16) as i64;
-            DexOp::ConstLiteral {
-                const_type: ConstLiteralType::ConstHigh16,
+impl<'a> DexOp<'a> {
+    /// Copies any borrowed text, detaching the operation from the parsed source
+    pub fn into_owned(self) -> DexOp<'static> {
+        match self {
+            DexOp::Invoke {
+                invoke_type,
+                registers,
+                range,
+                method,
+                call_site,
+                proto,
+            } => DexOp::Invoke {
+                invoke_type,
+                registers,
+                range,
+                method: method.map(|m| Box::new(m.into_owned())),
+                call_site: call_site.map(|c| Cow::Owned(c.into_owned())),
+                proto: proto.map(|c| Cow::Owned(c.into_owned())),
+            },
+            DexOp::Const {
+                const_type,
                 dest,
-                value: ConstLiteralValue::ConstHigh16(value),
-            }
-        }),
-    )
-}
-
-fn parse_const_wide_high16<'a>() -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>> {
-    preceded(
-        space1,
-        (parse_register(), ws(one_of(',')), parse_int_lit::<i64>()).map(|(dest, _, value64)| {
-            let value = value64 >> 48;
+                value,
+            } => DexOp::Const {
+                const_type,
+                dest,
+                value: value.into_owned(),
+            },
+            DexOp::MoveTwoReg {
+                move_type,
+                dest,
+                src,
+            } => DexOp::MoveTwoReg {
+                move_type,
+                dest,
+                src,
+            },
+            DexOp::MoveOneReg { move_type, dest } => DexOp::MoveOneReg { move_type, dest },
+            DexOp::Return { return_type, src } => DexOp::Return { return_type, src },
+            DexOp::Arith {
+                arith_type,
+                operand_type,
+                dest,
+                src1,
+                src2,
+            } => DexOp::Arith {
+                arith_type,
+                operand_type,
+                dest,
+                src1,
+                src2,
+            },
+            DexOp::ArithUnary {
+                arith_type,
+                operand_type,
+                dest,
+                src,
+            } => DexOp::ArithUnary {
+                arith_type,
+                operand_type,
+                dest,
+                src,
+            },
+            DexOp::Arith2Addr {
+                arith_type,
+                operand_type,
+                dest,
+                src,
+            } => DexOp::Arith2Addr {
+                arith_type,
+                operand_type,
+                dest,
+                src,
+            },
+            DexOp::Condition {
+                cond_type,
+                reg1,
+                offset,
+            } => DexOp::Condition {
+                cond_type,
+                reg1,
+                offset: offset.into_owned(),
+            },
+            DexOp::TwoRegCondition {
+                cond_type,
+                reg1,
+                reg2,
+                offset,
+            } => DexOp::TwoRegCondition {
+                cond_type,
+                reg1,
+                reg2,
+                offset: offset.into_owned(),
+            },
+            DexOp::Goto { goto_type, offset } => DexOp::Goto {
+                goto_type,
+                offset: offset.into_owned(),
+            },
             DexOp::ConstLiteral {
-                const_type: ConstLiteralType::ConstWideHigh16,
+                const_type,
                 dest,
-                value: ConstLiteralValue::ConstWideHigh16(value),
-            }
-        }),
-    )
-}
+                value,
+            } => DexOp::ConstLiteral {
+                const_type,
+                dest,
+                value,
+            },
+            DexOp::LitArith8 {
+                arith_type,
+                dest,
+                src,
+                literal,
+            } => DexOp::LitArith8 {
+                arith_type,
+                dest,
+                src,
+                literal,
+            },
+            DexOp::LitArith16 {
+                arith_type,
+                dest,
+                src,
+                literal,
+            } => DexOp::LitArith16 {
+                arith_type,
+                dest,
+                src,
+                literal,
+            },
+            DexOp::Convert {
+                convert_type,
+                dest,
+                src,
+            } => DexOp::Convert {
+                convert_type,
+                dest,
+                src,
+            },
+            DexOp::Cmp {
+                cmp_type,
+                dest,
+                src1,
+                src2,
+            } => DexOp::Cmp {
+                cmp_type,
+                dest,
+                src1,
+                src2,
+            },
+            DexOp::ArrayAccess {
+                access_type,
+                value_type,
+                reg,
+                arr,
+                idx,
+            } => DexOp::ArrayAccess {
+                access_type,
+                value_type,
+                reg,
+                arr,
+                idx,
+            },
+            DexOp::DynamicFieldAccess {
+                access_type,
+                value_type,
+                reg,
+                object,
+                field,
+            } => DexOp::DynamicFieldAccess {
+                access_type,
+                value_type,
+                reg,
+                object,
+                field: field.into_owned(),
+            },
+            DexOp::StaticFieldAccess {
+                access_type,
+                value_type,
+                reg,
+                field,
+            } => DexOp::StaticFieldAccess {
+                access_type,
+                value_type,
+                reg,
+                field: field.into_owned(),
+            },
+            DexOp::Nop => DexOp::Nop,
+            DexOp::MonitorEnter { src } => DexOp::MonitorEnter { src },
+            DexOp::MonitorExit { src } => DexOp::MonitorExit { src },
+            DexOp::CheckCast { dest, class } => DexOp::CheckCast {
+                dest,
+                class: class.into_owned(),
+            },
+            DexOp::InstanceOf { dest, src, class } => DexOp::InstanceOf {
+                dest,
+                src,
+                class: class.into_owned(),
+            },
+            DexOp::ArrayLength { dest, array } => DexOp::ArrayLength { dest, array },
+            DexOp::NewInstance { dest, class } => DexOp::NewInstance {
+                dest,
+                class: class.into_owned(),
+            },
+            DexOp::NewArray {
+                dest,
+                size_reg,
+                class,
+            } => DexOp::NewArray {
+                dest,
+                size_reg,
+                class: class.into_owned(),
+            },
+            DexOp::FilledNewArray { registers, class } => DexOp::FilledNewArray {
+                registers,
+                class: class.into_owned(),
+            },
+            DexOp::FilledNewArrayRange { registers, class } => DexOp::FilledNewArrayRange {
+                registers,
+                class: class.into_owned(),
+            },
+            DexOp::FillArrayData { reg, offset } => DexOp::FillArrayData {
+                reg,
+                offset: offset.into_owned(),
+            },
+            DexOp::Throw { src } => DexOp::Throw { src },
+            DexOp::Switch {
+                switch_type,
+                reg,
+                offset,
+            } => DexOp::Switch {
+                switch_type,
+                reg,
+                offset: offset.into_owned(),
+            },
+            DexOp::Unused { opcode } => DexOp::Unused { opcode },
+            #[cfg(feature = "odex")]
+            DexOp::Odex(op) => DexOp::Odex(op.into_owned()),
+        }
+    }
 
-/// Parses a register range enclosed in braces, e.g. "{v0 .. v6}".
-/// Returns a tuple (first_reg, last_reg)
-fn parse_register_range<'a>() -> impl ModalParser<&'a str, RegisterRange, InputError<&'a str>> {
-    delimited(
-        ws(one_of('{')),
-        (
-            terminated(parse_register(), literal("..")),
-            parse_register(),
-        )
-            .map(|(start, end)| RegisterRange { start, end }),
-        ws(one_of('}')),
-    )
-}
+    /// Returns the label this operation branches to, if any.
+    ///
+    /// For `switch` and `fill-array-data` this is the label of the payload directive.
+    pub fn target_label(&self) -> Option<&Label<'a>> {
+        match self {
+            DexOp::Condition { offset, .. }
+            | DexOp::TwoRegCondition { offset, .. }
+            | DexOp::Goto { offset, .. }
+            | DexOp::Switch { offset, .. }
+            | DexOp::FillArrayData { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
 
-fn parse_invoke_polymorphic<'a>() -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>> {
-    preceded(
-        space1,
-        (
-            parse_register_list(),
-            delimited(ws(one_of(',')), parse_method_ref(), ws(one_of(','))),
-            alphanumeric1,
-        )
-            .map(|(registers, method, proto)| DexOp::Invoke {
-                invoke_type: InvokeType::Polymorphic,
-                registers,
-                range: None,
-                method: Some(Box::new(method)),
-                call_site: None,
-                proto: Some(Cow::Borrowed(proto)),
-            }),
-    )
-}
+    /// Mutable access to the label returned by [`DexOp::target_label`].
+    pub fn target_label_mut(&mut self) -> Option<&mut Label<'a>> {
+        match self {
+            DexOp::Condition { offset, .. }
+            | DexOp::TwoRegCondition { offset, .. }
+            | DexOp::Goto { offset, .. }
+            | DexOp::Switch { offset, .. }
+            | DexOp::FillArrayData { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
 
-fn parse_invoke_polymorphic_range<'a>() -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
-{
-    preceded(
-        space1,
-        (
-            parse_register_range(),
-            delimited(ws(one_of(',')), parse_method_ref(), ws(one_of(','))),
-            alphanumeric1,
-        )
-            .map(|(range, method, proto)| DexOp::Invoke {
-                invoke_type: InvokeType::PolymorphicRange,
-                registers: Vec::new(),
-                range: Some(range),
-                method: Some(Box::new(method)),
-                call_site: None,
-                proto: Some(Cow::Borrowed(proto)),
-            }),
-    )
-}
+    /// Returns every label referenced by the operation.
+    ///
+    /// Each instruction names at most one label; the targets of a switch are listed
+    /// by its payload directive rather than by the instruction itself.
+    pub fn target_labels(&self) -> Vec<&Label<'a>> {
+        self.target_label().into_iter().collect()
+    }
 
-fn parse_invoke_custom<'a>() -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>> {
-    preceded(
-        space1,
-        (parse_register_list(), ws(one_of(',')), alphanumeric1).map(|(registers, _, call_site)| {
+    /// Loads the string `value` into `dest` with `const-string`, escaping it as smali
+    /// writes it
+    pub fn const_string(dest: Register, value: &str) -> DexOp<'a> {
+        DexOp::Const {
+            const_type: ConstType::String,
+            dest,
+            value: StringOrTypeSig::String(Cow::Owned(escape_string(value))),
+        }
+    }
+
+    /// Loads `value` into `dest` with the shortest of `const/4`, `const/16` and `const`
+    /// that holds it
+    pub fn const_int(dest: Register, value: i32) -> DexOp<'a> {
+        let (const_type, value) = if (-8..8).contains(&value) {
+            (
+                ConstLiteralType::Const4,
+                ConstLiteralValue::Const4(value as i8),
+            )
+        } else if let Ok(v) = i16::try_from(value) {
+            (ConstLiteralType::Const16, ConstLiteralValue::Const16(v))
+        } else {
+            (ConstLiteralType::Const, ConstLiteralValue::Const(value))
+        };
+        DexOp::ConstLiteral {
+            const_type,
+            dest,
+            value,
+        }
+    }
+
+    /// Loads `value` into the pair at `dest` with the shortest of `const-wide/16`,
+    /// `const-wide/32`, `const-wide/high16` and `const-wide` that holds it
+    pub fn const_wide(dest: Register, value: i64) -> DexOp<'a> {
+        let (const_type, value) = if let Ok(v) = i16::try_from(value) {
+            (
+                ConstLiteralType::ConstWide16,
+                ConstLiteralValue::ConstWide16(v),
+            )
+        } else if let Ok(v) = i32::try_from(value) {
+            (
+                ConstLiteralType::ConstWide32,
+                ConstLiteralValue::ConstWide32(v),
+            )
+        } else if value & 0xffff_ffff_ffff == 0 {
+            (
+                ConstLiteralType::ConstWideHigh16,
+                ConstLiteralValue::ConstWideHigh16(value >> 48),
+            )
+        } else {
+            (
+                ConstLiteralType::ConstWide,
+                ConstLiteralValue::ConstWide(value),
+            )
+        };
+        DexOp::ConstLiteral {
+            const_type,
+            dest,
+            value,
+        }
+    }
+
+    /// The integer a `const`, `const/4`, `const-wide` or other literal const loads,
+    /// e.g. to spot resource IDs shaped like `0x7fXXXXXX`
+    pub fn const_value(&self) -> Option<i64> {
+        match self {
+            DexOp::ConstLiteral { value, .. } => Some(value.value()),
+            _ => None,
+        }
+    }
+
+    /// The dalvik opcode byte, e.g. `0x00` for `nop` or `0x0e` for `return-void`.
+    /// `None` for combinations no opcode encodes, such as `not-float` or `shl-double`.
+    pub fn opcode(&self) -> Option<u8> {
+        // float and double arithmetic stops at `rem`, which ends the list of types
+        fn arith(arith_type: ArithType, operand: ArithOperandType, base: u8) -> Option<u8> {
+            let index = match arith_type {
+                ArithType::Add => 0,
+                ArithType::Sub => 1,
+                ArithType::Mul => 2,
+                ArithType::Div => 3,
+                ArithType::Rem => 4,
+                ArithType::And => 5,
+                ArithType::Or => 6,
+                ArithType::Xor => 7,
+                ArithType::Shl => 8,
+                ArithType::Shr => 9,
+                ArithType::Ushr => 10,
+            };
+            Some(match operand {
+                ArithOperandType::Int => base + index,
+                ArithOperandType::Long => base + 11 + index,
+                ArithOperandType::Float if index < 5 => base + 22 + index,
+                ArithOperandType::Double if index < 5 => base + 27 + index,
+                _ => return None,
+            })
+        }
+        let value_index = |value_type: FieldValueType| match value_type {
+            FieldValueType::Normal => 0,
+            FieldValueType::Wide => 1,
+            FieldValueType::Object => 2,
+            FieldValueType::Boolean => 3,
+            FieldValueType::Byte => 4,
+            FieldValueType::Char => 5,
+            FieldValueType::Short => 6,
+        };
+
+        Some(match self {
+            DexOp::Nop => 0x00,
+            DexOp::MoveTwoReg { move_type, .. } => match move_type {
+                TwoRegMoveType::Normal => 0x01,
+                TwoRegMoveType::From16 => 0x02,
+                TwoRegMoveType::Move16 => 0x03,
+                TwoRegMoveType::Wide => 0x04,
+                TwoRegMoveType::WideFrom16 => 0x05,
+                TwoRegMoveType::Wide16 => 0x06,
+                TwoRegMoveType::Object => 0x07,
+                TwoRegMoveType::ObjectFrom16 => 0x08,
+                TwoRegMoveType::Object16 => 0x09,
+            },
+            DexOp::MoveOneReg { move_type, .. } => match move_type {
+                OneRegMoveType::Result => 0x0a,
+                OneRegMoveType::ResultWide => 0x0b,
+                OneRegMoveType::ResultObject => 0x0c,
+                OneRegMoveType::Exception => 0x0d,
+            },
+            DexOp::Return { return_type, .. } => match return_type {
+                ReturnType::Void => 0x0e,
+                ReturnType::Normal => 0x0f,
+                ReturnType::Wide => 0x10,
+                ReturnType::Object => 0x11,
+            },
+            DexOp::ConstLiteral { const_type, .. } => match const_type {
+                ConstLiteralType::Const4 => 0x12,
+                ConstLiteralType::Const16 => 0x13,
+                ConstLiteralType::Const => 0x14,
+                ConstLiteralType::ConstHigh16 => 0x15,
+                ConstLiteralType::ConstWide16 => 0x16,
+                ConstLiteralType::ConstWide32 => 0x17,
+                ConstLiteralType::ConstWide => 0x18,
+                ConstLiteralType::ConstWideHigh16 => 0x19,
+            },
+            DexOp::Const { const_type, .. } => match const_type {
+                ConstType::String => 0x1a,
+                ConstType::StringJumbo => 0x1b,
+                ConstType::Class => 0x1c,
+                ConstType::MethodHandle => 0xfe,
+                ConstType::MethodType => 0xff,
+            },
+            DexOp::MonitorEnter { .. } => 0x1d,
+            DexOp::MonitorExit { .. } => 0x1e,
+            DexOp::CheckCast { .. } => 0x1f,
+            DexOp::InstanceOf { .. } => 0x20,
+            DexOp::ArrayLength { .. } => 0x21,
+            DexOp::NewInstance { .. } => 0x22,
+            DexOp::NewArray { .. } => 0x23,
+            DexOp::FilledNewArray { .. } => 0x24,
+            DexOp::FilledNewArrayRange { .. } => 0x25,
+            DexOp::FillArrayData { .. } => 0x26,
+            DexOp::Throw { .. } => 0x27,
+            DexOp::Goto { goto_type, .. } => match goto_type {
+                GotoType::Normal => 0x28,
+                GotoType::Size16 => 0x29,
+                GotoType::Size32 => 0x2a,
+            },
+            DexOp::Switch { switch_type, .. } => match switch_type {
+                SwitchType::PackedSwitch => 0x2b,
+                SwitchType::SparseSwitch => 0x2c,
+            },
+            DexOp::Cmp { cmp_type, .. } => match cmp_type {
+                CmpType::CmplFloat => 0x2d,
+                CmpType::CmpgFloat => 0x2e,
+                CmpType::CmplDouble => 0x2f,
+                CmpType::CmpgDouble => 0x30,
+                CmpType::CmpLong => 0x31,
+            },
+            DexOp::TwoRegCondition { cond_type, .. } => match cond_type {
+                TwoRegConditionType::Eq => 0x32,
+                TwoRegConditionType::Ne => 0x33,
+                TwoRegConditionType::Lt => 0x34,
+                TwoRegConditionType::Ge => 0x35,
+                TwoRegConditionType::Gt => 0x36,
+                TwoRegConditionType::Le => 0x37,
+            },
+            DexOp::Condition { cond_type, .. } => match cond_type {
+                ConditionType::Eqz => 0x38,
+                ConditionType::Nez => 0x39,
+                ConditionType::Ltz => 0x3a,
+                ConditionType::Gez => 0x3b,
+                ConditionType::Gtz => 0x3c,
+                ConditionType::Lez => 0x3d,
+            },
+            DexOp::ArrayAccess {
+                access_type,
+                value_type,
+                ..
+            } => {
+                let index = match value_type {
+                    ArrayValueType::Normal => 0,
+                    ArrayValueType::Wide => 1,
+                    ArrayValueType::Object => 2,
+                    ArrayValueType::Boolean => 3,
+                    ArrayValueType::Byte => 4,
+                    ArrayValueType::Char => 5,
+                    ArrayValueType::Short => 6,
+                };
+                match access_type {
+                    ArrayAccessType::Get => 0x44 + index,
+                    ArrayAccessType::Put => 0x4b + index,
+                }
+            }
+            DexOp::DynamicFieldAccess {
+                access_type,
+                value_type,
+                ..
+            } => match access_type {
+                DynamicFieldAccessType::Get => 0x52 + value_index(*value_type),
+                DynamicFieldAccessType::Put => 0x59 + value_index(*value_type),
+            },
+            DexOp::StaticFieldAccess {
+                access_type,
+                value_type,
+                ..
+            } => match access_type {
+                StaticFieldAccessType::Get => 0x60 + value_index(*value_type),
+                StaticFieldAccessType::Put => 0x67 + value_index(*value_type),
+            },
+            DexOp::Invoke { invoke_type, .. } => match invoke_type {
+                InvokeType::Virtual => 0x6e,
+                InvokeType::Super => 0x6f,
+                InvokeType::Direct => 0x70,
+                InvokeType::Static => 0x71,
+                InvokeType::Interface => 0x72,
+                InvokeType::VirtualRange => 0x74,
+                InvokeType::SuperRange => 0x75,
+                InvokeType::DirectRange => 0x76,
+                InvokeType::StaticRange => 0x77,
+                InvokeType::InterfaceRange => 0x78,
+                InvokeType::Polymorphic => 0xfa,
+                InvokeType::PolymorphicRange => 0xfb,
+                InvokeType::Custom => 0xfc,
+                InvokeType::CustomRange => 0xfd,
+            },
+            DexOp::ArithUnary {
+                arith_type,
+                operand_type,
+                ..
+            } => match (arith_type, operand_type) {
+                (ArithUnaryType::Neg, ArithOperandType::Int) => 0x7b,
+                (ArithUnaryType::Not, ArithOperandType::Int) => 0x7c,
+                (ArithUnaryType::Neg, ArithOperandType::Long) => 0x7d,
+                (ArithUnaryType::Not, ArithOperandType::Long) => 0x7e,
+                (ArithUnaryType::Neg, ArithOperandType::Float) => 0x7f,
+                (ArithUnaryType::Neg, ArithOperandType::Double) => 0x80,
+                (ArithUnaryType::Not, _) => return None,
+            },
+            DexOp::Convert { convert_type, .. } => match convert_type {
+                ConvertType::IntToLong => 0x81,
+                ConvertType::IntToFloat => 0x82,
+                ConvertType::IntToDouble => 0x83,
+                ConvertType::LongToInt => 0x84,
+                ConvertType::LongToFloat => 0x85,
+                ConvertType::LongToDouble => 0x86,
+                ConvertType::FloatToInt => 0x87,
+                ConvertType::FloatToLong => 0x88,
+                ConvertType::FloatToDouble => 0x89,
+                ConvertType::DoubleToInt => 0x8a,
+                ConvertType::DoubleToLong => 0x8b,
+                ConvertType::DoubleToFloat => 0x8c,
+                ConvertType::IntToByte => 0x8d,
+                ConvertType::IntToChar => 0x8e,
+                ConvertType::IntToShort => 0x8f,
+            },
+            DexOp::Arith {
+                arith_type,
+                operand_type,
+                ..
+            } => arith(*arith_type, *operand_type, 0x90)?,
+            DexOp::Arith2Addr {
+                arith_type,
+                operand_type,
+                ..
+            } => {
+                let operand_type = match operand_type {
+                    ArithOperand2AddrType::Int => ArithOperandType::Int,
+                    ArithOperand2AddrType::Long => ArithOperandType::Long,
+                    ArithOperand2AddrType::Float => ArithOperandType::Float,
+                    ArithOperand2AddrType::Double => ArithOperandType::Double,
+                };
+                arith(*arith_type, operand_type, 0xb0)?
+            }
+            DexOp::LitArith16 { arith_type, .. } => match arith_type {
+                LitArithType16::AddIntLit16 => 0xd0,
+                LitArithType16::RSubIntLit16 => 0xd1,
+                LitArithType16::MulIntLit16 => 0xd2,
+                LitArithType16::DivIntLit16 => 0xd3,
+                LitArithType16::RemIntLit16 => 0xd4,
+                LitArithType16::AndIntLit16 => 0xd5,
+                LitArithType16::OrIntLit16 => 0xd6,
+                LitArithType16::XorIntLit16 => 0xd7,
+            },
+            DexOp::LitArith8 { arith_type, .. } => match arith_type {
+                LitArithType8::AddIntLit8 => 0xd8,
+                LitArithType8::RSubIntLit8 => 0xd9,
+                LitArithType8::MulIntLit8 => 0xda,
+                LitArithType8::DivIntLit8 => 0xdb,
+                LitArithType8::RemIntLit8 => 0xdc,
+                LitArithType8::AndIntLit8 => 0xdd,
+                LitArithType8::OrIntLit8 => 0xde,
+                LitArithType8::XorIntLit8 => 0xdf,
+                LitArithType8::ShlIntLit8 => 0xe0,
+                LitArithType8::ShrIntLit8 => 0xe1,
+                LitArithType8::UshrIntLit8 => 0xe2,
+            },
+            DexOp::Unused { opcode } => *opcode,
+            #[cfg(feature = "odex")]
+            DexOp::Odex(op) => op.opcode(),
+        })
+    }
+
+    /// Size of the encoded instruction in 16-bit code units, as used for branch offsets
+    pub fn code_units(&self) -> u32 {
+        match self {
+            DexOp::Nop
+            | DexOp::MoveOneReg { .. }
+            | DexOp::Return { .. }
+            | DexOp::ArithUnary { .. }
+            | DexOp::Arith2Addr { .. }
+            | DexOp::Convert { .. }
+            | DexOp::MonitorEnter { .. }
+            | DexOp::MonitorExit { .. }
+            | DexOp::ArrayLength { .. }
+            | DexOp::Throw { .. }
+            | DexOp::Unused { .. } => 1,
+            DexOp::MoveTwoReg { move_type, .. } => match move_type {
+                TwoRegMoveType::Normal | TwoRegMoveType::Wide | TwoRegMoveType::Object => 1,
+                TwoRegMoveType::From16
+                | TwoRegMoveType::WideFrom16
+                | TwoRegMoveType::ObjectFrom16 => 2,
+                TwoRegMoveType::Move16 | TwoRegMoveType::Wide16 | TwoRegMoveType::Object16 => 3,
+            },
+            DexOp::Goto { goto_type, .. } => match goto_type {
+                GotoType::Normal => 1,
+                GotoType::Size16 => 2,
+                GotoType::Size32 => 3,
+            },
+            DexOp::ConstLiteral { const_type, .. } => match const_type {
+                ConstLiteralType::Const4 => 1,
+                ConstLiteralType::Const16
+                | ConstLiteralType::ConstHigh16
+                | ConstLiteralType::ConstWide16
+                | ConstLiteralType::ConstWideHigh16 => 2,
+                ConstLiteralType::Const | ConstLiteralType::ConstWide32 => 3,
+                ConstLiteralType::ConstWide => 5,
+            },
+            DexOp::Const { const_type, .. } => match const_type {
+                ConstType::StringJumbo => 3,
+                _ => 2,
+            },
+            DexOp::Invoke { invoke_type, .. } => match invoke_type {
+                InvokeType::Polymorphic | InvokeType::PolymorphicRange => 4,
+                _ => 3,
+            },
+            DexOp::Arith { .. }
+            | DexOp::Condition { .. }
+            | DexOp::TwoRegCondition { .. }
+            | DexOp::LitArith8 { .. }
+            | DexOp::LitArith16 { .. }
+            | DexOp::Cmp { .. }
+            | DexOp::ArrayAccess { .. }
+            | DexOp::DynamicFieldAccess { .. }
+            | DexOp::StaticFieldAccess { .. }
+            | DexOp::CheckCast { .. }
+            | DexOp::InstanceOf { .. }
+            | DexOp::NewInstance { .. }
+            | DexOp::NewArray { .. } => 2,
+            DexOp::FilledNewArray { .. }
+            | DexOp::FilledNewArrayRange { .. }
+            | DexOp::FillArrayData { .. }
+            | DexOp::Switch { .. } => 3,
+            #[cfg(feature = "odex")]
+            DexOp::Odex(op) => op.code_units(),
+        }
+    }
+
+    /// Returns every class type the operation refers to: the types of `const-class`,
+    /// casts, `new-instance` and array creation, the class and signature of an invoked
+    /// method, and the class and type of an accessed field.
+    pub fn referenced_types(&self) -> Vec<&ObjectIdentifier<'a>> {
+        match self {
+            DexOp::Invoke {
+                method: Some(m), ..
+            } => {
+                let mut types = m.class.object_types();
+                types.extend(m.param.ms.object_types());
+                types
+            }
+            DexOp::Const { value: class, .. }
+            | DexOp::CheckCast { class, .. }
+            | DexOp::InstanceOf { class, .. }
+            | DexOp::NewInstance { class, .. }
+            | DexOp::NewArray { class, .. }
+            | DexOp::FilledNewArray { class, .. }
+            | DexOp::FilledNewArrayRange { class, .. } => class.object_types(),
+            DexOp::DynamicFieldAccess { field, .. } | DexOp::StaticFieldAccess { field, .. } => {
+                let mut types = vec![&field.class];
+                types.extend(field.param.ts.object_types());
+                types
+            }
+            #[cfg(feature = "odex")]
+            DexOp::Odex(op) => op.referenced_types(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns the registers that hold the first half of a wide (long or double) value.
+    /// Such a register also occupies the next register number.
+    pub fn wide_registers(&self) -> Vec<Register> {
+        let is_shift =
+            |t: &ArithType| matches!(t, ArithType::Shl | ArithType::Shr | ArithType::Ushr);
+        match self {
+            DexOp::MoveTwoReg {
+                move_type:
+                    TwoRegMoveType::Wide | TwoRegMoveType::WideFrom16 | TwoRegMoveType::Wide16,
+                dest,
+                src,
+            } => vec![*dest, *src],
+            DexOp::MoveOneReg {
+                move_type: OneRegMoveType::ResultWide,
+                dest,
+            } => vec![*dest],
+            DexOp::Return {
+                return_type: ReturnType::Wide,
+                src,
+            } => src.iter().copied().collect(),
+            DexOp::Arith {
+                arith_type,
+                operand_type: ArithOperandType::Long | ArithOperandType::Double,
+                dest,
+                src1,
+                src2,
+            } => {
+                // The shift distance of a long shift is an int
+                if is_shift(arith_type) {
+                    vec![*dest, *src1]
+                } else {
+                    vec![*dest, *src1, *src2]
+                }
+            }
+            DexOp::ArithUnary {
+                operand_type: ArithOperandType::Long | ArithOperandType::Double,
+                dest,
+                src,
+                ..
+            } => vec![*dest, *src],
+            DexOp::Arith2Addr {
+                arith_type,
+                operand_type: ArithOperand2AddrType::Long | ArithOperand2AddrType::Double,
+                dest,
+                src,
+            } => {
+                if is_shift(arith_type) {
+                    vec![*dest]
+                } else {
+                    vec![*dest, *src]
+                }
+            }
+            DexOp::ConstLiteral {
+                const_type:
+                    ConstLiteralType::ConstWide16
+                    | ConstLiteralType::ConstWide32
+                    | ConstLiteralType::ConstWide
+                    | ConstLiteralType::ConstWideHigh16,
+                dest,
+                ..
+            } => vec![*dest],
+            DexOp::Convert {
+                convert_type,
+                dest,
+                src,
+            } => {
+                use ConvertType::*;
+                let mut regs = vec![];
+                if matches!(
+                    convert_type,
+                    IntToLong
+                        | IntToDouble
+                        | LongToDouble
+                        | FloatToLong
+                        | FloatToDouble
+                        | DoubleToLong
+                ) {
+                    regs.push(*dest);
+                }
+                if matches!(
+                    convert_type,
+                    LongToInt
+                        | LongToFloat
+                        | LongToDouble
+                        | DoubleToInt
+                        | DoubleToLong
+                        | DoubleToFloat
+                ) {
+                    regs.push(*src);
+                }
+                regs
+            }
+            DexOp::Cmp {
+                cmp_type: CmpType::CmplDouble | CmpType::CmpgDouble | CmpType::CmpLong,
+                src1,
+                src2,
+                ..
+            } => vec![*src1, *src2],
+            DexOp::ArrayAccess {
+                value_type: ArrayValueType::Wide,
+                reg,
+                ..
+            }
+            | DexOp::DynamicFieldAccess {
+                value_type: FieldValueType::Wide,
+                reg,
+                ..
+            }
+            | DexOp::StaticFieldAccess {
+                value_type: FieldValueType::Wide,
+                reg,
+                ..
+            } => vec![*reg],
+            _ => vec![],
+        }
+    }
+
+    /// Returns every register referenced by the operation, in operand order.
+    /// Register ranges contribute their start and end registers.
+    pub fn registers(&self) -> Vec<Register> {
+        match self {
             DexOp::Invoke {
+                registers, range, ..
+            } => {
+                let mut regs = registers.clone();
+                if let Some(range) = range {
+                    regs.push(range.start);
+                    regs.push(range.end);
+                }
+                regs
+            }
+            DexOp::Const { dest, .. }
+            | DexOp::MoveOneReg { dest, .. }
+            | DexOp::ConstLiteral { dest, .. }
+            | DexOp::CheckCast { dest, .. }
+            | DexOp::NewInstance { dest, .. } => vec![*dest],
+            DexOp::MoveTwoReg { dest, src, .. }
+            | DexOp::ArithUnary { dest, src, .. }
+            | DexOp::Arith2Addr { dest, src, .. }
+            | DexOp::LitArith8 { dest, src, .. }
+            | DexOp::LitArith16 { dest, src, .. }
+            | DexOp::Convert { dest, src, .. }
+            | DexOp::InstanceOf { dest, src, .. } => vec![*dest, *src],
+            DexOp::Return { src, .. } => src.iter().copied().collect(),
+            DexOp::Arith {
+                dest, src1, src2, ..
+            }
+            | DexOp::Cmp {
+                dest, src1, src2, ..
+            } => vec![*dest, *src1, *src2],
+            DexOp::Condition { reg1, .. } => vec![*reg1],
+            DexOp::TwoRegCondition { reg1, reg2, .. } => vec![*reg1, *reg2],
+            DexOp::ArrayAccess { reg, arr, idx, .. } => vec![*reg, *arr, *idx],
+            DexOp::DynamicFieldAccess { reg, object, .. } => vec![*reg, *object],
+            DexOp::StaticFieldAccess { reg, .. }
+            | DexOp::FillArrayData { reg, .. }
+            | DexOp::Switch { reg, .. } => vec![*reg],
+            DexOp::MonitorEnter { src } | DexOp::MonitorExit { src } | DexOp::Throw { src } => {
+                vec![*src]
+            }
+            DexOp::ArrayLength { dest, array } => vec![*dest, *array],
+            DexOp::NewArray { dest, size_reg, .. } => vec![*dest, *size_reg],
+            DexOp::FilledNewArray { registers, .. } => registers.clone(),
+            DexOp::FilledNewArrayRange { registers, .. } => vec![registers.start, registers.end],
+            DexOp::Goto { .. } | DexOp::Nop | DexOp::Unused { .. } => vec![],
+            #[cfg(feature = "odex")]
+            DexOp::Odex(op) => op.registers(),
+        }
+    }
+
+    /// Mutable access to every register referenced by the operation, in operand order.
+    pub fn registers_mut(&mut self) -> Vec<&mut Register> {
+        match self {
+            DexOp::Invoke {
+                registers, range, ..
+            } => {
+                let mut regs: Vec<&mut Register> = registers.iter_mut().collect();
+                if let Some(range) = range {
+                    regs.push(&mut range.start);
+                    regs.push(&mut range.end);
+                }
+                regs
+            }
+            DexOp::Const { dest, .. }
+            | DexOp::MoveOneReg { dest, .. }
+            | DexOp::ConstLiteral { dest, .. }
+            | DexOp::CheckCast { dest, .. }
+            | DexOp::NewInstance { dest, .. } => vec![dest],
+            DexOp::MoveTwoReg { dest, src, .. }
+            | DexOp::ArithUnary { dest, src, .. }
+            | DexOp::Arith2Addr { dest, src, .. }
+            | DexOp::LitArith8 { dest, src, .. }
+            | DexOp::LitArith16 { dest, src, .. }
+            | DexOp::Convert { dest, src, .. }
+            | DexOp::InstanceOf { dest, src, .. } => vec![dest, src],
+            DexOp::Return { src, .. } => src.iter_mut().collect(),
+            DexOp::Arith {
+                dest, src1, src2, ..
+            }
+            | DexOp::Cmp {
+                dest, src1, src2, ..
+            } => vec![dest, src1, src2],
+            DexOp::Condition { reg1, .. } => vec![reg1],
+            DexOp::TwoRegCondition { reg1, reg2, .. } => vec![reg1, reg2],
+            DexOp::ArrayAccess { reg, arr, idx, .. } => vec![reg, arr, idx],
+            DexOp::DynamicFieldAccess { reg, object, .. } => vec![reg, object],
+            DexOp::StaticFieldAccess { reg, .. }
+            | DexOp::FillArrayData { reg, .. }
+            | DexOp::Switch { reg, .. } => vec![reg],
+            DexOp::MonitorEnter { src } | DexOp::MonitorExit { src } | DexOp::Throw { src } => {
+                vec![src]
+            }
+            DexOp::ArrayLength { dest, array } => vec![dest, array],
+            DexOp::NewArray { dest, size_reg, .. } => vec![dest, size_reg],
+            DexOp::FilledNewArray { registers, .. } => registers.iter_mut().collect(),
+            DexOp::FilledNewArrayRange { registers, .. } => {
+                vec![&mut registers.start, &mut registers.end]
+            }
+            DexOp::Goto { .. } | DexOp::Nop | DexOp::Unused { .. } => vec![],
+            #[cfg(feature = "odex")]
+            DexOp::Odex(op) => op.registers_mut(),
+        }
+    }
+}
+
+/// Parse a register reference like "v0" or "p1", returning its number.
+pub fn parse_register<'a>() -> impl ModalParser<&'a str, Register, ParseFailure> {
+    ws(
+        (one_of(['v', 'p']), digit1).try_map(|(t, o): (char, &str)| {
+            let num = o.parse::<u16>().map_err(|_| ParseFailure::default())?;
+            Ok::<Register, ParseFailure>(if t == 'v' {
+                Register::Local(num)
+            } else {
+                Register::Parameter(num)
+            })
+        }),
+    )
+}
+
+/// Parse a comma-separated list of registers inside curly braces.
+pub(crate) fn parse_register_list<'a>() -> impl ModalParser<&'a str, Vec<Register>, ParseFailure> {
+    delimited(
+        ws(one_of('{')),
+        separated(0.., parse_register(), ws(one_of(','))),
+        ws(one_of('}')),
+    )
+}
+
+fn parse_const_high16<'a>() -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
+    preceded(
+        space1,
+        (parse_register(), ws(one_of(',')), parse_int_lit::<i32>()).map(|(dest, _, value32)| {
+            let value = (value32 >> 16) as i64;
+            DexOp::ConstLiteral {
+                const_type: ConstLiteralType::ConstHigh16,
+                dest,
+                value: ConstLiteralValue::ConstHigh16(value),
+            }
+        }),
+    )
+}
+
+/// `const` takes a 32-bit literal; one that needs 64 bits fails with a hint to use
+/// `const-wide` rather than a bare position
+fn parse_const<'a>() -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
+    alt((
+        parse_one_reg_and_literal::<i32, _>(|dest, value| DexOp::ConstLiteral {
+            const_type: ConstLiteralType::Const,
+            dest,
+            value: ConstLiteralValue::Const(value),
+        }),
+        |input: &mut &'a str| {
+            (parse_register(), ws(one_of(',')), multispace0).parse_next(input)?;
+            let at = *input;
+            let literal = parse_int_lit::<i64>().take().parse_next(input)?;
+            // report the literal's position, not the end of it
+            *input = at;
+            Err(fail(format!(
+                "{literal} is too large for a 32-bit const, use const-wide"
+            )))
+        },
+    ))
+}
+
+fn parse_const_wide_high16<'a>() -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
+    preceded(
+        space1,
+        (parse_register(), ws(one_of(',')), parse_int_lit::<i64>()).try_map(
+            |(dest, _, value64)| {
+                // only the top 16 bits are encoded, anything below them would be lost
+                if value64 & 0xffff_ffff_ffff != 0 {
+                    return Err(SmaliError::new(
+                        "const-wide/high16 literal has non-zero low 48 bits",
+                    ));
+                }
+                Ok(DexOp::ConstLiteral {
+                    const_type: ConstLiteralType::ConstWideHigh16,
+                    dest,
+                    value: ConstLiteralValue::ConstWideHigh16(value64 >> 48),
+                })
+            },
+        ),
+    )
+}
+
+/// Parses a register range enclosed in braces, e.g. "{v0 .. v6}".
+/// Returns a tuple (first_reg, last_reg)
+pub(crate) fn parse_register_range<'a>() -> impl ModalParser<&'a str, RegisterRange, ParseFailure> {
+    delimited(
+        ws(one_of('{')),
+        (
+            terminated(parse_register(), literal("..")),
+            parse_register(),
+        )
+            .map(|(start, end)| RegisterRange { start, end }),
+        ws(one_of('}')),
+    )
+}
+
+fn parse_invoke_polymorphic<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
+    preceded(
+        space1,
+        (
+            parse_register_list(),
+            delimited(
+                ws(one_of(',')),
+                parse_method_ref_with(options),
+                ws(one_of(',')),
+            ),
+            parse_methodsignature_with(options).take(),
+        )
+            .map(|(registers, method, proto)| DexOp::Invoke {
+                invoke_type: InvokeType::Polymorphic,
+                registers,
+                range: None,
+                method: Some(Box::new(method)),
+                call_site: None,
+                proto: Some(Cow::Borrowed(proto)),
+            }),
+    )
+}
+
+fn parse_invoke_polymorphic_range<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
+    preceded(
+        space1,
+        (
+            parse_register_range(),
+            delimited(
+                ws(one_of(',')),
+                parse_method_ref_with(options),
+                ws(one_of(',')),
+            ),
+            parse_methodsignature_with(options).take(),
+        )
+            .map(|(range, method, proto)| DexOp::Invoke {
+                invoke_type: InvokeType::PolymorphicRange,
+                registers: Vec::new(),
+                range: Some(range),
+                method: Some(Box::new(method)),
+                call_site: None,
+                proto: Some(Cow::Borrowed(proto)),
+            }),
+    )
+}
+
+/// Parses a call site as written, e.g. `call_site_0("run", (I)V)@La;->bsm(...)Ljava/lang/invoke/CallSite;`
+fn parse_call_site<'a>(options: ParseOptions) -> impl ModalParser<&'a str, &'a str, ParseFailure> {
+    (
+        take_while(1.., |c: char| c.is_alphanumeric() || c == '_' || c == '$'),
+        one_of('('),
+        take_until(0.., ")@"),
+        literal(")@"),
+        parse_method_ref_with(options),
+    )
+        .take()
+}
+
+fn parse_invoke_custom<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
+    preceded(
+        space1,
+        (
+            parse_register_list(),
+            ws(one_of(',')),
+            parse_call_site(options),
+        )
+            .map(|(registers, _, call_site)| DexOp::Invoke {
                 invoke_type: InvokeType::Custom,
                 registers,
                 range: None,
                 method: None,
                 call_site: Some(Cow::Borrowed(call_site)),
                 proto: None,
-            }
-        }),
+            }),
     )
 }
 
-fn parse_invoke_custom_range<'a>() -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>> {
+fn parse_invoke_custom_range<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
     preceded(
         space1,
-        (parse_register_range(), ws(one_of(',')), alphanumeric1).map(|(range, _, call_site)| {
-            DexOp::Invoke {
+        (
+            parse_register_range(),
+            ws(one_of(',')),
+            parse_call_site(options),
+        )
+            .map(|(range, _, call_site)| DexOp::Invoke {
                 invoke_type: InvokeType::CustomRange,
                 registers: Vec::new(),
                 range: Some(range),
                 method: None,
                 call_site: Some(Cow::Borrowed(call_site)),
                 proto: None,
-            }
-        }),
+            }),
     )
 }
 
 fn parse_invoke<'a>(
     invoke_type: InvokeType,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>> {
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
     (
         terminated(parse_register_list(), ws(one_of(','))),
-        parse_method_ref(),
+        parse_method_ref_with(options),
     )
         .map(move |(registers, method)| DexOp::Invoke {
             invoke_type,
@@ -1538,10 +2568,11 @@ fn parse_invoke<'a>(
 
 fn parse_invoke_range<'a>(
     invoke_type: InvokeType,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>> {
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
     (
         terminated(parse_register_range(), ws(one_of(','))),
-        parse_method_ref(),
+        parse_method_ref_with(options),
     )
         .map(move |(range, method)| DexOp::Invoke {
             invoke_type,
@@ -1553,9 +2584,7 @@ fn parse_invoke_range<'a>(
         })
 }
 
-fn parse_one_reg_op<'a, F>(
-    constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+fn parse_one_reg_op<'a, F>(constructor: F) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register) -> DexOp<'a>,
 {
@@ -1563,9 +2592,7 @@ where
 }
 
 /// Helper function: it consumes a space, then a register, then a comma (with optional spaces), then another register.
-fn parse_two_reg_op<'a, F>(
-    constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+fn parse_two_reg_op<'a, F>(constructor: F) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, Register) -> DexOp<'a>,
 {
@@ -1579,9 +2606,7 @@ where
 /// Helper function: parses three registers from the input.
 /// It expects at least one space, then a register, a comma, another register,
 /// a comma, and a third register.
-fn parse_three_reg_op<'a, F>(
-    constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+fn parse_three_reg_op<'a, F>(constructor: F) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, Register, Register) -> DexOp<'a>,
 {
@@ -1597,11 +2622,11 @@ where
 /// It assumes the opcode has already been consumed.
 fn parse_one_reg_and_literal<'a, T, F>(
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
-    T: num_traits::Num + std::ops::Neg<Output = T> + std::str::FromStr + TryFrom<i64> + 'a,
+    T: num_traits::Num + core::ops::Neg<Output = T> + FromStr + TryFrom<i64> + 'a,
     F: Fn(Register, T) -> DexOp<'a>,
-    <T as TryFrom<i64>>::Error: std::fmt::Debug,
+    <T as TryFrom<i64>>::Error: Debug,
 {
     (
         terminated(parse_register(), ws(one_of(','))),
@@ -1613,11 +2638,11 @@ where
 /// Helper for two-reg + literal operations.
 fn parse_two_reg_and_literal<'a, T, F>(
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
-    T: num_traits::Num + std::ops::Neg<Output = T> + std::str::FromStr + TryFrom<i64> + 'a,
+    T: num_traits::Num + core::ops::Neg<Output = T> + FromStr + TryFrom<i64> + 'a,
     F: Fn(Register, Register, T) -> DexOp<'a>,
-    <T as TryFrom<i64>>::Error: std::fmt::Debug,
+    <T as TryFrom<i64>>::Error: Debug,
 {
     (
         parse_register(),
@@ -1628,28 +2653,30 @@ where
 }
 
 fn parse_one_reg_and_fieldref<'a, F>(
+    options: ParseOptions,
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, FieldRef) -> DexOp + 'a,
 {
     (
         terminated(parse_register(), ws(one_of(','))),
-        parse_field_ref(),
+        parse_field_ref_with(options),
     )
         .map(move |(dest, field)| constructor(dest, field))
 }
 
 fn parse_two_reg_and_fieldref<'a, F>(
+    options: ParseOptions,
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, Register, FieldRef) -> DexOp + 'a,
 {
     (
         parse_register(),
         delimited(ws(one_of(',')), parse_register(), ws(one_of(','))),
-        parse_field_ref(),
+        parse_field_ref_with(options),
     )
         .map(move |(reg1, reg2, field)| constructor(reg1, reg2, field))
 }
@@ -1658,6 +2685,31 @@ where
 pub enum StringOrTypeSig<'a> {
     String(Cow<'a, str>),
     TypeSig(TypeSignature<'a>),
+    /// Method prototype of a `const-method-type`, e.g. `(II)V`
+    MethodType(MethodSignature<'a>),
+    /// Method handle of a `const-method-handle` as written, e.g. `invoke-static@La;->m()V`
+    MethodHandle(Cow<'a, str>),
+}
+
+impl<'a> StringOrTypeSig<'a> {
+    /// Copies any borrowed text, detaching the operand from the parsed source
+    pub fn into_owned(self) -> StringOrTypeSig<'static> {
+        match self {
+            Self::String(s) => StringOrTypeSig::String(Cow::Owned(s.into_owned())),
+            Self::TypeSig(ts) => StringOrTypeSig::TypeSig(ts.into_owned()),
+            Self::MethodType(ms) => StringOrTypeSig::MethodType(ms.into_owned()),
+            Self::MethodHandle(mh) => StringOrTypeSig::MethodHandle(Cow::Owned(mh.into_owned())),
+        }
+    }
+
+    /// Every class type mentioned by a type or method type operand
+    pub fn object_types(&self) -> Vec<&ObjectIdentifier<'a>> {
+        match self {
+            Self::TypeSig(ts) => ts.object_types(),
+            Self::MethodType(ms) => ms.object_types(),
+            Self::String(_) | Self::MethodHandle(_) => vec![],
+        }
+    }
 }
 
 impl fmt::Display for StringOrTypeSig<'_> {
@@ -1670,6 +2722,12 @@ impl fmt::Display for StringOrTypeSig<'_> {
             Self::TypeSig(ts) => {
                 write!(f, "{ts}")
             }
+            Self::MethodType(ms) => {
+                write!(f, "{}", ms.to_jni())
+            }
+            Self::MethodHandle(mh) => {
+                write!(f, "{mh}")
+            }
         }
     }
 }
@@ -1677,8 +2735,9 @@ impl fmt::Display for StringOrTypeSig<'_> {
 /// Helper for one-reg + literal operations.
 /// It assumes the opcode has already been consumed.
 fn parse_one_reg_and_string<'a, F>(
+    options: ParseOptions,
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, StringOrTypeSig<'a>) -> DexOp<'a>,
 {
@@ -1686,15 +2745,47 @@ where
         terminated(parse_register(), ws(one_of(','))),
         alt((
             parse_string_lit().map(|s| StringOrTypeSig::String(Cow::Borrowed(s))),
-            parse_typesignature().map(StringOrTypeSig::TypeSig),
+            parse_typesignature_with(options).map(StringOrTypeSig::TypeSig),
         )),
     )
         .map(move |(reg, literal)| constructor(reg, literal))
 }
 
+/// Parses the operand of a `const-*` op in the shape its [`ConstType`] requires:
+/// a string literal for `const-string`, a type for `const-class` and a prototype
+/// for `const-method-type`.
+fn parse_const_value<'a>(
+    const_type: ConstType,
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, StringOrTypeSig<'a>, ParseFailure> {
+    move |input: &mut &'a str| match const_type {
+        ConstType::String | ConstType::StringJumbo => parse_string_lit()
+            .map(|s| StringOrTypeSig::String(Cow::Borrowed(s)))
+            .parse_next(input),
+        ConstType::Class => parse_typesignature_with(options)
+            .map(StringOrTypeSig::TypeSig)
+            .parse_next(input),
+        ConstType::MethodType => ws(parse_methodsignature_with(options))
+            .map(StringOrTypeSig::MethodType)
+            .parse_next(input),
+        ConstType::MethodHandle => ws((
+            take_while(1.., |c: char| c.is_alphanumeric() || c == '-'),
+            one_of('@'),
+            alt((
+                parse_method_ref_with(options).void(),
+                parse_field_ref_with(options).void(),
+            )),
+        )
+            .take())
+        .map(|mh: &str| StringOrTypeSig::MethodHandle(Cow::Borrowed(mh)))
+        .parse_next(input),
+    }
+}
+
 fn parse_two_reg_and_string<'a, F>(
+    options: ParseOptions,
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, Register, StringOrTypeSig<'a>) -> DexOp<'a>,
 {
@@ -1703,7 +2794,7 @@ where
         delimited(ws(one_of(',')), parse_register(), ws(one_of(','))),
         alt((
             parse_string_lit().map(|s| StringOrTypeSig::String(Cow::Borrowed(s))),
-            parse_typesignature().map(StringOrTypeSig::TypeSig),
+            parse_typesignature_with(options).map(StringOrTypeSig::TypeSig),
         )),
     )
         .map(move |(reg1, reg2, literal)| constructor(reg1, reg2, literal))
@@ -1711,17 +2802,20 @@ where
 
 fn parse_one_reg_and_label<'a, F>(
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, Label<'a>) -> DexOp<'a>,
 {
-    (terminated(parse_register(), ws(one_of(','))), parse_label())
-        .map(move |(reg, label)| constructor(reg, label))
+    preceded(
+        space1,
+        (terminated(parse_register(), ws(one_of(','))), parse_label())
+            .map(move |(reg, label)| constructor(reg, label)),
+    )
 }
 
 fn parse_two_reg_and_label<'a, F>(
     constructor: F,
-) -> impl ModalParser<&'a str, DexOp<'a>, InputError<&'a str>>
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure>
 where
     F: Fn(Register, Register, Label<'a>) -> DexOp<'a>,
 {
@@ -1736,31 +2830,76 @@ where
     )
 }
 
+/// Parses a type signature that must be an array type, as `filled-new-array` takes
+fn parse_array_type<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, TypeSignature<'a>, ParseFailure> {
+    parse_typesignature_with(options)
+        .verify(|ts: &TypeSignature| matches!(ts, TypeSignature::Array(_)))
+}
+
 // Higher level parser for all operations
-// Higher level parser for all operations
-pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputError<&'a str>> {
-    let op =
-        take_while(1.., |c: char| c.is_alphanumeric() || c == '-' || c == '/').parse_next(input)?;
+pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, ParseFailure> {
+    dex_op(ParseOptions::default(), input)
+}
+
+/// Parses an instruction with the nesting limit of `options`
+pub fn parse_dex_op_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, DexOp<'a>, ParseFailure> {
+    move |input: &mut &'a str| dex_op(options, input)
+}
+
+fn dex_op<'a>(options: ParseOptions, input: &mut &'a str) -> ModalResult<DexOp<'a>, ParseFailure> {
+    // mnemonics are lowercase ascii with `-` and `/` separators, e.g. `const-wide/high16`;
+    // like smali itself, `CONST` or `Const` are not accepted
+    let start = *input;
+    let op = take_while(1.., |c: char| {
+        c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '/'
+    })
+    .parse_next(input)?;
+    // and must end at whitespace, so `const/4v0` isn't read as `const/4` and `v0`
+    if input.starts_with(|c: char| !c.is_whitespace() && c != '#') {
+        return Err(ErrMode::Backtrack(ParseFailure::default()));
+    }
+
+    #[cfg(feature = "odex")]
+    if let Some(odex_op) = parse_odex_op(op, options, input)? {
+        return Ok(DexOp::Odex(odex_op));
+    }
 
     // Handle ungrouped operations first
     let op_result = match op {
         "nop" => return Ok(DexOp::Nop),
         "monitor-enter" => parse_one_reg_op(|src| DexOp::MonitorEnter { src }).parse_next(input)?,
         "monitor-exit" => parse_one_reg_op(|src| DexOp::MonitorExit { src }).parse_next(input)?,
-        "check-cast" => parse_one_reg_and_string(|dest, class| DexOp::CheckCast { dest, class })
-            .parse_next(input)?,
-        "instance-of" => {
-            parse_two_reg_and_string(|dest, src, class| DexOp::InstanceOf { dest, src, class })
+        "check-cast" => {
+            parse_one_reg_and_string(options, |dest, class| DexOp::CheckCast { dest, class })
                 .parse_next(input)?
         }
+        "instance-of" => parse_two_reg_and_string(options, |dest, src, class| DexOp::InstanceOf {
+            dest,
+            src,
+            class,
+        })
+        .parse_next(input)?,
         "array-length" => {
             parse_two_reg_op(|dest, array| DexOp::ArrayLength { dest, array }).parse_next(input)?
         }
         "new-instance" => {
-            parse_one_reg_and_string(|dest, class| DexOp::NewInstance { dest, class })
+            parse_one_reg_and_string(options, |dest, class| DexOp::NewInstance { dest, class })
+                .try_map(|op| match op {
+                    DexOp::NewInstance {
+                        class: StringOrTypeSig::TypeSig(TypeSignature::Array(_)),
+                        ..
+                    } => Err(SmaliError::new(
+                        "new-instance can't create an array, use new-array",
+                    )),
+                    op => Ok(op),
+                })
                 .parse_next(input)?
         }
-        "new-array" => parse_two_reg_and_string(|dest, size_reg, class| DexOp::NewArray {
+        "new-array" => parse_two_reg_and_string(options, |dest, size_reg, class| DexOp::NewArray {
             dest,
             size_reg,
             class,
@@ -1771,7 +2910,7 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
             (
                 parse_register_list(),
                 ws(one_of(',')),
-                parse_typesignature(),
+                parse_array_type(options),
             )
                 .map(|(registers, _, class)| DexOp::FilledNewArray {
                     registers,
@@ -1784,7 +2923,7 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
             (
                 parse_register_range(),
                 ws(one_of(',')),
-                parse_typesignature(),
+                parse_array_type(options),
             )
                 .map(|(registers, _, class)| DexOp::FilledNewArrayRange {
                     registers,
@@ -1800,27 +2939,35 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
         _ => {
             if let Ok(invoke_type) = InvokeType::from_str(op) {
                 match invoke_type {
-                    InvokeType::Polymorphic => parse_invoke_polymorphic().parse_next(input)?,
+                    InvokeType::Polymorphic => {
+                        parse_invoke_polymorphic(options).parse_next(input)?
+                    }
                     InvokeType::PolymorphicRange => {
-                        parse_invoke_polymorphic_range().parse_next(input)?
+                        parse_invoke_polymorphic_range(options).parse_next(input)?
+                    }
+                    InvokeType::Custom => parse_invoke_custom(options).parse_next(input)?,
+                    InvokeType::CustomRange => {
+                        parse_invoke_custom_range(options).parse_next(input)?
                     }
-                    InvokeType::Custom => parse_invoke_custom().parse_next(input)?,
-                    InvokeType::CustomRange => parse_invoke_custom_range().parse_next(input)?,
                     _ => {
                         if invoke_type.is_range() {
-                            parse_invoke_range(invoke_type).parse_next(input)?
+                            parse_invoke_range(invoke_type, options).parse_next(input)?
                         } else {
-                            parse_invoke(invoke_type).parse_next(input)?
+                            parse_invoke(invoke_type, options).parse_next(input)?
                         }
                     }
                 }
             } else if let Ok(const_type) = ConstType::from_str(op) {
-                parse_one_reg_and_string(|dest, value| DexOp::Const {
-                    const_type,
-                    dest,
-                    value,
-                })
-                .parse_next(input)?
+                (
+                    terminated(parse_register(), ws(one_of(','))),
+                    parse_const_value(const_type, options),
+                )
+                    .map(|(dest, value)| DexOp::Const {
+                        const_type,
+                        dest,
+                        value,
+                    })
+                    .parse_next(input)?
             } else if let Ok(move_type) = TwoRegMoveType::from_str(op) {
                 parse_two_reg_op(|dest, src| DexOp::MoveTwoReg {
                     move_type,
@@ -1859,19 +3006,12 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
                 })
                 .parse_next(input)?
             } else if let Ok(goto_type) = GotoType::from_str(op) {
-                parse_label()
+                preceded(space1, parse_label())
                     .map(|offset| DexOp::Goto { goto_type, offset })
                     .parse_next(input)?
             } else if let Ok(const_type) = ConstLiteralType::from_str(op) {
                 match const_type {
-                    ConstLiteralType::Const => {
-                        parse_one_reg_and_literal::<i32, _>(|dest, value| DexOp::ConstLiteral {
-                            const_type,
-                            dest,
-                            value: ConstLiteralValue::Const(value),
-                        })
-                        .parse_next(input)?
-                    }
+                    ConstLiteralType::Const => parse_const().parse_next(input)?,
                     ConstLiteralType::Const4 => {
                         parse_one_reg_and_literal::<i8, _>(|dest, value| DexOp::ConstLiteral {
                             const_type,
@@ -1960,7 +3100,7 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
 
                 if let Ok(arith_type) = ArithUnaryType::from_str(t) {
                     let operand_type = ArithOperandType::from_str(v)
-                        .map_err(|_| ErrMode::Backtrack(InputError::at(*input)))?;
+                        .map_err(|_| ErrMode::Backtrack(ParseFailure::default()))?;
 
                     parse_two_reg_op(|dest, src| DexOp::ArithUnary {
                         arith_type,
@@ -1981,7 +3121,7 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
                         .parse_next(input)?
                     } else {
                         let operand_type = ArithOperand2AddrType::from_str(v)
-                            .map_err(|_| ErrMode::Backtrack(InputError::at(*input)))?;
+                            .map_err(|_| ErrMode::Backtrack(ParseFailure::default()))?;
                         parse_two_reg_op(|dest, src| DexOp::Arith2Addr {
                             arith_type,
                             operand_type,
@@ -1991,8 +3131,11 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
                         .parse_next(input)?
                     }
                 } else if let Ok(access_type) = ArrayAccessType::from_str(t) {
-                    let value_type = ArrayValueType::from_str(v)
-                        .map_err(|_| ErrMode::Backtrack(InputError::at(*input)))?;
+                    let Ok(value_type) = ArrayValueType::from_str(v) else {
+                        // nothing else starts with `aget-`/`aput-`, so name the culprit
+                        *input = start;
+                        return Err(fail(format!("unknown array opcode {op}")));
+                    };
                     parse_three_reg_op(|reg, arr, idx| DexOp::ArrayAccess {
                         access_type,
                         value_type,
@@ -2003,8 +3146,8 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
                     .parse_next(input)?
                 } else if let Ok(access_type) = DynamicFieldAccessType::from_str(t) {
                     let value_type = FieldValueType::from_str(v)
-                        .map_err(|_| ErrMode::Backtrack(InputError::at(*input)))?;
-                    parse_two_reg_and_fieldref(move |reg, object, field| {
+                        .map_err(|_| ErrMode::Backtrack(ParseFailure::default()))?;
+                    parse_two_reg_and_fieldref(options, move |reg, object, field| {
                         DexOp::DynamicFieldAccess {
                             access_type,
                             value_type,
@@ -2016,16 +3159,18 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
                     .parse_next(input)?
                 } else if let Ok(access_type) = StaticFieldAccessType::from_str(t) {
                     let value_type = FieldValueType::from_str(v)
-                        .map_err(|_| ErrMode::Backtrack(InputError::at(*input)))?;
-                    parse_one_reg_and_fieldref(move |reg, field| DexOp::StaticFieldAccess {
-                        access_type,
-                        value_type,
-                        reg,
-                        field,
+                        .map_err(|_| ErrMode::Backtrack(ParseFailure::default()))?;
+                    parse_one_reg_and_fieldref(options, move |reg, field| {
+                        DexOp::StaticFieldAccess {
+                            access_type,
+                            value_type,
+                            reg,
+                            field,
+                        }
                     })
                     .parse_next(input)?
                 } else {
-                    return Err(ErrMode::Backtrack(InputError::at(*input)));
+                    return Err(ErrMode::Backtrack(ParseFailure::default()));
                 }
             }
         }
@@ -2037,6 +3182,7 @@ pub fn parse_dex_op<'a>(input: &mut &'a str) -> ModalResult<DexOp<'a>, InputErro
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{method_ref::parse_method_ref, signature::type_signature::parse_typesignature};
 
     #[test]
     fn test_const_string() {
@@ -2052,6 +3198,725 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_const_operand_shape() {
+        let mut input = "const-class v0, Ljava/lang/String;";
+        assert!(matches!(
+            parse_dex_op(&mut input),
+            Ok(DexOp::Const {
+                value: StringOrTypeSig::TypeSig(_),
+                ..
+            })
+        ));
+        let mut input = "const-method-type v0, (II)V";
+        let op = parse_dex_op(&mut input).unwrap();
+        assert_eq!(op.to_string(), "const-method-type v0, (II)V");
+
+        for mut input in [
+            r#"const-class v0, "foo""#,
+            r#"const-method-type v0, "foo""#,
+            "const-method-type v0, Ljava/lang/String;",
+            "const-string v0, Ljava/lang/String;",
+            "const-string/jumbo v0, Ljava/lang/String;",
+        ] {
+            assert!(parse_dex_op(&mut input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_const_wide_high16() {
+        for (input, high, canonical) in [
+            (
+                "const-wide/high16 v0, 0x8000000000000000L",
+                -0x8000,
+                "const-wide/high16 v0, -0x8000000000000000L",
+            ),
+            (
+                "const-wide/high16 v0, -0x10000000000000L",
+                -0x10,
+                "const-wide/high16 v0, -0x10000000000000L",
+            ),
+            (
+                "const-wide/high16 v0, 0x7fff000000000000L",
+                0x7fff,
+                "const-wide/high16 v0, 0x7fff000000000000L",
+            ),
+            (
+                "const-wide/high16 v2, 0x0L",
+                0,
+                "const-wide/high16 v2, 0x0L",
+            ),
+        ] {
+            let op = parse_dex_op.parse(input).unwrap();
+            assert!(
+                matches!(
+                    op,
+                    DexOp::ConstLiteral {
+                        value: ConstLiteralValue::ConstWideHigh16(v),
+                        ..
+                    } if v == high
+                ),
+                "{input}"
+            );
+            assert_eq!(op.to_string(), canonical);
+            assert_eq!(parse_dex_op.parse(canonical), Ok(op));
+        }
+        assert!(
+            parse_dex_op
+                .parse("const-wide/high16 v0, 0x4000000000000001L")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_label_whitespace() {
+        for (mut input, label) in [
+            ("goto    :goto_0", "goto_0"),
+            ("goto/16\t:goto_1", "goto_1"),
+            ("if-eqz v0,\t\t:cond_0", "cond_0"),
+            ("if-eqz\tv0 ,   :cond_1", "cond_1"),
+            ("if-ne v0,  v1,\t:cond_2", "cond_2"),
+            ("packed-switch\tv0,    :pswitch_data_0", "pswitch_data_0"),
+            ("sparse-switch v0,\t:sswitch_data_0", "sswitch_data_0"),
+            ("fill-array-data v0,  \t:array_0", "array_0"),
+        ] {
+            let op = parse_dex_op(&mut input).unwrap();
+            assert_eq!(op.target_label().map(|l| l.0.as_ref()), Some(label));
+        }
+
+        // the mnemonic has to be separated from its operands
+        let mut input = "goto:goto_0";
+        assert!(parse_dex_op(&mut input).is_err());
+    }
+
+    #[test]
+    fn test_opcode_boundary() {
+        let mut input = "const/4 v0, 0x1\n    int-to-long v0, v1\n    return-void# done";
+        assert_eq!(
+            parse_dex_op(&mut input),
+            Ok(DexOp::ConstLiteral {
+                const_type: ConstLiteralType::Const4,
+                dest: Register::Local(0),
+                value: ConstLiteralValue::Const4(1),
+            })
+        );
+        input = input.trim_start();
+        assert!(input.starts_with("int-to-long v0, v1"));
+        let op = parse_dex_op(&mut input).unwrap();
+        assert_eq!(op.to_string(), "int-to-long v0, v1");
+        input = input.trim_start();
+        assert_eq!(parse_dex_op(&mut input).unwrap().to_string(), "return-void");
+        assert_eq!(input, "# done");
+
+        for mut input in [
+            "const/4v0, 0x1",
+            "const/high16v0, 0x7f000000",
+            "int-to-long,v0, v1",
+            "const/4: v0, 0x1",
+            "Const/4 v0, 0x1",
+        ] {
+            assert!(parse_dex_op(&mut input).is_err(), "{input}");
+        }
+    }
+
+    /// One example of every dalvik mnemonic
+    const ALL_OPS: &[&str] = &[
+        "nop",
+        "move v0, v1",
+        "move/from16 v0, v1",
+        "move/16 v0, v1",
+        "move-wide v0, v1",
+        "move-wide/from16 v0, v1",
+        "move-wide/16 v0, v1",
+        "move-object v0, v1",
+        "move-object/from16 v0, v1",
+        "move-object/16 v0, v1",
+        "move-result v0",
+        "move-result-wide v0",
+        "move-result-object v0",
+        "move-exception v0",
+        "return-void",
+        "return v0",
+        "return-wide v0",
+        "return-object v0",
+        "const/4 v0, 0x1",
+        "const/16 v0, 0x100",
+        "const v0, 0x10000",
+        "const/high16 v0, 0x7f000000",
+        "const-wide/16 v0, 0x1",
+        "const-wide/32 v0, 0x10000",
+        "const-wide v0, 0x100000000L",
+        "const-wide/high16 v0, 0x4000000000000000L",
+        r#"const-string v0, "a""#,
+        r#"const-string/jumbo v0, "a""#,
+        "const-class v0, La/b;",
+        "monitor-enter v0",
+        "monitor-exit v0",
+        "check-cast v0, La/b;",
+        "instance-of v0, v1, La/b;",
+        "array-length v0, v1",
+        "new-instance v0, La/b;",
+        "new-array v0, v1, [I",
+        "filled-new-array {v0, v1}, [I",
+        "filled-new-array/range {v0 .. v1}, [I",
+        "fill-array-data v0, :array_0",
+        "throw v0",
+        "goto :goto_0",
+        "goto/16 :goto_0",
+        "goto/32 :goto_0",
+        "packed-switch v0, :pswitch_data_0",
+        "sparse-switch v0, :sswitch_data_0",
+        "cmpl-float v0, v1, v2",
+        "cmpg-float v0, v1, v2",
+        "cmpl-double v0, v1, v2",
+        "cmpg-double v0, v1, v2",
+        "cmp-long v0, v1, v2",
+        "if-eq v0, v1, :cond_0",
+        "if-eqz v0, :cond_0",
+        "if-ne v0, v1, :cond_0",
+        "if-nez v0, :cond_0",
+        "if-lt v0, v1, :cond_0",
+        "if-ltz v0, :cond_0",
+        "if-ge v0, v1, :cond_0",
+        "if-gez v0, :cond_0",
+        "if-gt v0, v1, :cond_0",
+        "if-gtz v0, :cond_0",
+        "if-le v0, v1, :cond_0",
+        "if-lez v0, :cond_0",
+        "aget v0, v1, v2",
+        "aget-wide v0, v1, v2",
+        "aget-object v0, v1, v2",
+        "aget-boolean v0, v1, v2",
+        "aget-byte v0, v1, v2",
+        "aget-char v0, v1, v2",
+        "aget-short v0, v1, v2",
+        "aput v0, v1, v2",
+        "aput-wide v0, v1, v2",
+        "aput-object v0, v1, v2",
+        "aput-boolean v0, v1, v2",
+        "aput-byte v0, v1, v2",
+        "aput-char v0, v1, v2",
+        "aput-short v0, v1, v2",
+        "iget v0, v1, La/b;->f:I",
+        "iget-wide v0, v1, La/b;->f:I",
+        "iget-object v0, v1, La/b;->f:I",
+        "iget-boolean v0, v1, La/b;->f:I",
+        "iget-byte v0, v1, La/b;->f:I",
+        "iget-char v0, v1, La/b;->f:I",
+        "iget-short v0, v1, La/b;->f:I",
+        "iput v0, v1, La/b;->f:I",
+        "iput-wide v0, v1, La/b;->f:I",
+        "iput-object v0, v1, La/b;->f:I",
+        "iput-boolean v0, v1, La/b;->f:I",
+        "iput-byte v0, v1, La/b;->f:I",
+        "iput-char v0, v1, La/b;->f:I",
+        "iput-short v0, v1, La/b;->f:I",
+        "sget v0, La/b;->f:I",
+        "sget-wide v0, La/b;->f:I",
+        "sget-object v0, La/b;->f:I",
+        "sget-boolean v0, La/b;->f:I",
+        "sget-byte v0, La/b;->f:I",
+        "sget-char v0, La/b;->f:I",
+        "sget-short v0, La/b;->f:I",
+        "sput v0, La/b;->f:I",
+        "sput-wide v0, La/b;->f:I",
+        "sput-object v0, La/b;->f:I",
+        "sput-boolean v0, La/b;->f:I",
+        "sput-byte v0, La/b;->f:I",
+        "sput-char v0, La/b;->f:I",
+        "sput-short v0, La/b;->f:I",
+        "invoke-virtual {v0, v1}, La/b;->m(I)V",
+        "invoke-virtual/range {v0 .. v1}, La/b;->m(I)V",
+        "invoke-super {v0, v1}, La/b;->m(I)V",
+        "invoke-super/range {v0 .. v1}, La/b;->m(I)V",
+        "invoke-direct {v0, v1}, La/b;->m(I)V",
+        "invoke-direct/range {v0 .. v1}, La/b;->m(I)V",
+        "invoke-static {v0, v1}, La/b;->m(I)V",
+        "invoke-static/range {v0 .. v1}, La/b;->m(I)V",
+        "invoke-interface {v0, v1}, La/b;->m(I)V",
+        "invoke-interface/range {v0 .. v1}, La/b;->m(I)V",
+        "neg-int v0, v1",
+        "not-int v0, v1",
+        "neg-long v0, v1",
+        "not-long v0, v1",
+        "neg-float v0, v1",
+        "neg-double v0, v1",
+        "int-to-long v0, v1",
+        "int-to-float v0, v1",
+        "int-to-double v0, v1",
+        "long-to-int v0, v1",
+        "long-to-float v0, v1",
+        "long-to-double v0, v1",
+        "float-to-int v0, v1",
+        "float-to-long v0, v1",
+        "float-to-double v0, v1",
+        "double-to-int v0, v1",
+        "double-to-long v0, v1",
+        "double-to-float v0, v1",
+        "int-to-byte v0, v1",
+        "int-to-char v0, v1",
+        "int-to-short v0, v1",
+        "add-int v0, v1, v2",
+        "sub-int v0, v1, v2",
+        "mul-int v0, v1, v2",
+        "div-int v0, v1, v2",
+        "rem-int v0, v1, v2",
+        "and-int v0, v1, v2",
+        "or-int v0, v1, v2",
+        "xor-int v0, v1, v2",
+        "shl-int v0, v1, v2",
+        "shr-int v0, v1, v2",
+        "ushr-int v0, v1, v2",
+        "add-long v0, v1, v2",
+        "sub-long v0, v1, v2",
+        "mul-long v0, v1, v2",
+        "div-long v0, v1, v2",
+        "rem-long v0, v1, v2",
+        "and-long v0, v1, v2",
+        "or-long v0, v1, v2",
+        "xor-long v0, v1, v2",
+        "shl-long v0, v1, v2",
+        "shr-long v0, v1, v2",
+        "ushr-long v0, v1, v2",
+        "add-float v0, v1, v2",
+        "sub-float v0, v1, v2",
+        "mul-float v0, v1, v2",
+        "div-float v0, v1, v2",
+        "rem-float v0, v1, v2",
+        "add-double v0, v1, v2",
+        "sub-double v0, v1, v2",
+        "mul-double v0, v1, v2",
+        "div-double v0, v1, v2",
+        "rem-double v0, v1, v2",
+        "add-int/2addr v0, v1",
+        "sub-int/2addr v0, v1",
+        "mul-int/2addr v0, v1",
+        "div-int/2addr v0, v1",
+        "rem-int/2addr v0, v1",
+        "and-int/2addr v0, v1",
+        "or-int/2addr v0, v1",
+        "xor-int/2addr v0, v1",
+        "shl-int/2addr v0, v1",
+        "shr-int/2addr v0, v1",
+        "ushr-int/2addr v0, v1",
+        "add-long/2addr v0, v1",
+        "sub-long/2addr v0, v1",
+        "mul-long/2addr v0, v1",
+        "div-long/2addr v0, v1",
+        "rem-long/2addr v0, v1",
+        "and-long/2addr v0, v1",
+        "or-long/2addr v0, v1",
+        "xor-long/2addr v0, v1",
+        "shl-long/2addr v0, v1",
+        "shr-long/2addr v0, v1",
+        "ushr-long/2addr v0, v1",
+        "add-float/2addr v0, v1",
+        "sub-float/2addr v0, v1",
+        "mul-float/2addr v0, v1",
+        "div-float/2addr v0, v1",
+        "rem-float/2addr v0, v1",
+        "add-double/2addr v0, v1",
+        "sub-double/2addr v0, v1",
+        "mul-double/2addr v0, v1",
+        "div-double/2addr v0, v1",
+        "rem-double/2addr v0, v1",
+        "add-int/lit16 v0, v1, 0x100",
+        "rsub-int v0, v1, 0x100",
+        "mul-int/lit16 v0, v1, 0x100",
+        "div-int/lit16 v0, v1, 0x100",
+        "rem-int/lit16 v0, v1, 0x100",
+        "and-int/lit16 v0, v1, 0x100",
+        "or-int/lit16 v0, v1, 0x100",
+        "xor-int/lit16 v0, v1, 0x100",
+        "add-int/lit8 v0, v1, 0x2",
+        "rsub-int/lit8 v0, v1, 0x2",
+        "mul-int/lit8 v0, v1, 0x2",
+        "div-int/lit8 v0, v1, 0x2",
+        "rem-int/lit8 v0, v1, 0x2",
+        "and-int/lit8 v0, v1, 0x2",
+        "or-int/lit8 v0, v1, 0x2",
+        "xor-int/lit8 v0, v1, 0x2",
+        "shl-int/lit8 v0, v1, 0x2",
+        "shr-int/lit8 v0, v1, 0x2",
+        "ushr-int/lit8 v0, v1, 0x2",
+        "invoke-polymorphic {v0, v1}, Ljava/lang/invoke/MethodHandle;->invoke([Ljava/lang/Object;)Ljava/lang/Object;, (I)V",
+        "invoke-polymorphic/range {v0 .. v1}, Ljava/lang/invoke/MethodHandle;->invoke([Ljava/lang/Object;)Ljava/lang/Object;, (I)V",
+        r#"invoke-custom {v0}, call_site_0("run", (I)V)@La/b;->bsm(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;"#,
+        r#"invoke-custom/range {v0 .. v1}, call_site_0("run", (I)V)@La/b;->bsm(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;"#,
+        "const-method-handle v0, invoke-static@La/b;->m(I)V",
+        "const-method-type v0, (I)V",
+    ];
+
+    #[test]
+    fn test_all_opcodes() {
+        let mut failed = vec![];
+        for op in ALL_OPS {
+            match parse_dex_op.parse(op) {
+                Ok(DexOp::Unused { .. }) | Err(_) => failed.push(*op),
+                Ok(parsed) => assert_eq!(parse_dex_op.parse(&parsed.to_string()), Ok(parsed)),
+            }
+        }
+        assert!(failed.is_empty(), "unsupported ops: {failed:#?}");
+    }
+
+    #[test]
+    fn test_unary_arith() {
+        for (input, arith_type, operand_type) in [
+            ("neg-int v0, v1", ArithUnaryType::Neg, ArithOperandType::Int),
+            (
+                "neg-long v0, v2",
+                ArithUnaryType::Neg,
+                ArithOperandType::Long,
+            ),
+            (
+                "neg-float v0, v1",
+                ArithUnaryType::Neg,
+                ArithOperandType::Float,
+            ),
+            (
+                "neg-double v0, v2",
+                ArithUnaryType::Neg,
+                ArithOperandType::Double,
+            ),
+            ("not-int v0, v1", ArithUnaryType::Not, ArithOperandType::Int),
+            (
+                "not-long v0, v2",
+                ArithUnaryType::Not,
+                ArithOperandType::Long,
+            ),
+        ] {
+            let op = parse_dex_op.parse(input).unwrap();
+            let DexOp::ArithUnary {
+                arith_type: a,
+                operand_type: o,
+                ..
+            } = op
+            else {
+                panic!("{input} parsed as {op:?}");
+            };
+            assert_eq!((a, o), (arith_type, operand_type));
+            assert_eq!(op.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn test_rsub_int_routing() {
+        // `rsub-int` splits on the dash like `add-int`, but must not land in the arith branch
+        let op = parse_dex_op.parse("rsub-int v0, v1, 0x100").unwrap();
+        assert!(matches!(
+            op,
+            DexOp::LitArith16 {
+                arith_type: LitArithType16::RSubIntLit16,
+                literal: 0x100,
+                ..
+            }
+        ));
+        let op = parse_dex_op.parse("rsub-int/lit8 v0, v1, 0x2").unwrap();
+        assert!(matches!(
+            op,
+            DexOp::LitArith8 {
+                arith_type: LitArithType8::RSubIntLit8,
+                literal: 2,
+                ..
+            }
+        ));
+        assert!(parse_dex_op.parse("rsub-long v0, v1, v2").is_err());
+    }
+
+    #[test]
+    fn test_array_access_value_types() {
+        use ArrayValueType::*;
+
+        for (access_type, access) in [
+            (ArrayAccessType::Get, "aget"),
+            (ArrayAccessType::Put, "aput"),
+        ] {
+            for (value_type, suffix) in [
+                (Normal, ""),
+                (Wide, "-wide"),
+                (Object, "-object"),
+                (Boolean, "-boolean"),
+                (Byte, "-byte"),
+                (Char, "-char"),
+                (Short, "-short"),
+            ] {
+                let input = format!("{access}{suffix} v0, p1, v2");
+                let op = parse_dex_op.parse(&input).unwrap();
+                assert_eq!(
+                    op,
+                    DexOp::ArrayAccess {
+                        access_type,
+                        value_type,
+                        reg: Register::Local(0),
+                        arr: Register::Parameter(1),
+                        idx: Register::Local(2),
+                    }
+                );
+                assert_eq!(op.to_string(), input);
+            }
+        }
+
+        let e = crate::op::parse_ops("    const/4 v0, 0\n    aget-int v0, p1, v0\n").unwrap_err();
+        // without `std` the reason is dropped and only the position reported
+        assert_eq!(
+            e.details,
+            "unknown array opcode aget-int at line 2 (byte 22)"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_tab_separated_fixture_ops() {
+        use crate::{class::parse_class, op::Op};
+        use winnow::Parser;
+
+        // every separator outside a string literal becomes a tab
+        let tabbed = |written: &str| {
+            let (mut quoted, mut escaped) = (false, false);
+            written
+                .chars()
+                .map(|c| {
+                    match c {
+                        '"' if !escaped => quoted = !quoted,
+                        ' ' if !quoted => return '\t',
+                        _ => {}
+                    }
+                    escaped = quoted && c == '\\' && !escaped;
+                    c
+                })
+                .collect::<String>()
+        };
+        for entry in std::fs::read_dir("tests").unwrap() {
+            let smali = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            let class = parse_class().parse_next(&mut smali.as_str()).unwrap();
+            for op in class.methods.iter().flat_map(|m| &m.ops) {
+                let Op::Op(op) = op else { continue };
+                let input = tabbed(&op.to_string());
+                let mut rest = input.as_str();
+                assert_eq!(&parse_dex_op(&mut rest).unwrap(), op, "{input}");
+                assert!(rest.is_empty(), "{input}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_tab_separated_operands() {
+        for (input, written) in [
+            ("if-eqz\tv0,\t:cond_0", "if-eqz v0, :cond_0"),
+            (
+                "const/high16\tv0, 0x7f0a0000",
+                "const/high16 v0, 0x7f0a0000",
+            ),
+            (
+                "const-wide/high16\t\tv0, 0x4000000000000000L",
+                "const-wide/high16 v0, 0x4000000000000000L",
+            ),
+            (
+                "invoke-virtual\t{p0},\tLa;->b()V",
+                "invoke-virtual {p0}, La;->b()V",
+            ),
+            (
+                "invoke-static/range\t{v0 .. v2}, La;->c(III)V",
+                "invoke-static/range { v0 .. v2 }, La;->c(III)V",
+            ),
+            (
+                "packed-switch\tv0, :pswitch_data_0",
+                "packed-switch v0, :pswitch_data_0",
+            ),
+            ("add-int/lit8\tv0,\tv1,\t0x1", "add-int/lit8 v0, v1, 1"),
+        ] {
+            let op = parse_dex_op(&mut &input[..]).unwrap();
+            assert_eq!(op.to_string(), written);
+        }
+    }
+
+    #[test]
+    fn test_const_string_surrogate_pair() {
+        use crate::{escape_string, unescape_string};
+
+        let mut input = r#"const-string v0, "smile \ud83d\ude00\n\"caf\u00e9\"""#;
+        let DexOp::Const {
+            value: StringOrTypeSig::String(raw),
+            ..
+        } = parse_dex_op(&mut input).unwrap()
+        else {
+            panic!("expected a const-string");
+        };
+        let decoded = unescape_string(&raw).unwrap();
+        assert_eq!(decoded, "smile \u{1F600}\n\"café\"");
+        assert_eq!(escape_string(&decoded), raw);
+
+        assert_eq!(unescape_string(r"\uD83D\uDE00").unwrap(), "\u{1F600}");
+        for bad in [r"\ud83d", r"\ude00\ud83d", r"\ud83dx", r"\u12", r"\x"] {
+            assert!(unescape_string(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_opcode() {
+        let opcode = |mut input: &str| parse_dex_op(&mut input).unwrap().opcode();
+        for (input, expected) in [
+            ("nop", 0x00),
+            ("move v0, v1", 0x01),
+            ("move-object/16 v0, v1", 0x09),
+            ("return-void", 0x0e),
+            ("const-wide/high16 v0, 0x4000000000000000L", 0x19),
+            ("goto/16 :goto_0", 0x29),
+            ("if-lez v0, :cond_0", 0x3d),
+            ("aput-short v0, v1, v2", 0x51),
+            ("iget-object v0, p0, La;->b:Ljava/lang/Object;", 0x54),
+            ("sput-wide v0, La;->c:J", 0x68),
+            ("invoke-interface/range {v0 .. v1}, La;->d(I)V", 0x78),
+            ("int-to-short v0, v1", 0x8f),
+            ("ushr-long v0, v2, v4", 0xa5),
+            ("rem-double/2addr v0, v2", 0xcf),
+            ("xor-int/lit16 v0, v1, 0x100", 0xd7),
+            ("ushr-int/lit8 v0, v1, 0x1", 0xe2),
+            ("const-method-type v0, ()V", 0xff),
+        ] {
+            assert_eq!(opcode(input), Some(expected), "{input}");
+        }
+
+        let not_float = DexOp::ArithUnary {
+            arith_type: ArithUnaryType::Not,
+            operand_type: ArithOperandType::Float,
+            dest: Register::Local(0),
+            src: Register::Local(1),
+        };
+        assert_eq!(not_float.opcode(), None);
+        let shl_double = DexOp::Arith2Addr {
+            arith_type: ArithType::Shl,
+            operand_type: ArithOperand2AddrType::Double,
+            dest: Register::Local(0),
+            src: Register::Local(2),
+        };
+        assert_eq!(shl_double.opcode(), None);
+    }
+
+    #[test]
+    fn test_const_encoding_selection() {
+        let int = |value| DexOp::const_int(Register::Local(0), value).to_string();
+        assert_eq!(int(0), "const/4 v0, 0");
+        assert_eq!(int(-8), "const/4 v0, -8");
+        assert_eq!(int(8), "const/16 v0, 8");
+        assert_eq!(int(0x1000), "const/16 v0, 4096");
+        assert_eq!(int(0x10000), "const v0, 65536");
+        assert_eq!(int(i32::MIN), "const v0, -2147483648");
+
+        let wide = |value| DexOp::const_wide(Register::Local(0), value);
+        for (value, mnemonic) in [
+            (0, "const-wide/16"),
+            (-0x8000, "const-wide/16"),
+            (0x8000, "const-wide/32"),
+            (i64::from(i32::MIN), "const-wide/32"),
+            (0x4000_0000_0000_0000, "const-wide/high16"),
+            (0x1_0000_0000, "const-wide"),
+        ] {
+            let op = wide(value);
+            assert_eq!(op.const_value(), Some(value));
+            assert!(op.to_string().starts_with(&format!("{mnemonic} ")), "{op}");
+            let written = op.to_string();
+            assert_eq!(parse_dex_op(&mut written.as_str()).unwrap(), op);
+        }
+    }
+
+    #[test]
+    fn test_const_value() {
+        let value = |mut input: &str| parse_dex_op(&mut input).unwrap().const_value();
+        assert_eq!(value("const v0, 0x7f0a0001"), Some(0x7f0a0001));
+        assert_eq!(value("const/4 v0, -0x1"), Some(-1));
+        assert_eq!(value("const/high16 v0, 0x7f0a0000"), Some(0x7f0a0000));
+        assert_eq!(value("const/high16 v0, -0x10000"), Some(-0x10000));
+        assert_eq!(
+            value("const-wide/high16 v0, 0x4000000000000000L"),
+            Some(0x4000000000000000)
+        );
+        assert_eq!(value("const-wide v0, 0x123456789L"), Some(0x123456789));
+        assert_eq!(value(r#"const-string v0, "s""#), None);
+    }
+
+    #[test]
+    fn test_lit_arith_round_trip() {
+        use LitArithType8::*;
+        use LitArithType16::*;
+
+        let lit8 = [
+            (AddIntLit8, "add-int/lit8"),
+            (RSubIntLit8, "rsub-int/lit8"),
+            (MulIntLit8, "mul-int/lit8"),
+            (DivIntLit8, "div-int/lit8"),
+            (RemIntLit8, "rem-int/lit8"),
+            (AndIntLit8, "and-int/lit8"),
+            (OrIntLit8, "or-int/lit8"),
+            (XorIntLit8, "xor-int/lit8"),
+            (ShlIntLit8, "shl-int/lit8"),
+            (ShrIntLit8, "shr-int/lit8"),
+            (UshrIntLit8, "ushr-int/lit8"),
+        ];
+        for (arith_type, mnemonic) in lit8 {
+            assert_eq!(arith_type.to_string(), mnemonic);
+            for (literal, written) in [(-128, "-0x80"), (-1, "0xff"), (0, "0x0"), (127, "0x7f")] {
+                let input = format!("{mnemonic} v3, p1, {written}");
+                let op = parse_dex_op.parse(&input).unwrap();
+                assert_eq!(
+                    op,
+                    DexOp::LitArith8 {
+                        arith_type,
+                        dest: Register::Local(3),
+                        src: Register::Parameter(1),
+                        literal,
+                    }
+                );
+                assert_eq!(op.to_string(), format!("{mnemonic} v3, p1, {literal}"));
+                assert_eq!(parse_dex_op.parse(&op.to_string()), Ok(op));
+            }
+            assert!(
+                parse_dex_op
+                    .parse(&format!("{mnemonic} v0, v1, 0x80"))
+                    .is_ok()
+            );
+            assert!(
+                parse_dex_op
+                    .parse(&format!("{mnemonic} v0, v1, 0x100"))
+                    .is_err()
+            );
+        }
+
+        let lit16 = [
+            (AddIntLit16, "add-int/lit16"),
+            (RSubIntLit16, "rsub-int"),
+            (MulIntLit16, "mul-int/lit16"),
+            (DivIntLit16, "div-int/lit16"),
+            (RemIntLit16, "rem-int/lit16"),
+            (AndIntLit16, "and-int/lit16"),
+            (OrIntLit16, "or-int/lit16"),
+            (XorIntLit16, "xor-int/lit16"),
+        ];
+        for (arith_type, mnemonic) in lit16 {
+            assert_eq!(arith_type.to_string(), mnemonic);
+            for (literal, written) in [(-32768, "-0x8000"), (-1, "0xffff"), (0x7fff, "0x7fff")] {
+                let input = format!("{mnemonic} v0, v15, {written}");
+                let op = parse_dex_op.parse(&input).unwrap();
+                assert_eq!(
+                    op,
+                    DexOp::LitArith16 {
+                        arith_type,
+                        dest: Register::Local(0),
+                        src: Register::Local(15),
+                        literal,
+                    }
+                );
+                assert_eq!(op.to_string(), format!("{mnemonic} v0, v15, {literal}"));
+                assert_eq!(parse_dex_op.parse(&op.to_string()), Ok(op));
+            }
+            assert!(
+                parse_dex_op
+                    .parse(&format!("{mnemonic} v0, v1, 0x10000"))
+                    .is_err()
+            );
+        }
+    }
+
     #[test]
     fn test_parse_method_ref() {
         let mut input = r#"Landroidx/core/content/res/TypedArrayUtils;->getNamedString(Landroid/content/res/TypedArray;Lorg/xmlpull/v1/XmlPullParser;Ljava/lang/String;I)Ljava/lang/String;"#;
@@ -2064,6 +3929,16 @@ mod tests {
         let _ = parse_dex_op(&mut input).unwrap();
     }
 
+    #[test]
+    fn test_invoke_static_clinit() {
+        let mut input = "invoke-static {}, Lcom/example/Foo;-><clinit>()V";
+        let instr = parse_dex_op(&mut input).unwrap();
+        assert_eq!(
+            instr.to_string(),
+            "invoke-static {}, Lcom/example/Foo;-><clinit>()V"
+        );
+    }
+
     #[test]
     fn test_invoke_interface() {
         let mut input = "invoke-interface/range {v6 .. v12}, Lzpf;->a(JIIILxpf;)V";
@@ -2095,9 +3970,31 @@ mod tests {
         assert_eq!(sixteen, -0x8000);
     }
 
+    #[test]
+    fn test_parse_literal_bit_pattern() {
+        let i: i8 = parse_int_lit().parse_next(&mut "0xff").unwrap();
+        assert_eq!(i, -1);
+        let i: i32 = parse_int_lit().parse_next(&mut "0xffffffff").unwrap();
+        assert_eq!(i, -1);
+        let i: i64 = parse_int_lit()
+            .parse_next(&mut "0x8000000000000000L")
+            .unwrap();
+        assert_eq!(i, i64::MIN);
+
+        // only hex, and only as many bits as the type has
+        assert!(parse_int_lit::<i8>().parse_next(&mut "255").is_err());
+        assert!(parse_int_lit::<i8>().parse_next(&mut "0x100").is_err());
+        assert!(parse_int_lit::<i8>().parse_next(&mut "-0xff").is_err());
+        assert!(
+            parse_int_lit::<i32>()
+                .parse_next(&mut "0x1ffffffff")
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_filled_new_array() {
-        let mut input = "filled-new-array {v0, v1}, Ljava/lang/String;";
+        let mut input = "filled-new-array {v0, v1}, [Ljava/lang/String;";
         let instr = parse_dex_op(&mut input).unwrap();
         assert_eq!(
             instr,
@@ -2105,11 +4002,21 @@ mod tests {
                 registers: vec![Register::Local(0), Register::Local(1)],
                 class: StringOrTypeSig::TypeSig(
                     parse_typesignature()
-                        .parse_next(&mut "Ljava/lang/String;")
+                        .parse_next(&mut "[Ljava/lang/String;")
                         .unwrap()
                 )
             }
         );
+        assert_eq!(
+            instr.to_string(),
+            "filled-new-array {v0, v1}, [Ljava/lang/String;"
+        );
+
+        // the operand names the array type, an element type is rejected
+        let mut input = "filled-new-array {v0, v1}, Ljava/lang/String;";
+        assert!(parse_dex_op(&mut input).is_err());
+        let mut input = "filled-new-array/range {v0 .. v1}, I";
+        assert!(parse_dex_op(&mut input).is_err());
     }
 
     #[test]
@@ -2129,4 +4036,121 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_target_label() {
+        let op = parse_dex_op(&mut "if-eqz v0, :cond_0").unwrap();
+        assert_eq!(op.target_label(), Some(&Label(Cow::Borrowed("cond_0"))));
+
+        let op = parse_dex_op(&mut "if-ne v0, v1, :cond_1").unwrap();
+        assert_eq!(op.target_label(), Some(&Label(Cow::Borrowed("cond_1"))));
+
+        let op = parse_dex_op(&mut "goto/16 :goto_2").unwrap();
+        assert_eq!(op.target_labels(), vec![&Label(Cow::Borrowed("goto_2"))]);
+
+        let op = parse_dex_op(&mut "packed-switch p1, :pswitch_data_0").unwrap();
+        assert_eq!(
+            op.target_label(),
+            Some(&Label(Cow::Borrowed("pswitch_data_0")))
+        );
+
+        let op = parse_dex_op(&mut "fill-array-data v0, :array_0").unwrap();
+        assert_eq!(op.target_label(), Some(&Label(Cow::Borrowed("array_0"))));
+
+        let op = parse_dex_op(&mut "return-void").unwrap();
+        assert!(op.target_label().is_none());
+        assert!(op.target_labels().is_empty());
+    }
+
+    #[test]
+    fn test_array_type_operands() {
+        let is_array = |class: &StringOrTypeSig| {
+            matches!(class, StringOrTypeSig::TypeSig(TypeSignature::Array(_)))
+        };
+        for input in [
+            "check-cast v0, [I",
+            "check-cast p1, [Ljava/lang/String;",
+            "instance-of v0, v1, [Ljava/lang/Object;",
+            "new-array v0, v1, [B",
+        ] {
+            let op = parse_dex_op.parse(input).unwrap();
+            match &op {
+                DexOp::CheckCast { class, .. }
+                | DexOp::InstanceOf { class, .. }
+                | DexOp::NewArray { class, .. } => assert!(is_array(class), "{op:?}"),
+                _ => panic!("{op:?}"),
+            }
+            assert_eq!(op.to_string(), input);
+        }
+
+        assert!(
+            parse_dex_op
+                .parse("new-instance v0, Ljava/lang/Object;")
+                .is_ok()
+        );
+        assert!(parse_dex_op.parse("new-instance v0, [I").is_err());
+        assert!(
+            parse_dex_op
+                .parse("new-instance v0, [Ljava/lang/Object;")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_register_order() {
+        let mut regs = vec![
+            Register::Parameter(1),
+            Register::Local(10),
+            Register::Parameter(0),
+            Register::Local(2),
+        ];
+        regs.sort();
+        assert_eq!(
+            regs,
+            [
+                Register::Local(2),
+                Register::Local(10),
+                Register::Parameter(0),
+                Register::Parameter(1),
+            ]
+        );
+        assert_eq!(
+            Register::Local(4).wide_pair(),
+            Some((Register::Local(4), Register::Local(5)))
+        );
+        assert_eq!(
+            Register::Parameter(1).wide_pair(),
+            Some((Register::Parameter(1), Register::Parameter(2)))
+        );
+        assert_eq!(Register::Local(u16::MAX).wide_pair(), None);
+        assert_eq!(Register::Parameter(u16::MAX).wide_pair(), None);
+    }
+
+    #[test]
+    fn test_literal_case_and_suffix() {
+        for (input, value, canonical) in [
+            ("const v0, 0XFF", 0xff, "const v0, 255"),
+            ("const v0, 0xAbCdEf", 0xabcdef, "const v0, 11259375"),
+            ("const v0, 0x10L", 0x10, "const v0, 16"),
+            ("const v0, -0X1L", -1, "const v0, -1"),
+        ] {
+            let op = parse_dex_op.parse(input).unwrap();
+            assert_eq!(
+                op,
+                DexOp::ConstLiteral {
+                    const_type: ConstLiteralType::Const,
+                    dest: Register::Local(0),
+                    value: ConstLiteralValue::Const(value),
+                },
+                "{input}"
+            );
+            assert_eq!(op.to_string(), canonical);
+        }
+        assert_eq!(
+            parse_dex_op.parse("const/4 v1, 0x7L").unwrap().to_string(),
+            "const/4 v1, 7"
+        );
+        // opcodes are case-sensitive, as in smali itself
+        assert!(parse_dex_op.parse("CONST v0, 0x1").is_err());
+    }
 }
@@ -1,19 +1,28 @@
-use std::{borrow::Cow, fmt};
+use alloc::borrow::Cow;
+use core::fmt;
 
 use winnow::{
     ModalParser, Parser,
+    ascii::{digit0, digit1, multispace0},
     combinator::{alt, delimited, opt, preceded, repeat, terminated},
-    error::InputError,
+    error::ErrMode,
+    stream::Stream,
     token::{literal, one_of, take_while},
 };
 
 use crate::{
-    object_identifier::{ObjectIdentifier, parse_object_identifier},
-    op::dex_op::{DexOp, parse_dex_op},
-    parse_int_lit, ws,
+    ParseFailure, ParseOptions, SmaliError, comment, fit_bits,
+    format::FormatStyle,
+    object_identifier::{ObjectIdentifier, parse_object_identifier_with},
+    op::dex_op::{DexOp, Register, parse_dex_op_with},
+    parse_complete, parse_int_lit,
+    prelude::*,
+    ws,
 };
 
 pub mod dex_op;
+#[cfg(feature = "odex")]
+pub mod odex;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Label<'a>(pub Cow<'a, str>);
@@ -25,8 +34,20 @@ impl fmt::Display for Label<'_> {
     }
 }
 
+impl<'a> Label<'a> {
+    /// A label named `name`, without the leading colon
+    pub fn new(name: impl Into<Cow<'a, str>>) -> Self {
+        Label(name.into())
+    }
+
+    /// Copies the label name, detaching it from the parsed source
+    pub fn into_owned(self) -> Label<'static> {
+        Label(Cow::Owned(self.0.into_owned()))
+    }
+}
+
 /// Parse a label in smali syntax, e.g. ":cond_0"
-pub fn parse_label<'a>() -> impl ModalParser<&'a str, Label<'a>, InputError<&'a str>> {
+pub fn parse_label<'a>() -> impl ModalParser<&'a str, Label<'a>, ParseFailure> {
     ws(preceded(
         one_of(':'),
         take_while(1.., |c: char| c.is_alphanumeric() || c == '_' || c == '$'),
@@ -41,7 +62,17 @@ pub struct TryRange<'a> {
     pub end: Label<'a>,
 }
 
-pub fn parse_try_range<'a>() -> impl ModalParser<&'a str, TryRange<'a>, InputError<&'a str>> {
+impl TryRange<'_> {
+    /// Copies the label names, detaching the range from the parsed source
+    pub fn into_owned(self) -> TryRange<'static> {
+        TryRange {
+            start: self.start.into_owned(),
+            end: self.end.into_owned(),
+        }
+    }
+}
+
+pub fn parse_try_range<'a>() -> impl ModalParser<&'a str, TryRange<'a>, ParseFailure> {
     delimited(
         ws(one_of('{')),
         (terminated(parse_label(), literal("..")), parse_label()),
@@ -73,13 +104,40 @@ pub enum CatchDirective<'a> {
     },
 }
 
-pub fn parse_catch_directive<'a>()
--> impl ModalParser<&'a str, CatchDirective<'a>, InputError<&'a str>> {
+impl CatchDirective<'_> {
+    /// Copies any borrowed text, detaching the directive from the parsed source
+    pub fn into_owned(self) -> CatchDirective<'static> {
+        match self {
+            CatchDirective::Catch {
+                exception,
+                try_range,
+                handler,
+            } => CatchDirective::Catch {
+                exception: exception.into_owned(),
+                try_range: try_range.into_owned(),
+                handler: handler.into_owned(),
+            },
+            CatchDirective::CatchAll { try_range, handler } => CatchDirective::CatchAll {
+                try_range: try_range.into_owned(),
+                handler: handler.into_owned(),
+            },
+        }
+    }
+}
+
+pub fn parse_catch_directive<'a>() -> impl ModalParser<&'a str, CatchDirective<'a>, ParseFailure> {
+    parse_catch_directive_with(ParseOptions::default())
+}
+
+/// Parses a `.catch` or `.catchall` directive with the nesting limit of `options`
+pub fn parse_catch_directive_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, CatchDirective<'a>, ParseFailure> {
     alt((
         preceded(
             literal(".catch"),
             (
-                ws(parse_object_identifier()),
+                ws(parse_object_identifier_with(options)),
                 ws(parse_try_range()),
                 parse_label(),
             ),
@@ -123,15 +181,80 @@ pub enum ArrayDataElement {
     Double(f64),
 }
 
+/// Writes `v` as signed hex the way baksmali does, e.g. `-0x1` rather than `0xff`
+pub(crate) fn write_signed_hex(f: &mut impl fmt::Write, v: i64) -> fmt::Result {
+    if v < 0 {
+        write!(f, "-{:#x}", v.unsigned_abs())
+    } else {
+        write!(f, "{v:#x}")
+    }
+}
+
+/// Writes `v` as a float literal the way baksmali does, spelling out `NaN` and
+/// `Infinity`. The caller adds any postfix.
+pub(crate) fn write_float<T: fmt::Debug + Into<f64> + Copy>(
+    f: &mut impl fmt::Write,
+    v: T,
+) -> fmt::Result {
+    let d: f64 = v.into();
+    if d.is_nan() {
+        write!(f, "NaN")
+    } else if d.is_infinite() {
+        write!(f, "{}Infinity", if d < 0.0 { "-" } else { "" })
+    } else {
+        write!(f, "{v:?}")
+    }
+}
+
 impl fmt::Display for ArrayDataElement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // baksmali's canonical form: the postfix names the element width, none for ints
         match self {
-            ArrayDataElement::Byte(b) => write!(f, "{b:#x}t"),
-            ArrayDataElement::Short(s) => write!(f, "{s:#x}s"),
-            ArrayDataElement::Int(i) => write!(f, "{i:#x}"),
-            ArrayDataElement::Long(l) => write!(f, "{l:#x}l"),
-            ArrayDataElement::Float(fl) => write!(f, "{:#x}f", fl.to_bits()),
-            ArrayDataElement::Double(d) => write!(f, "{:#x}d", d.to_bits()),
+            ArrayDataElement::Byte(b) => {
+                write_signed_hex(f, *b as i64)?;
+                write!(f, "t")
+            }
+            ArrayDataElement::Short(s) => {
+                write_signed_hex(f, *s as i64)?;
+                write!(f, "s")
+            }
+            ArrayDataElement::Int(i) => write_signed_hex(f, *i as i64),
+            ArrayDataElement::Long(l) => {
+                write_signed_hex(f, *l)?;
+                write!(f, "L")
+            }
+            ArrayDataElement::Float(fl) => {
+                write_float(f, *fl)?;
+                write!(f, "f")
+            }
+            ArrayDataElement::Double(d) => {
+                write_float(f, *d)?;
+                write!(f, "d")
+            }
+        }
+    }
+}
+
+impl ArrayDataElement {
+    /// Size in bytes of the kind the element was written as, e.g. 8 for `0x1l`
+    pub fn width(&self) -> u32 {
+        match self {
+            ArrayDataElement::Byte(_) => 1,
+            ArrayDataElement::Short(_) => 2,
+            ArrayDataElement::Int(_) | ArrayDataElement::Float(_) => 4,
+            ArrayDataElement::Long(_) | ArrayDataElement::Double(_) => 8,
+        }
+    }
+
+    /// Raw bit pattern of the element, sign-extended for the integer kinds
+    pub fn to_bits(&self) -> u64 {
+        match self {
+            ArrayDataElement::Byte(b) => *b as u64,
+            ArrayDataElement::Short(s) => *s as u64,
+            ArrayDataElement::Int(i) => *i as u64,
+            ArrayDataElement::Long(l) => *l as u64,
+            ArrayDataElement::Float(fl) => fl.to_bits() as u64,
+            ArrayDataElement::Double(d) => d.to_bits(),
         }
     }
 }
@@ -145,69 +268,149 @@ pub struct ArrayDataDirective {
     pub elements: Vec<ArrayDataElement>,
 }
 
-pub fn parse_array_data_directive<'a>()
--> impl ModalParser<&'a str, ArrayDataDirective, InputError<&'a str>> {
-    delimited(
-        ws(literal(".array-data")),
+/// Parses a float literal as baksmali writes it, e.g. `1.5f`, `-2.0E10d` or `Infinityf`,
+/// or a decimal integer with the postfix, e.g. `1f` for 1.0. The postfix is required,
+/// it tells floats from doubles.
+fn parse_float_element<'a>() -> impl ModalParser<&'a str, ArrayDataElement, ParseFailure> {
+    let exponent = || (one_of(['e', 'E']), opt(one_of(['+', '-'])), digit1);
+    (
         (
-            ws(parse_int_lit::<u32>()),
-            repeat(
-                0..,
-                ws((
-                    parse_int_lit::<i64>(),
-                    opt(alt((
-                        one_of('t'),
-                        one_of('s'),
-                        one_of('l'),
-                        one_of('f'),
-                        one_of('d'),
-                    ))),
-                )),
-            ),
-        ),
-        ws(literal(".end array-data")),
+            opt(one_of('-')),
+            alt((
+                literal("Infinity"),
+                literal("NaN"),
+                (digit1, one_of('.'), digit0, opt(exponent())).take(),
+                (digit1, exponent()).take(),
+                digit1,
+            )),
+        )
+            .take(),
+        one_of(['f', 'F', 'd', 'D']),
     )
-    .map(
-        |(width, e): (u32, Vec<(i64, Option<char>)>)| ArrayDataDirective {
-            width,
-            elements: e
-                .into_iter()
-                .map(|(value, postfix)| {
-                    if let Some(postfix) = postfix {
-                        match postfix {
-                            't' => ArrayDataElement::Byte(value as i8),
-                            's' => ArrayDataElement::Short(value as i16),
-                            'l' => ArrayDataElement::Long(value),
-                            'f' => ArrayDataElement::Float(f32::from_bits(value as u32)),
-                            'd' => ArrayDataElement::Double(f64::from_bits(value as u64)),
-                            _ => unreachable!(),
-                        }
-                    } else {
-                        match width {
-                            1 => ArrayDataElement::Byte(value as i8),
-                            2 => ArrayDataElement::Short(value as i16),
-                            4 => ArrayDataElement::Int(value as i32),
-                            8 => ArrayDataElement::Long(value),
-                            _ => ArrayDataElement::Int(value as i32),
-                        }
+        .try_map(|(lit, postfix): (&str, char)| {
+            if matches!(postfix, 'f' | 'F') {
+                lit.parse::<f32>().map(ArrayDataElement::Float)
+            } else {
+                lit.parse::<f64>().map(ArrayDataElement::Double)
+            }
+        })
+}
+
+/// Parses one `.array-data` element; bare integers are typed by the directive's `width`.
+fn parse_array_data_element<'a>(
+    width: u32,
+) -> impl ModalParser<&'a str, ArrayDataElement, ParseFailure> {
+    ws(alt((
+        parse_float_element(),
+        (
+            parse_int_lit::<i64>(),
+            opt(alt((
+                one_of('t'),
+                one_of('s'),
+                one_of('l'),
+                one_of('f'),
+                one_of('d'),
+            ))),
+        )
+            .try_map(move |(value, postfix)| {
+                Ok::<_, SmaliError>(match postfix {
+                    Some('t') => ArrayDataElement::Byte(fit_bits(value, 8)? as i8),
+                    Some('s') => ArrayDataElement::Short(fit_bits(value, 16)? as i16),
+                    Some('l') => ArrayDataElement::Long(value),
+                    Some('f') => {
+                        ArrayDataElement::Float(f32::from_bits(fit_bits(value, 32)? as u32))
                     }
+                    Some('d') => ArrayDataElement::Double(f64::from_bits(value as u64)),
+                    _ => match width {
+                        1 => ArrayDataElement::Byte(fit_bits(value, 8)? as i8),
+                        2 => ArrayDataElement::Short(fit_bits(value, 16)? as i16),
+                        8 => ArrayDataElement::Long(value),
+                        _ => ArrayDataElement::Int(fit_bits(value, 32)? as i32),
+                    },
                 })
-                .collect(),
-        },
+            }),
+    )))
+}
+
+pub fn parse_array_data_directive<'a>()
+-> impl ModalParser<&'a str, ArrayDataDirective, ParseFailure> {
+    delimited(
+        ws(literal(".array-data")),
+        ws(parse_int_lit::<u32>()).flat_map(|width| {
+            repeat(0.., parse_array_data_element(width))
+                .map(move |elements| ArrayDataDirective { width, elements })
+        }),
+        ws(literal(".end array-data")),
     )
 }
 
+impl ArrayDataDirective {
+    /// Serializes the payload the way it is stored in the dex file: every element
+    /// takes `width` bytes in little-endian order, whatever kind it was written as.
+    /// Elements wider than `width` are truncated, narrower ones are sign-extended.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let width = self.width as usize;
+        let mut out = Vec::with_capacity(width * self.elements.len());
+        for e in &self.elements {
+            let bits = e.to_bits();
+            let sign = if (bits as i64) < 0 { 0xff } else { 0 };
+            out.extend(
+                bits.to_le_bytes()
+                    .into_iter()
+                    .chain(core::iter::repeat(sign))
+                    .take(width),
+            );
+        }
+        out
+    }
+
+    /// Checks that the width is one dex allows, 1, 2, 4 or 8, and that every element
+    /// was written as that width, e.g. not a `0x1l` long in a width 1 payload
+    pub fn validate(&self) -> Result<(), SmaliError> {
+        if !matches!(self.width, 1 | 2 | 4 | 8) {
+            return Err(SmaliError {
+                details: format!("invalid array-data width {}", self.width),
+            });
+        }
+        match self
+            .elements
+            .iter()
+            .enumerate()
+            .find(|(_, e)| e.width() != self.width)
+        {
+            Some((i, e)) => Err(SmaliError {
+                details: format!(
+                    "array-data element {i} ({e}) is {} bytes wide, expected {}",
+                    e.width(),
+                    self.width
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The payload as bytes, if it is a `byte[]`/`boolean[]` payload (width 1)
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        (self.width == 1).then(|| self.to_bytes())
+    }
+
+    /// The payload as ints, if it is an `int[]` payload (width 4)
+    pub fn as_i32_slice(&self) -> Option<Vec<i32>> {
+        (self.width == 4).then(|| {
+            self.to_bytes()
+                .chunks_exact(4)
+                .map(|c| i32::from_le_bytes(c.try_into().unwrap()))
+                .collect()
+        })
+    }
+}
+
 impl fmt::Display for ArrayDataDirective {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Print the header. We'll print the width in hex.
-        writeln!(f, ".array-data {:#x}", self.width)?;
-        // Print elements in groups (here, 4 per line).
-        for chunk in self.elements.chunks(4) {
-            write!(f, "    ")?;
-            for elem in chunk {
-                write!(f, "{elem} ")?;
-            }
-            writeln!(f)?;
+        // Print the header and one element per line, like baksmali.
+        writeln!(f, ".array-data {}", self.width)?;
+        for elem in &self.elements {
+            writeln!(f, "    {elem}")?;
         }
         write!(f, ".end array-data")
     }
@@ -219,8 +422,26 @@ pub struct PackedSwitchDirective<'a> {
     pub targets: Vec<Label<'a>>,
 }
 
+impl<'a> PackedSwitchDirective<'a> {
+    /// Copies the target labels, detaching the directive from the parsed source
+    pub fn into_owned(self) -> PackedSwitchDirective<'static> {
+        PackedSwitchDirective {
+            first_key: self.first_key,
+            targets: self.targets.into_iter().map(Label::into_owned).collect(),
+        }
+    }
+
+    /// Pairs every target with its key, `first_key + i` for the `i`th target
+    pub fn keys(&self) -> impl Iterator<Item = (i32, &Label<'a>)> {
+        self.targets
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (self.first_key.wrapping_add(i as i32), t))
+    }
+}
+
 pub fn parse_packed_switch_directive<'a>()
--> impl ModalParser<&'a str, PackedSwitchDirective<'a>, InputError<&'a str>> {
+-> impl ModalParser<&'a str, PackedSwitchDirective<'a>, ParseFailure> {
     delimited(
         ws(literal(".packed-switch")),
         (ws(parse_int_lit::<i32>()), repeat(0.., parse_label())),
@@ -229,16 +450,25 @@ pub fn parse_packed_switch_directive<'a>()
     .map(|(first_key, targets)| PackedSwitchDirective { first_key, targets })
 }
 
-impl fmt::Display for PackedSwitchDirective<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Print the header with the first key in hex.
-        writeln!(f, ".packed-switch {:#x}", self.first_key)?;
-        // Print each target label, indented.
+impl PackedSwitchDirective<'_> {
+    /// Writes the directive with the first key in the radix chosen by `style`
+    pub fn write_with(&self, style: &FormatStyle) -> String {
+        let mut out = format!(
+            ".packed-switch {}\n",
+            style.switch_keys.format(self.first_key)
+        );
         for target in &self.targets {
-            writeln!(f, "    {target}")?;
+            out.push_str(&format!("    {target}\n"));
         }
-        // Print the footer without a trailing newline.
-        write!(f, ".end packed-switch")
+        // no trailing newline after the footer
+        out.push_str(".end packed-switch");
+        out
+    }
+}
+
+impl fmt::Display for PackedSwitchDirective<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.write_with(&FormatStyle::default()))
     }
 }
 
@@ -250,7 +480,7 @@ pub struct SparseSwitchEntry<'a> {
 }
 
 pub fn parse_sparse_switch_entry<'a>()
--> impl ModalParser<&'a str, SparseSwitchEntry<'a>, InputError<&'a str>> {
+-> impl ModalParser<&'a str, SparseSwitchEntry<'a>, ParseFailure> {
     (
         terminated(ws(parse_int_lit::<i32>()), literal("->")),
         parse_label(),
@@ -272,7 +502,7 @@ pub struct SparseSwitchDirective<'a> {
 }
 
 pub fn parse_sparse_switch_directive<'a>()
--> impl ModalParser<&'a str, SparseSwitchDirective<'a>, InputError<&'a str>> {
+-> impl ModalParser<&'a str, SparseSwitchDirective<'a>, ParseFailure> {
     delimited(
         ws(literal(".sparse-switch")),
         repeat(0.., parse_sparse_switch_entry()),
@@ -281,16 +511,39 @@ pub fn parse_sparse_switch_directive<'a>()
     .map(|entries| SparseSwitchDirective { entries })
 }
 
-impl fmt::Display for SparseSwitchDirective<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Print the header.
-        writeln!(f, ".sparse-switch")?;
-        // Print each entry indented.
+impl SparseSwitchDirective<'_> {
+    /// Copies the target labels, detaching the directive from the parsed source
+    pub fn into_owned(self) -> SparseSwitchDirective<'static> {
+        SparseSwitchDirective {
+            entries: self
+                .entries
+                .into_iter()
+                .map(|e| SparseSwitchEntry {
+                    key: e.key,
+                    target: e.target.into_owned(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes the directive with its keys in the radix chosen by `style`
+    pub fn write_with(&self, style: &FormatStyle) -> String {
+        let mut out = ".sparse-switch\n".to_string();
         for entry in &self.entries {
-            writeln!(f, "    {entry}")?;
+            out.push_str(&format!(
+                "    {} -> {}\n",
+                style.switch_keys.format(entry.key),
+                entry.target
+            ));
         }
-        // Print the footer.
-        write!(f, ".end sparse-switch")
+        out.push_str(".end sparse-switch");
+        out
+    }
+}
+
+impl fmt::Display for SparseSwitchDirective<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.write_with(&FormatStyle::default()))
     }
 }
 
@@ -300,25 +553,252 @@ impl fmt::Display for SparseSwitchDirective<'_> {
 pub enum Op<'a> {
     Label(Label<'a>),
     Line(u32),
+    /// `.prologue` debug marker: where the debugger considers the method's setup done
+    Prologue,
+    /// `.epilogue` debug marker: where the debugger considers the method's teardown to begin
+    Epilogue,
     Op(DexOp<'a>),
     Catch(CatchDirective<'a>),
     ArrayData(ArrayDataDirective),
     PackedSwitch(PackedSwitchDirective<'a>),
     SparseSwitch(SparseSwitchDirective<'a>),
+    /// A directive the crate doesn't model, kept as its whole line when parsing with
+    /// [`ParseOptions::lenient`](crate::ParseOptions::lenient)
+    UnknownDirective(Cow<'a, str>),
 }
 
-pub fn parse_op<'a>() -> impl ModalParser<&'a str, Op<'a>, InputError<&'a str>> {
+impl<'a> Op<'a> {
+    /// Copies any borrowed text, detaching the op from the parsed source
+    pub fn into_owned(self) -> Op<'static> {
+        match self {
+            Op::Label(l) => Op::Label(l.into_owned()),
+            Op::Line(l) => Op::Line(l),
+            Op::Prologue => Op::Prologue,
+            Op::Epilogue => Op::Epilogue,
+            Op::Op(op) => Op::Op(op.into_owned()),
+            Op::Catch(c) => Op::Catch(c.into_owned()),
+            Op::ArrayData(ad) => Op::ArrayData(ad),
+            Op::PackedSwitch(ps) => Op::PackedSwitch(ps.into_owned()),
+            Op::SparseSwitch(ss) => Op::SparseSwitch(ss.into_owned()),
+            Op::UnknownDirective(d) => Op::UnknownDirective(Cow::Owned(d.into_owned())),
+        }
+    }
+
+    /// Returns every label defined or referenced by this op: the label itself,
+    /// a branch target, the bounds and handler of a catch, or switch payload targets.
+    pub fn labels(&self) -> Vec<&Label<'a>> {
+        match self {
+            Op::Label(l) => vec![l],
+            Op::Op(op) => op.target_labels(),
+            Op::Catch(CatchDirective::Catch {
+                try_range, handler, ..
+            })
+            | Op::Catch(CatchDirective::CatchAll { try_range, handler }) => {
+                vec![&try_range.start, &try_range.end, handler]
+            }
+            Op::PackedSwitch(ps) => ps.targets.iter().collect(),
+            Op::SparseSwitch(ss) => ss.entries.iter().map(|e| &e.target).collect(),
+            Op::Line(_)
+            | Op::Prologue
+            | Op::Epilogue
+            | Op::ArrayData(_)
+            | Op::UnknownDirective(_) => vec![],
+        }
+    }
+
+    /// Size in 16-bit code units: that of the instruction or payload, and 0 for labels
+    /// and other directives. A payload may be preceded by a `nop` to align it, which
+    /// is not counted here.
+    pub fn code_units(&self) -> u32 {
+        match self {
+            Op::Op(op) => op.code_units(),
+            Op::ArrayData(ad) => 4 + (ad.width * ad.elements.len() as u32).div_ceil(2),
+            Op::PackedSwitch(ps) => 4 + 2 * ps.targets.len() as u32,
+            Op::SparseSwitch(ss) => 2 + 4 * ss.entries.len() as u32,
+            Op::Label(_)
+            | Op::Line(_)
+            | Op::Prologue
+            | Op::Epilogue
+            | Op::Catch(_)
+            | Op::UnknownDirective(_) => 0,
+        }
+    }
+
+    /// Returns every class type this op refers to, see [`DexOp::referenced_types`];
+    /// a `.catch` refers to its exception type.
+    pub fn referenced_types(&self) -> Vec<&ObjectIdentifier<'a>> {
+        match self {
+            Op::Op(op) => op.referenced_types(),
+            Op::Catch(CatchDirective::Catch { exception, .. }) => vec![exception],
+            _ => vec![],
+        }
+    }
+
+    /// Highest local `vN` register touched by this op, counting the upper half of wide
+    /// pairs. That can be one past `v65535`, so it is widened to a `u32`.
+    pub fn max_local_register(&self) -> Option<u32> {
+        let Op::Op(op) = self else {
+            return None;
+        };
+        let wide = op.wide_registers();
+        op.registers()
+            .into_iter()
+            .filter_map(|r| match r {
+                Register::Local(n) if wide.contains(&r) => Some(u32::from(n) + 1),
+                Register::Local(n) => Some(u32::from(n)),
+                Register::Parameter(_) => None,
+            })
+            .max()
+    }
+
+    /// Mutable access to the labels returned by [`Op::labels`].
+    pub fn labels_mut(&mut self) -> Vec<&mut Label<'a>> {
+        match self {
+            Op::Label(l) => vec![l],
+            Op::Op(op) => op.target_label_mut().into_iter().collect(),
+            Op::Catch(CatchDirective::Catch {
+                try_range, handler, ..
+            })
+            | Op::Catch(CatchDirective::CatchAll { try_range, handler }) => {
+                vec![&mut try_range.start, &mut try_range.end, handler]
+            }
+            Op::PackedSwitch(ps) => ps.targets.iter_mut().collect(),
+            Op::SparseSwitch(ss) => ss.entries.iter_mut().map(|e| &mut e.target).collect(),
+            Op::Line(_)
+            | Op::Prologue
+            | Op::Epilogue
+            | Op::ArrayData(_)
+            | Op::UnknownDirective(_) => vec![],
+        }
+    }
+}
+
+pub fn parse_op<'a>() -> impl ModalParser<&'a str, Op<'a>, ParseFailure> {
+    parse_op_with(ParseOptions::default())
+}
+
+/// Parses an op as `options` say, without its trailing comment
+pub fn parse_op_with<'a>(options: ParseOptions) -> impl ModalParser<&'a str, Op<'a>, ParseFailure> {
     alt((
         ws(parse_label().map(Op::Label)),
         preceded(ws(literal(".line")), ws(parse_int_lit::<u32>())).map(Op::Line),
-        ws(parse_dex_op).map(Op::Op),
-        parse_catch_directive().map(Op::Catch),
+        ws(literal(".prologue")).map(|_| Op::Prologue),
+        ws(literal(".epilogue")).map(|_| Op::Epilogue),
+        ws(parse_dex_op_with(options)).map(Op::Op),
+        parse_catch_directive_with(options).map(Op::Catch),
         parse_array_data_directive().map(Op::ArrayData),
         parse_packed_switch_directive().map(Op::PackedSwitch),
         parse_sparse_switch_directive().map(Op::SparseSwitch),
+        parse_unknown_directive(options).map(|d| Op::UnknownDirective(Cow::Borrowed(d))),
     ))
 }
 
+/// Every directive the parsers understand. Lenient parsing only keeps others
+/// verbatim, so a malformed `.locals` or a `.end method` is never swallowed.
+const KNOWN_DIRECTIVES: [&str; 21] = [
+    ".annotation",
+    ".array-data",
+    ".catch",
+    ".catchall",
+    ".class",
+    ".end",
+    ".enum",
+    ".epilogue",
+    ".field",
+    ".implements",
+    ".line",
+    ".locals",
+    ".method",
+    ".packed-switch",
+    ".param",
+    ".prologue",
+    ".registers",
+    ".source",
+    ".sparse-switch",
+    ".subannotation",
+    ".super",
+];
+
+/// The line of a directive not in [`KNOWN_DIRECTIVES`], e.g. `.foobar 1`, if parsing
+/// is [`ParseOptions::lenient`]. Fails otherwise.
+pub(crate) fn parse_unknown_directive<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, &'a str, ParseFailure> {
+    ws(move |input: &mut &'a str| {
+        if !options.lenient {
+            return Err(ErrMode::Backtrack(ParseFailure::default()));
+        }
+        let start = *input;
+        let name = (one_of('.'), take_while(1.., |c: char| !c.is_whitespace()))
+            .take()
+            .parse_next(input)?;
+        if KNOWN_DIRECTIVES.contains(&name) {
+            return Err(ErrMode::Backtrack(ParseFailure::default()));
+        }
+        let rest = take_while(0.., |c| c != '\n' && c != '\r').parse_next(input)?;
+        Ok(start[..name.len() + rest.len()].trim_end())
+    })
+}
+
+/// Parses a block of instructions, labels and directives, one per line, into ops,
+/// e.g. to assemble a method body: `parse_ops("const/4 v0, 0x0\nreturn v0")?`.
+/// The whole input has to parse.
+pub fn parse_ops(input: &str) -> Result<Vec<Op<'_>>, SmaliError> {
+    parse_complete(
+        input,
+        "ops",
+        terminated(repeat(0.., parse_op()), multispace0),
+    )
+}
+
+/// Like [`parse_op_with`], but with [`ParseOptions::keep_comments`] also returns the
+/// comment trailing an instruction on the same line, e.g. the `R.id.foo` of
+/// `const v0, 0x7f0a0001    # R.id.foo`.
+pub fn parse_op_with_comment<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, (Op<'a>, Option<&'a str>), ParseFailure> {
+    move |input: &mut &'a str| {
+        if !options.keep_comments {
+            return parse_op_with(options)
+                .map(|op| (op, None))
+                .parse_next(input);
+        }
+        multispace0.parse_next(input)?;
+        // the dex op parsers swallow trailing comments themselves, so look for one
+        // on the source line of the instruction instead
+        let line = input.split('\n').next().unwrap_or_default();
+        let start = input.checkpoint();
+        match parse_dex_op_with(options).parse_next(input) {
+            Ok(op) => {
+                (multispace0, opt((comment(), multispace0))).parse_next(input)?;
+                Ok((Op::Op(op), trailing_comment(line)))
+            }
+            Err(_) => {
+                input.reset(&start);
+                parse_op_with(options)
+                    .map(|op| (op, None))
+                    .parse_next(input)
+            }
+        }
+    }
+}
+
+/// Text of the `#` comment ending `line`, skipping `#` inside string literals
+fn trailing_comment(line: &str) -> Option<&str> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return Some(line[i + 1..].trim()),
+            _ => {}
+        }
+    }
+    None
+}
+
 mod tests {
 
     #[test]
@@ -330,7 +810,7 @@ mod tests {
                                 0x3f800000
                              .end array-data"#;
         let ad = parse_array_data_directive().parse_next(&mut input).unwrap();
-        println!("{ad:?}");
+        assert_eq!((ad.width, ad.elements.len()), (4, 2));
     }
 
     #[test]
@@ -339,10 +819,272 @@ mod tests {
         use winnow::Parser;
         let mut input = "\n\tinvoke-virtual {v0, p0}, Ljava/lang/StringBuilder;->append(Ljava/lang/Object;)Ljava/lang/StringBuilder;\n\t#1";
         let a = parse_op().parse_next(&mut input).unwrap();
-        println!("{a:?}");
+        assert!(matches!(a, Op::Op(DexOp::Invoke { .. })), "{a:?}");
 
         let mut input = "\n    :goto_0\n\n";
         let a = parse_op().parse_next(&mut input).unwrap();
-        println!("{a:?}");
+        assert_eq!(a, Op::Label(Label::new("goto_0")));
+    }
+
+    #[test]
+    fn test_line() {
+        use super::*;
+        use winnow::Parser;
+        let mut input = "    .line 0x10\n";
+        assert!(matches!(
+            parse_op().parse_next(&mut input),
+            Ok(Op::Line(16))
+        ));
+
+        let mut input = "    .line 4294967295\n";
+        assert!(matches!(
+            parse_op().parse_next(&mut input),
+            Ok(Op::Line(u32::MAX))
+        ));
+
+        // out of range values are rejected instead of wrapping
+        let mut input = "    .line 4294967296\n";
+        assert!(parse_op().parse_next(&mut input).is_err());
+        let mut input = "    .line 0x100000000\n";
+        assert!(parse_op().parse_next(&mut input).is_err());
+    }
+
+    #[test]
+    fn test_array_data_bytes() {
+        use super::*;
+        use winnow::Parser;
+        let mut input = r#".array-data 1
+                                0x1t
+                                -0x1t
+                                0x7f
+                             .end array-data"#;
+        let ad = parse_array_data_directive().parse_next(&mut input).unwrap();
+        assert_eq!(ad.as_bytes(), Some(vec![0x01, 0xff, 0x7f]));
+        assert_eq!(ad.as_i32_slice(), None);
+
+        // mixed element kinds are all laid out at the header width
+        let ad = ArrayDataDirective {
+            width: 4,
+            elements: vec![
+                ArrayDataElement::Int(1),
+                ArrayDataElement::Byte(-2),
+                ArrayDataElement::Float(1.0),
+            ],
+        };
+        assert_eq!(
+            ad.to_bytes(),
+            vec![1, 0, 0, 0, 0xfe, 0xff, 0xff, 0xff, 0, 0, 0x80, 0x3f]
+        );
+        assert_eq!(ad.as_i32_slice(), Some(vec![1, -2, 0x3f800000]));
+
+        let ad = ArrayDataDirective {
+            width: 8,
+            elements: vec![ArrayDataElement::Double(1.0)],
+        };
+        assert_eq!(ad.to_bytes(), 1.0f64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_array_data_round_trip() {
+        use super::*;
+        use winnow::Parser;
+        for input in [
+            ".array-data 1\n    0x1t\n    -0x1t\n    0x7ft\n    -0x80t\n.end array-data",
+            ".array-data 2\n    0x100s\n    -0x8000s\n.end array-data",
+            ".array-data 4\n    0x0\n    -0x1\n    0x7fffffff\n.end array-data",
+            ".array-data 8\n    0x100000000L\n    -0x8000000000000000L\n.end array-data",
+        ] {
+            let ad = parse_array_data_directive().parse(input).unwrap();
+            assert_eq!(ad.to_string(), input);
+        }
+
+        // postfix-free elements are typed by the width and written canonically
+        let ad = parse_array_data_directive()
+            .parse(".array-data 1\n 0x1\n 0xff\n.end array-data")
+            .unwrap();
+        assert_eq!(
+            ad.elements,
+            vec![ArrayDataElement::Byte(1), ArrayDataElement::Byte(-1)]
+        );
+        assert_eq!(
+            ad.to_string(),
+            ".array-data 1\n    0x1t\n    -0x1t\n.end array-data"
+        );
+    }
+
+    #[test]
+    fn test_float_array_data() {
+        use super::*;
+        use winnow::Parser;
+        let input = ".array-data 4\n    1.0f\n    2.5f\n    -0.001f\n    1e20f\n    NaNf\n    -Infinityf\n.end array-data";
+        let ad = parse_array_data_directive().parse(input).unwrap();
+        assert_eq!(ad.elements[0], ArrayDataElement::Float(1.0));
+        assert_eq!(ad.elements[1], ArrayDataElement::Float(2.5));
+        assert_eq!(ad.elements[5], ArrayDataElement::Float(f32::NEG_INFINITY));
+        assert_eq!(ad.to_string(), input);
+
+        let input = ".array-data 8\n    1.0d\n    -2.5E-10d\n.end array-data";
+        let ad = parse_array_data_directive().parse(input).unwrap();
+        assert_eq!(
+            ad.elements,
+            vec![
+                ArrayDataElement::Double(1.0),
+                ArrayDataElement::Double(-2.5e-10)
+            ]
+        );
+        let out = ad.to_string();
+        let again = parse_array_data_directive().parse(&out).unwrap();
+        assert_eq!(again, ad);
+
+        // decimal integers with a postfix are values, not bit patterns
+        let input = ".array-data 4\n    1f\n    -3F\n.end array-data";
+        let ad = parse_array_data_directive().parse(input).unwrap();
+        assert_eq!(
+            ad.elements,
+            vec![ArrayDataElement::Float(1.0), ArrayDataElement::Float(-3.0)]
+        );
+        let input = ".array-data 8\n    2d\n.end array-data";
+        let ad = parse_array_data_directive().parse(input).unwrap();
+        assert_eq!(ad.elements, vec![ArrayDataElement::Double(2.0)]);
+    }
+
+    #[test]
+    fn test_packed_switch_keys() {
+        use super::*;
+        use winnow::Parser;
+        let ps = parse_packed_switch_directive()
+            .parse(".packed-switch -0x1\n    :pswitch_0\n    :pswitch_1\n    :pswitch_2\n.end packed-switch")
+            .unwrap();
+        let keys: Vec<_> = ps.keys().map(|(k, l)| (k, l.0.as_ref())).collect();
+        assert_eq!(
+            keys,
+            vec![(-1, "pswitch_0"), (0, "pswitch_1"), (1, "pswitch_2")]
+        );
+    }
+
+    #[test]
+    fn test_array_data_validate() {
+        use super::*;
+        use winnow::Parser;
+        let parse = |input| parse_array_data_directive().parse(input).unwrap();
+        assert!(
+            parse(".array-data 2\n    0x1s\n    -0x2s\n.end array-data")
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            parse(".array-data 8\n    1.5d\n    0x3\n.end array-data")
+                .validate()
+                .is_ok()
+        );
+        assert_eq!(
+            parse(".array-data 1\n    0x1t\n    0x2l\n.end array-data")
+                .validate()
+                .unwrap_err()
+                .details,
+            "array-data element 1 (0x2L) is 8 bytes wide, expected 1"
+        );
+        assert_eq!(
+            parse(".array-data 4\n    1.5d\n.end array-data")
+                .validate()
+                .unwrap_err()
+                .details,
+            "array-data element 0 (1.5d) is 8 bytes wide, expected 4"
+        );
+        assert_eq!(
+            parse(".array-data 3\n.end array-data")
+                .validate()
+                .unwrap_err()
+                .details,
+            "invalid array-data width 3"
+        );
+    }
+
+    #[test]
+    fn test_array_data_out_of_range() {
+        use super::*;
+        use winnow::Parser;
+        let ad = parse_array_data_directive()
+            .parse(".array-data 1\n    0xfft\n    -0x80t\n.end array-data")
+            .unwrap();
+        assert_eq!(
+            ad.elements,
+            [ArrayDataElement::Byte(-1), ArrayDataElement::Byte(-128)]
+        );
+        for input in [
+            ".array-data 1\n    0x100t\n.end array-data",
+            ".array-data 2\n    -0x8001s\n.end array-data",
+            ".array-data 4\n    0x100000000\n.end array-data",
+        ] {
+            assert!(
+                parse_array_data_directive().parse(input).is_err(),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_ops() {
+        use super::*;
+        let ops = parse_ops(
+            "
+            const/4 v0, 0x0
+            :try_start_0
+            invoke-static {}, Lcom/example/Trace;->begin()V
+            :try_end_0
+            .catchall {:try_start_0 .. :try_end_0} :catchall_0
+            return v0    # the result
+            :catchall_0
+            return v0
+            ",
+        )
+        .unwrap();
+        assert_eq!(ops.len(), 8);
+        assert!(matches!(&ops[0], Op::Op(op) if op.to_string() == "const/4 v0, 0"));
+        assert_eq!(ops[1], Op::Label(Label(Cow::Borrowed("try_start_0"))));
+        assert!(matches!(ops[4], Op::Catch(CatchDirective::CatchAll { .. })));
+
+        assert_eq!(parse_ops("").unwrap(), vec![]);
+        assert_eq!(
+            parse_ops("const/4 v0, 0x0\nbogus v0\n")
+                .unwrap_err()
+                .details,
+            "failed to parse ops at line 2 (byte 16)"
+        );
+        let e = parse_ops("const/4 v0, 0x0\nconst v1, 0x100000000L\n").unwrap_err();
+        // without `std` the reason is dropped and only the position reported
+        assert_eq!(
+            e.details,
+            "0x100000000L is too large for a 32-bit const, use const-wide at line 2 (byte 26)"
+        );
+        assert!(parse_ops("const v1, 0x7fffffff\nconst v1, -0x80000000").is_ok());
+        assert!(parse_ops("const-wide v1, 0x100000000L").is_ok());
+    }
+
+    #[test]
+    fn test_code_units() {
+        use super::*;
+        let ops = parse_ops(
+            "
+            const-wide v0, 0x1L
+            invoke-polymorphic {p0, v0}, Ljava/lang/invoke/MethodHandle;->invoke([Ljava/lang/Object;)Ljava/lang/Object;, (J)V
+            :array_0
+            .array-data 2
+                0x1s
+                0x2s
+                0x3s
+            .end array-data
+            .packed-switch 0x0
+                :a
+                :b
+            .end packed-switch
+            .sparse-switch
+                0x5 -> :a
+            .end sparse-switch
+            ",
+        )
+        .unwrap();
+        let units: Vec<_> = ops.iter().map(Op::code_units).collect();
+        assert_eq!(units, [5, 4, 0, 7, 8, 6]);
     }
 }
@@ -0,0 +1,381 @@
+//! Instructions that only appear in optimized (odex) dex files from pre-ART devices.
+//! Enabled with the `odex` feature.
+
+use core::{fmt, str::FromStr};
+
+use winnow::{
+    ModalParser, ModalResult, Parser,
+    ascii::space1,
+    combinator::{alt, preceded},
+    token::{literal, take_while},
+};
+
+use crate::{
+    ParseFailure, ParseOptions, SmaliError,
+    field_ref::{FieldRef, parse_field_ref_with},
+    method_ref::{MethodRef, parse_method_ref_with},
+    object_identifier::ObjectIdentifier,
+    op::dex_op::{Register, RegisterRange, parse_register_list, parse_register_range},
+    parse_int_lit,
+    prelude::*,
+    signature::type_signature::{TypeSignature, parse_typesignature_with},
+    ws,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerificationErrorType {
+    Generic,
+    NoSuchClass,
+    NoSuchField,
+    NoSuchMethod,
+    IllegalClassAccess,
+    IllegalFieldAccess,
+    IllegalMethodAccess,
+    ClassChange,
+    Instantiation,
+}
+
+impl FromStr for VerificationErrorType {
+    type Err = SmaliError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "generic-error" => Ok(VerificationErrorType::Generic),
+            "no-such-class" => Ok(VerificationErrorType::NoSuchClass),
+            "no-such-field" => Ok(VerificationErrorType::NoSuchField),
+            "no-such-method" => Ok(VerificationErrorType::NoSuchMethod),
+            "illegal-class-access" => Ok(VerificationErrorType::IllegalClassAccess),
+            "illegal-field-access" => Ok(VerificationErrorType::IllegalFieldAccess),
+            "illegal-method-access" => Ok(VerificationErrorType::IllegalMethodAccess),
+            "class-change-error" => Ok(VerificationErrorType::ClassChange),
+            "instantiation-error" => Ok(VerificationErrorType::Instantiation),
+            _ => Err(SmaliError {
+                details: format!("Unknown verification error type {s}"),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for VerificationErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationErrorType::Generic => write!(f, "generic-error"),
+            VerificationErrorType::NoSuchClass => write!(f, "no-such-class"),
+            VerificationErrorType::NoSuchField => write!(f, "no-such-field"),
+            VerificationErrorType::NoSuchMethod => write!(f, "no-such-method"),
+            VerificationErrorType::IllegalClassAccess => write!(f, "illegal-class-access"),
+            VerificationErrorType::IllegalFieldAccess => write!(f, "illegal-field-access"),
+            VerificationErrorType::IllegalMethodAccess => write!(f, "illegal-method-access"),
+            VerificationErrorType::ClassChange => write!(f, "class-change-error"),
+            VerificationErrorType::Instantiation => write!(f, "instantiation-error"),
+        }
+    }
+}
+
+/// The class, field or method a `throw-verification-error` failed to resolve
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationRef<'a> {
+    Type(TypeSignature<'a>),
+    Field(FieldRef<'a>),
+    Method(MethodRef<'a>),
+}
+
+impl fmt::Display for VerificationRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationRef::Type(t) => write!(f, "{}", t.to_jni()),
+            VerificationRef::Field(r) => write!(f, "{r}"),
+            VerificationRef::Method(r) => write!(f, "{r}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OdexOp<'a> {
+    /// `execute-inline {v0, v1}, inline@0x4`, a call to a VM-inlined method by index
+    ExecuteInline {
+        registers: Vec<Register>,
+        inline_index: u16,
+    },
+    ExecuteInlineRange {
+        range: RegisterRange,
+        inline_index: u16,
+    },
+    /// Replaces an `invoke-direct` of an empty `Object.<init>`
+    InvokeObjectInitRange {
+        range: RegisterRange,
+        method: Box<MethodRef<'a>>,
+    },
+    /// Thrown in place of an instruction that failed verification at optimization time
+    ThrowVerificationError {
+        error_type: VerificationErrorType,
+        reference: VerificationRef<'a>,
+    },
+}
+
+impl fmt::Display for OdexOp<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OdexOp::ExecuteInline {
+                registers,
+                inline_index,
+            } => {
+                let regs: Vec<String> = registers.iter().map(|r| r.to_string()).collect();
+                write!(
+                    f,
+                    "execute-inline {{{}}}, inline@0x{inline_index:x}",
+                    regs.join(", ")
+                )
+            }
+            OdexOp::ExecuteInlineRange {
+                range,
+                inline_index,
+            } => write!(f, "execute-inline/range {range}, inline@0x{inline_index:x}"),
+            OdexOp::InvokeObjectInitRange { range, method } => {
+                write!(f, "invoke-object-init/range {range}, {method}")
+            }
+            OdexOp::ThrowVerificationError {
+                error_type,
+                reference,
+            } => write!(f, "throw-verification-error {error_type}, {reference}"),
+        }
+    }
+}
+
+impl<'a> OdexOp<'a> {
+    /// Copies any borrowed text, detaching the operation from the parsed source
+    pub fn into_owned(self) -> OdexOp<'static> {
+        match self {
+            OdexOp::ExecuteInline {
+                registers,
+                inline_index,
+            } => OdexOp::ExecuteInline {
+                registers,
+                inline_index,
+            },
+            OdexOp::ExecuteInlineRange {
+                range,
+                inline_index,
+            } => OdexOp::ExecuteInlineRange {
+                range,
+                inline_index,
+            },
+            OdexOp::InvokeObjectInitRange { range, method } => OdexOp::InvokeObjectInitRange {
+                range,
+                method: Box::new(method.into_owned()),
+            },
+            OdexOp::ThrowVerificationError {
+                error_type,
+                reference,
+            } => OdexOp::ThrowVerificationError {
+                error_type,
+                reference: match reference {
+                    VerificationRef::Type(t) => VerificationRef::Type(t.into_owned()),
+                    VerificationRef::Field(r) => VerificationRef::Field(r.into_owned()),
+                    VerificationRef::Method(r) => VerificationRef::Method(r.into_owned()),
+                },
+            },
+        }
+    }
+
+    /// The opcode byte odex files use for the instruction
+    pub fn opcode(&self) -> u8 {
+        match self {
+            OdexOp::ThrowVerificationError { .. } => 0xed,
+            OdexOp::ExecuteInline { .. } => 0xee,
+            OdexOp::ExecuteInlineRange { .. } => 0xef,
+            OdexOp::InvokeObjectInitRange { .. } => 0xf0,
+        }
+    }
+
+    /// See [`DexOp::code_units`](crate::op::dex_op::DexOp::code_units)
+    pub fn code_units(&self) -> u32 {
+        match self {
+            OdexOp::ExecuteInline { .. }
+            | OdexOp::ExecuteInlineRange { .. }
+            | OdexOp::InvokeObjectInitRange { .. } => 3,
+            OdexOp::ThrowVerificationError { .. } => 2,
+        }
+    }
+
+    /// See [`DexOp::referenced_types`](crate::op::dex_op::DexOp::referenced_types)
+    pub fn referenced_types(&self) -> Vec<&ObjectIdentifier<'a>> {
+        fn method_types<'b, 'a>(method: &'b MethodRef<'a>) -> Vec<&'b ObjectIdentifier<'a>> {
+            let mut types = method.class.object_types();
+            types.extend(method.param.ms.object_types());
+            types
+        }
+        match self {
+            OdexOp::InvokeObjectInitRange { method, .. } => method_types(method),
+            OdexOp::ThrowVerificationError {
+                reference: VerificationRef::Method(method),
+                ..
+            } => method_types(method),
+            OdexOp::ThrowVerificationError {
+                reference: VerificationRef::Field(field),
+                ..
+            } => {
+                let mut types = vec![&field.class];
+                types.extend(field.param.ts.object_types());
+                types
+            }
+            OdexOp::ThrowVerificationError {
+                reference: VerificationRef::Type(t),
+                ..
+            } => t.object_types(),
+            OdexOp::ExecuteInline { .. } | OdexOp::ExecuteInlineRange { .. } => vec![],
+        }
+    }
+
+    /// See [`DexOp::registers`](crate::op::dex_op::DexOp::registers)
+    pub fn registers(&self) -> Vec<Register> {
+        match self {
+            OdexOp::ExecuteInline { registers, .. } => registers.clone(),
+            OdexOp::ExecuteInlineRange { range, .. }
+            | OdexOp::InvokeObjectInitRange { range, .. } => vec![range.start, range.end],
+            OdexOp::ThrowVerificationError { .. } => vec![],
+        }
+    }
+
+    /// See [`DexOp::registers_mut`](crate::op::dex_op::DexOp::registers_mut)
+    pub fn registers_mut(&mut self) -> Vec<&mut Register> {
+        match self {
+            OdexOp::ExecuteInline { registers, .. } => registers.iter_mut().collect(),
+            OdexOp::ExecuteInlineRange { range, .. }
+            | OdexOp::InvokeObjectInitRange { range, .. } => {
+                vec![&mut range.start, &mut range.end]
+            }
+            OdexOp::ThrowVerificationError { .. } => vec![],
+        }
+    }
+}
+
+fn parse_inline_index<'a>() -> impl ModalParser<&'a str, u16, ParseFailure> {
+    preceded(ws(literal("inline@")), parse_int_lit::<u16>())
+}
+
+/// Parses the operands of the odex instruction `op`, or returns `None` if `op` isn't one
+pub(crate) fn parse_odex_op<'a>(
+    op: &str,
+    options: ParseOptions,
+    input: &mut &'a str,
+) -> ModalResult<Option<OdexOp<'a>>, ParseFailure> {
+    let odex_op = match op {
+        "execute-inline" => preceded(
+            space1,
+            (
+                parse_register_list(),
+                ws(literal(",")),
+                parse_inline_index(),
+            ),
+        )
+        .map(|(registers, _, inline_index)| OdexOp::ExecuteInline {
+            registers,
+            inline_index,
+        })
+        .parse_next(input)?,
+        "execute-inline/range" => preceded(
+            space1,
+            (
+                parse_register_range(),
+                ws(literal(",")),
+                parse_inline_index(),
+            ),
+        )
+        .map(|(range, _, inline_index)| OdexOp::ExecuteInlineRange {
+            range,
+            inline_index,
+        })
+        .parse_next(input)?,
+        "invoke-object-init/range" => preceded(
+            space1,
+            (
+                parse_register_range(),
+                ws(literal(",")),
+                parse_method_ref_with(options),
+            ),
+        )
+        .map(|(range, _, method)| OdexOp::InvokeObjectInitRange {
+            range,
+            method: Box::new(method),
+        })
+        .parse_next(input)?,
+        "throw-verification-error" => preceded(
+            space1,
+            (
+                take_while(1.., |c: char| c.is_ascii_lowercase() || c == '-')
+                    .try_map(VerificationErrorType::from_str),
+                ws(literal(",")),
+                alt((
+                    parse_method_ref_with(options).map(VerificationRef::Method),
+                    parse_field_ref_with(options).map(VerificationRef::Field),
+                    parse_typesignature_with(options).map(VerificationRef::Type),
+                )),
+            ),
+        )
+        .map(
+            |(error_type, _, reference)| OdexOp::ThrowVerificationError {
+                error_type,
+                reference,
+            },
+        )
+        .parse_next(input)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(odex_op))
+}
+
+#[cfg(test)]
+mod tests {
+    use winnow::Parser;
+
+    use super::*;
+    use crate::op::dex_op::{DexOp, parse_dex_op};
+
+    #[test]
+    fn test_odex_ops() {
+        for input in [
+            "execute-inline {v0, v1}, inline@0x4",
+            "execute-inline {}, inline@0x0",
+            "execute-inline/range {v0 .. v2}, inline@0x10",
+            "invoke-object-init/range {p0 .. p0}, Ljava/lang/Object;-><init>()V",
+            "throw-verification-error generic-error, La/b;",
+            "throw-verification-error no-such-field, La/b;->c:I",
+            "throw-verification-error illegal-method-access, La/b;->c(I)V",
+        ] {
+            let op = parse_dex_op.parse(input).unwrap();
+            assert!(matches!(op, DexOp::Odex(_)), "{op:?}");
+            assert_eq!(parse_dex_op.parse(&op.to_string()), Ok(op), "{input}");
+        }
+
+        let op = parse_dex_op
+            .parse("execute-inline {v0, v1}, inline@0x4")
+            .unwrap();
+        assert_eq!(op.to_string(), "execute-inline {v0, v1}, inline@0x4");
+        assert_eq!(op.registers(), [Register::Local(0), Register::Local(1)]);
+
+        let DexOp::Odex(OdexOp::ThrowVerificationError {
+            error_type,
+            reference: VerificationRef::Field(field),
+        }) = parse_dex_op
+            .parse("throw-verification-error no-such-field, La/b;->c:I")
+            .unwrap()
+        else {
+            panic!();
+        };
+        assert_eq!(error_type, VerificationErrorType::NoSuchField);
+        assert_eq!(field.param.ident, "c");
+
+        assert!(
+            parse_dex_op
+                .parse("throw-verification-error bad-error, La/b;")
+                .is_err()
+        );
+        assert_eq!(
+            "bad-error"
+                .parse::<VerificationErrorType>()
+                .unwrap_err()
+                .details,
+            "Unknown verification error type bad-error"
+        );
+    }
+}
@@ -1,16 +1,18 @@
-use std::borrow::Cow;
+use alloc::borrow::Cow;
 
 use winnow::{
     ModalParser, Parser,
     combinator::{opt, preceded, repeat, terminated},
-    error::InputError,
     token::{literal, one_of},
 };
 
 use crate::{
-    annotation::{Annotation, parse_annotation, write_annotation},
+    ParseFailure, ParseOptions,
+    annotation::{Annotation, parse_annotation_with, write_annotation},
     op::dex_op::{Register, parse_register},
-    parse_string_lit, ws,
+    parse_string_lit,
+    prelude::*,
+    ws,
 };
 
 /// Struct representing a method parameter
@@ -24,14 +26,36 @@ pub struct Param<'a> {
     pub annotations: Vec<Annotation<'a>>,
 }
 
-pub fn parse_param<'a>() -> impl ModalParser<&'a str, Param<'a>, InputError<&'a str>> {
+impl Param<'_> {
+    /// Copies any borrowed text, detaching the directive from the parsed source
+    pub fn into_owned(self) -> Param<'static> {
+        Param {
+            register: self.register,
+            name: self.name.map(|n| Cow::Owned(n.into_owned())),
+            annotations: self
+                .annotations
+                .into_iter()
+                .map(Annotation::into_owned)
+                .collect(),
+        }
+    }
+}
+
+pub fn parse_param<'a>() -> impl ModalParser<&'a str, Param<'a>, ParseFailure> {
+    parse_param_with(ParseOptions::default())
+}
+
+/// Parses a `.param` directive with the nesting limit of `options`
+pub fn parse_param_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, Param<'a>, ParseFailure> {
     preceded(
         ws(literal(".param")),
         (
             parse_register(),
             opt(preceded(ws(one_of(',')), ws(parse_string_lit()))),
             opt(terminated(
-                repeat(0.., parse_annotation()),
+                repeat(0.., parse_annotation_with(options)),
                 ws(literal(".end param")),
             )),
         ),
@@ -1,16 +1,21 @@
-use std::borrow::Cow;
+use alloc::borrow::Cow;
 
 use serde::{Deserialize, Serialize};
 use winnow::{
     ModalParser, Parser,
-    combinator::{delimited, opt, preceded, repeat},
-    error::InputError,
-    token::{one_of, take_until},
+    combinator::{alt, delimited, opt, preceded, repeat},
+    token::{one_of, take_till},
 };
 
-use crate::signature::{
-    parse_type_parameters,
-    type_signature::{TypeSignature, parse_typesignature},
+use crate::{
+    ParseFailure, ParseOptions, SmaliError,
+    object_identifier::ObjectIdentifier,
+    parse_complete,
+    prelude::*,
+    signature::{
+        parse_type_parameters_with,
+        type_signature::{TypeSignature, parse_typesignature_with},
+    },
 };
 
 /// Represents a Java method signature consisting of arguments and a return type
@@ -31,13 +36,85 @@ pub struct MethodSignature<'a> {
     pub throws: Option<TypeSignature<'a>>,
 }
 
-impl MethodSignature<'_> {
-    pub fn from_jni(mut s: &str) -> MethodSignature {
+impl<'a> MethodSignature<'a> {
+    /// Copies any borrowed text, detaching the signature from the parsed source
+    pub fn into_owned(self) -> MethodSignature<'static> {
+        MethodSignature {
+            type_parameters: self
+                .type_parameters
+                .map(|v| v.into_iter().map(TypeSignature::into_owned).collect()),
+            args: self
+                .args
+                .into_iter()
+                .map(TypeSignature::into_owned)
+                .collect(),
+            result: self.result.into_owned(),
+            throws: self.throws.map(TypeSignature::into_owned),
+        }
+    }
+
+    /// Parses the method signature `s` starts with.
+    ///
+    /// # Panics
+    ///
+    /// If `s` doesn't start with a method signature. Use
+    /// [`try_from_jni`](Self::try_from_jni) for input that isn't known to be valid.
+    pub fn from_jni(mut s: &str) -> MethodSignature<'_> {
         parse_methodsignature()
             .parse_next(&mut s)
             .expect("Can't parse MethodSignature")
     }
 
+    /// Parses a method signature such as `(ILjava/lang/String;)V`, failing unless all
+    /// of `s` is one
+    pub fn try_from_jni(s: &str) -> Result<MethodSignature<'_>, SmaliError> {
+        parse_complete(s, "method signature", parse_methodsignature())
+    }
+
+    /// The dex method descriptor: the erased argument and return types only, without
+    /// type parameters or `^throws`. `<T:Ljava/lang/Object;>(TT;)TT;` becomes
+    /// `(Ljava/lang/Object;)Ljava/lang/Object;`. Unlike [`TypeSignature::descriptor`],
+    /// which keeps a type's generics, nothing generic is left.
+    pub fn descriptor(&self) -> String {
+        let type_parameters = self.type_parameters.as_deref().unwrap_or_default();
+        let mut s = "(".to_string();
+        for t in &self.args {
+            s.push_str(&t.erased_with(type_parameters).to_jni());
+        }
+        s.push(')');
+        s.push_str(&self.result.erased_with(type_parameters).to_jni());
+        s
+    }
+
+    /// Number of arguments, not counting `this`
+    pub fn arity(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Number of registers an invoke has to pass: wide arguments take two, and
+    /// instance methods take one more for `this`. `(JI)V` needs 3, or 4 if not static.
+    pub fn register_arity(&self, is_static: bool) -> usize {
+        let args: usize = self
+            .args
+            .iter()
+            .map(|a| if a.is_wide() { 2 } else { 1 })
+            .sum();
+        if is_static { args } else { args + 1 }
+    }
+
+    /// Every class type the signature mentions: type parameter bounds, arguments,
+    /// the return type and the thrown type
+    pub fn object_types(&self) -> Vec<&ObjectIdentifier<'a>> {
+        self.type_parameters
+            .iter()
+            .flatten()
+            .chain(&self.args)
+            .chain([&self.result])
+            .chain(&self.throws)
+            .flat_map(TypeSignature::object_types)
+            .collect()
+    }
+
     pub fn to_jni(&self) -> String {
         let mut s = String::new();
         if let Some(v) = &self.type_parameters {
@@ -68,25 +145,59 @@ pub struct MethodParameter<'a> {
     pub ms: MethodSignature<'a>,
 }
 
-pub fn parse_method_parameter<'a>()
--> impl ModalParser<&'a str, MethodParameter<'a>, InputError<&'a str>> {
-    (take_until(0.., "("), parse_methodsignature()).map(|(ident, ms)| MethodParameter {
-        ident: ident.into(),
-        ms,
-    })
+impl MethodParameter<'_> {
+    /// Copies any borrowed text, detaching the parameter from the parsed source
+    pub fn into_owned(self) -> MethodParameter<'static> {
+        MethodParameter {
+            ident: Cow::Owned(self.ident.into_owned()),
+            ms: self.ms.into_owned(),
+        }
+    }
+}
+
+pub fn parse_method_parameter<'a>() -> impl ModalParser<&'a str, MethodParameter<'a>, ParseFailure>
+{
+    parse_method_parameter_with(ParseOptions::default())
+}
+
+/// Parses a method name and signature with the nesting limit of `options`
+pub fn parse_method_parameter_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, MethodParameter<'a>, ParseFailure> {
+    (
+        // A `<` past the constructor names opens the type parameters, e.g. `foo<T:...>(TT;)V`
+        alt(("<init>", "<clinit>", take_till(0.., ['(', '<']))),
+        parse_methodsignature_with(options),
+    )
+        .map(|(ident, ms)| MethodParameter {
+            ident: ident.into(),
+            ms,
+        })
+}
+
+fn parse_arguments<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, Vec<TypeSignature<'a>>, ParseFailure> {
+    delimited(
+        one_of('('),
+        repeat(0.., parse_typesignature_with(options)),
+        one_of(')'),
+    )
 }
 
-fn parse_arguments<'a>() -> impl ModalParser<&'a str, Vec<TypeSignature<'a>>, InputError<&'a str>> {
-    delimited(one_of('('), repeat(0.., parse_typesignature()), one_of(')'))
+pub fn parse_methodsignature<'a>() -> impl ModalParser<&'a str, MethodSignature<'a>, ParseFailure> {
+    parse_methodsignature_with(ParseOptions::default())
 }
 
-pub fn parse_methodsignature<'a>()
--> impl ModalParser<&'a str, MethodSignature<'a>, InputError<&'a str>> {
+/// Parses a method signature with the nesting limit of `options`
+pub fn parse_methodsignature_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, MethodSignature<'a>, ParseFailure> {
     (
-        opt(parse_type_parameters()),
-        parse_arguments(),
-        parse_typesignature(),
-        opt(preceded(one_of('^'), parse_typesignature())),
+        opt(parse_type_parameters_with(options)),
+        parse_arguments(options),
+        parse_typesignature_with(options),
+        opt(preceded(one_of('^'), parse_typesignature_with(options))),
     )
         .map(|(type_parameters, args, result, throws)| MethodSignature {
             type_parameters,
@@ -102,19 +213,32 @@ mod tests {
 
     use crate::signature::method_signature::{MethodSignature, parse_method_parameter};
 
+    #[test]
+    fn test_try_from_jni() {
+        let ms = "<T:Ljava/lang/Object;>(ILjava/util/List<TT;>;)V";
+        let m = MethodSignature::try_from_jni(ms).unwrap();
+        assert_eq!(m, MethodSignature::from_jni(ms));
+        assert_eq!(
+            MethodSignature::try_from_jni("V").unwrap_err().details,
+            "failed to parse method signature at line 1 (byte 0)"
+        );
+        assert!(MethodSignature::try_from_jni("(I").is_err());
+        assert!(MethodSignature::try_from_jni("()VV").is_err());
+    }
+
     #[test]
     fn test_methodsignature() {
         let t = parse_method_parameter()
             .parse_next(&mut "<init>()V")
             .unwrap();
-        println!("{t:?}");
+        assert_eq!(t.ident, "<init>");
+        assert_eq!(t.ms.to_jni(), "()V");
     }
 
     #[test]
     fn test_method_signature1() {
         let ts = "(TTSource;TTAccumulate;Lcom/strobel/core/Accumulator<TTSource;TTAccumulate;>;Lcom/strobel/core/Selector<TTAccumulate;TTResult;>;)TTResult;";
         let m = MethodSignature::from_jni(ts);
-        println!("{m:?}");
         assert_eq!(m.to_jni(), ts);
     }
 
@@ -122,7 +246,6 @@ mod tests {
     fn test_method_signature2() {
         let ts = "<R2:Ljava/lang/Object;>(Lcom/strobel/core/Selector<-TR;+TR2;>;)Ljava/lang/Iterable<TR2;>;^Ljava/lang/Exception;";
         let m = MethodSignature::from_jni(ts);
-        println!("{m:?}");
         assert_eq!(m.to_jni(), ts);
     }
 
@@ -130,7 +253,6 @@ mod tests {
     fn test_method_signature3() {
         let ts = "<U:TT;>(TU;)I";
         let m = MethodSignature::from_jni(ts);
-        println!("{m:?}");
         assert_eq!(m.to_jni(), ts);
     }
 
@@ -138,7 +260,6 @@ mod tests {
     fn test_method_signature4() {
         let ts = "<R2:Ljava/lang/Object;>(Lcom/strobel/core/Selector<-TR;+TR2;>;)Ljava/lang/Iterable<TR2;>;";
         let m = MethodSignature::from_jni(ts);
-        println!("{m:?}");
         assert_eq!(m.to_jni(), ts);
     }
 
@@ -146,7 +267,57 @@ mod tests {
     fn test_method_signature5() {
         let ts = "<T:Landroidx/lifecycle/ViewModel;>(Ljava/lang/Class<TT;>;)TT;";
         let m = MethodSignature::from_jni(ts);
-        println!("{m:?}");
         assert_eq!(m.to_jni(), ts);
     }
+
+    #[test]
+    fn test_arity() {
+        for (ts, arity, static_regs) in [
+            ("(JI)V", 2, 3),
+            ("()V", 0, 0),
+            ("(DLjava/lang/String;[J)J", 3, 4),
+            (
+                "<T:Ljava/lang/Object;>(TT;Z)TT;^Ljava/io/IOException;",
+                2,
+                2,
+            ),
+        ] {
+            let m = MethodSignature::from_jni(ts);
+            assert_eq!(m.arity(), arity, "{ts}");
+            assert_eq!(m.register_arity(true), static_regs, "{ts}");
+            assert_eq!(m.register_arity(false), static_regs + 1, "{ts}");
+        }
+    }
+
+    #[test]
+    fn test_descriptor() {
+        for (ts, descriptor) in [
+            (
+                "<T:Ljava/lang/Object;>(TT;)TT;",
+                "(Ljava/lang/Object;)Ljava/lang/Object;",
+            ),
+            (
+                "<T:Landroidx/lifecycle/ViewModel;>(Ljava/lang/Class<TT;>;)TT;",
+                "(Ljava/lang/Class;)Landroidx/lifecycle/ViewModel;",
+            ),
+            (
+                "<T::Ljava/lang/Comparable<-TT;>;>([TT;)TT;",
+                "([Ljava/lang/Comparable;)Ljava/lang/Comparable;",
+            ),
+            (
+                "<T:Ljava/lang/Number;:Ljava/lang/Runnable;U:TT;>(TU;J)V^Ljava/io/IOException;",
+                "(Ljava/lang/Number;J)V",
+            ),
+            // type variables of the enclosing class aren't known here
+            (
+                "(TK;Ljava/util/Map$Entry<TK;TV;>;Lcom/example/Outer<TK;>.Inner;)I",
+                "(Ljava/lang/Object;Ljava/util/Map$Entry;Lcom/example/Outer$Inner;)I",
+            ),
+            ("([I)V", "([I)V"),
+        ] {
+            let m = MethodSignature::from_jni(ts);
+            assert_eq!(m.to_jni(), ts);
+            assert_eq!(m.descriptor(), descriptor, "{ts}");
+        }
+    }
 }
@@ -1,22 +1,45 @@
 use winnow::{
     ModalParser, Parser,
-    combinator::{delimited, repeat},
-    error::InputError,
+    combinator::{alt, delimited, repeat},
     token::one_of,
 };
 
-use crate::signature::type_signature::{TypeSignature, parse_typesignature};
+use crate::{
+    ParseFailure, ParseOptions,
+    prelude::*,
+    signature::type_signature::{TypeSignature, nested_typesignature, type_parameter},
+};
 
 pub mod method_signature;
 pub mod type_signature;
 
-pub fn parse_type_parameters<'a>()
--> impl ModalParser<&'a str, Vec<TypeSignature<'a>>, InputError<&'a str>> {
+pub fn parse_type_parameters<'a>() -> impl ModalParser<&'a str, Vec<TypeSignature<'a>>, ParseFailure>
+{
+    parse_type_parameters_with(ParseOptions::default())
+}
+
+/// Parses type parameters or arguments with the nesting limit of `options`
+pub fn parse_type_parameters_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, Vec<TypeSignature<'a>>, ParseFailure> {
+    type_parameters(options, 0)
+}
+
+/// Type parameters or arguments whose types are `depth` levels into another type
+pub(crate) fn type_parameters<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, Vec<TypeSignature<'a>>, ParseFailure> {
     delimited(
         one_of('<'),
-        repeat(0.., |input: &mut &'a str| {
-            parse_typesignature().parse_next(input)
-        }),
+        repeat(
+            0..,
+            alt((
+                // `T:Ljava/lang/Object;` declares `T` rather than being the type variable `T`
+                type_parameter(options, depth).map(|t| TypeSignature::TypeParameter(Box::new(t))),
+                move |input: &mut &'a str| nested_typesignature(options, depth).parse_next(input),
+            )),
+        ),
         one_of('>'),
     )
 }
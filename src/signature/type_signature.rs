@@ -1,16 +1,19 @@
-use std::{borrow::Cow, fmt};
+use alloc::borrow::Cow;
+use core::fmt;
 
 use serde::{Deserialize, Serialize};
 use winnow::{
     ModalParser, Parser,
-    combinator::{alt, delimited, preceded, terminated},
-    error::InputError,
+    combinator::{alt, delimited, terminated},
     token::{one_of, take_while},
 };
 
 use crate::{
-    object_identifier::{ObjectIdentifier, parse_object_identifier},
-    signature::parse_type_parameters,
+    MAX_ARRAY_DIMENSIONS, ParseFailure, ParseOptions, SmaliError, enter_nested, fail,
+    object_identifier::{ObjectIdentifier, object_identifier},
+    parse_complete,
+    prelude::*,
+    signature::type_parameters,
     ws,
 };
 
@@ -52,17 +55,140 @@ impl fmt::Display for TypeSignature<'_> {
         // stream: `f`. Returns `fmt::Result` which indicates whether the
         // operation succeeded or failed. Note that `write!` uses syntax which
         // is very similar to `println!`.
-        write!(f, "{}", self.to_jni())
+        write!(f, "{}", self.descriptor())
     }
 }
 
-impl TypeSignature<'_> {
-    pub fn from_jni(mut s: &str) -> TypeSignature {
+impl<'a> TypeSignature<'a> {
+    /// Copies any borrowed text, detaching the type from the parsed source
+    pub fn into_owned(self) -> TypeSignature<'static> {
+        match self {
+            TypeSignature::Array(a) => TypeSignature::Array(Box::new(a.into_owned())),
+            TypeSignature::Object(o) => TypeSignature::Object(Box::new(o.into_owned())),
+            TypeSignature::Int => TypeSignature::Int,
+            TypeSignature::Bool => TypeSignature::Bool,
+            TypeSignature::Byte => TypeSignature::Byte,
+            TypeSignature::Char => TypeSignature::Char,
+            TypeSignature::Short => TypeSignature::Short,
+            TypeSignature::Long => TypeSignature::Long,
+            TypeSignature::Float => TypeSignature::Float,
+            TypeSignature::Double => TypeSignature::Double,
+            TypeSignature::Void => TypeSignature::Void,
+            TypeSignature::TypeParameters(params, rest) => TypeSignature::TypeParameters(
+                params.into_iter().map(TypeSignature::into_owned).collect(),
+                Box::new(rest.into_owned()),
+            ),
+            TypeSignature::TypeParameter(t) => {
+                TypeSignature::TypeParameter(Box::new(t.into_owned()))
+            }
+            TypeSignature::TypeVariableSignature(name) => {
+                TypeSignature::TypeVariableSignature(Cow::Owned(name.into_owned()))
+            }
+            TypeSignature::WildcardPlus => TypeSignature::WildcardPlus,
+            TypeSignature::WildcardMinus => TypeSignature::WildcardMinus,
+            TypeSignature::WildcardStar => TypeSignature::WildcardStar,
+        }
+    }
+
+    /// Parses the type signature `s` starts with.
+    ///
+    /// # Panics
+    ///
+    /// If `s` doesn't start with a type signature. Use [`try_from_jni`](Self::try_from_jni)
+    /// for input that isn't known to be valid.
+    pub fn from_jni(mut s: &str) -> TypeSignature<'_> {
         parse_typesignature()
             .parse_next(&mut s)
             .unwrap_or_else(|_| panic!("Could not parse TypeSignature: {s}"))
     }
 
+    /// Parses a type signature such as `Ljava/util/List<TT;>;`, failing unless all
+    /// of `s` is one
+    pub fn try_from_jni(s: &str) -> Result<TypeSignature<'_>, SmaliError> {
+        parse_complete(s, "type signature", parse_typesignature())
+    }
+
+    /// Parses a dex type descriptor such as `I` or `[Ljava/lang/String;`, the same as
+    /// [`try_from_jni`](Self::try_from_jni)
+    pub fn parse_descriptor(s: &str) -> Result<TypeSignature<'_>, SmaliError> {
+        Self::try_from_jni(s)
+    }
+
+    /// Parses a type written in Java source syntax, e.g. `int[][]` or
+    /// `java.util.Map.Entry<String, ? extends Number>`.
+    ///
+    /// Names without a package are taken to be in `java.lang`, and segments after the
+    /// first capitalised one are nested classes (`Map.Entry` is `Map$Entry`).
+    pub fn from_java(s: &str) -> Result<TypeSignature<'static>, SmaliError> {
+        let s = s.trim();
+        if let Some(elem) = s.strip_suffix("[]") {
+            return Ok(TypeSignature::Array(Box::new(Self::from_java(elem)?)));
+        }
+        let primitive = match s {
+            "boolean" => Some(TypeSignature::Bool),
+            "byte" => Some(TypeSignature::Byte),
+            "char" => Some(TypeSignature::Char),
+            "short" => Some(TypeSignature::Short),
+            "int" => Some(TypeSignature::Int),
+            "long" => Some(TypeSignature::Long),
+            "float" => Some(TypeSignature::Float),
+            "double" => Some(TypeSignature::Double),
+            "void" => Some(TypeSignature::Void),
+            _ => None,
+        };
+        if let Some(p) = primitive {
+            return Ok(p);
+        }
+
+        let (name, args) = match s.split_once('<') {
+            Some((name, rest)) => {
+                let inner = rest
+                    .strip_suffix('>')
+                    .ok_or_else(|| SmaliError::new(&format!("Unbalanced type arguments in {s}")))?;
+                let mut args = vec![];
+                for arg in split_type_arguments(inner)? {
+                    args.extend(java_type_argument(arg)?);
+                }
+                (name.trim(), Some(args))
+            }
+            None => (s, None),
+        };
+        let valid = |c: char| c.is_alphanumeric() || c == '_' || c == '$' || c == '.';
+        if name.is_empty() || !name.chars().all(valid) || name.split('.').any(str::is_empty) {
+            return Err(SmaliError::new(&format!("Invalid Java type: {s}")));
+        }
+
+        let segments: Vec<&str> = name.split('.').collect();
+        let class_name = if segments.len() == 1 {
+            format!("java/lang/{name}")
+        } else {
+            let outer = segments
+                .iter()
+                .position(|seg| seg.starts_with(char::is_uppercase))
+                .unwrap_or(segments.len() - 1);
+            format!(
+                "{}{}{}",
+                segments[..outer].join("/"),
+                if outer > 0 { "/" } else { "" },
+                segments[outer..].join("$")
+            )
+        };
+        Ok(TypeSignature::Object(Box::new(ObjectIdentifier {
+            class_name: Cow::Owned(class_name),
+            type_arguments: args,
+            suffix: None,
+        })))
+    }
+
+    /// The dex type descriptor as smali writes it, e.g. `[Ljava/lang/String;`. The
+    /// same as [`to_jni`](Self::to_jni), so unlike [`MethodSignature::descriptor`] it
+    /// keeps type arguments and variables; [`erased`](Self::erased) drops them first.
+    ///
+    /// [`MethodSignature::descriptor`]: crate::types::MethodSignature::descriptor
+    pub fn descriptor(&self) -> String {
+        self.to_jni()
+    }
+
     pub fn to_jni(&self) -> String {
         match self {
             TypeSignature::Array(a) => "[".to_string() + &a.to_jni(),
@@ -95,6 +221,103 @@ impl TypeSignature<'_> {
         }
     }
 
+    /// The type with its generics erased as javac does: type arguments are dropped and
+    /// type variables become `Ljava/lang/Object;`. Use [`MethodSignature::descriptor`]
+    /// to also resolve the type variables a method declares to their bounds.
+    ///
+    /// [`MethodSignature::descriptor`]: crate::types::MethodSignature::descriptor
+    pub fn erased(&self) -> TypeSignature<'a> {
+        self.erased_with(&[])
+    }
+
+    /// Like [`TypeSignature::erased`], resolving the type variables declared in
+    /// `type_parameters` to the erasure of their first bound
+    pub(crate) fn erased_with(&self, type_parameters: &[TypeSignature<'a>]) -> TypeSignature<'a> {
+        match self {
+            TypeSignature::Array(a) => {
+                TypeSignature::Array(Box::new(a.erased_with(type_parameters)))
+            }
+            TypeSignature::Object(o) => TypeSignature::Object(Box::new(o.erased())),
+            TypeSignature::TypeParameters(_, rest) => rest.erased_with(type_parameters),
+            TypeSignature::TypeVariableSignature(name) => {
+                let declared = type_parameters.iter().find_map(|p| match p {
+                    TypeSignature::TypeParameter(t) if t.ident == *name => Some(&t.ts),
+                    _ => None,
+                });
+                match declared {
+                    // `T::Ljava/lang/Runnable;` has no class bound, its first bound is the interface
+                    Some(TypeSignature::TypeParameter(interface)) if interface.ident.is_empty() => {
+                        interface.ts.erased_with(type_parameters)
+                    }
+                    Some(bound) => {
+                        // drop the variable itself so a malformed `<T:TT;>` can't recurse forever
+                        let rest: Vec<_> = type_parameters
+                            .iter()
+                            .filter(|p| !matches!(p, TypeSignature::TypeParameter(t) if t.ident == *name))
+                            .cloned()
+                            .collect();
+                        bound.erased_with(&rest)
+                    }
+                    None => TypeSignature::Object(Box::new(ObjectIdentifier::from_java_type(
+                        "java.lang.Object",
+                    ))),
+                }
+            }
+            t => t.clone(),
+        }
+    }
+
+    /// Every class type this type mentions, including array elements and type arguments
+    pub fn object_types(&self) -> Vec<&ObjectIdentifier<'a>> {
+        let mut out = vec![];
+        self.collect_object_types(&mut out);
+        out
+    }
+
+    fn collect_object_types<'s>(&'s self, out: &mut Vec<&'s ObjectIdentifier<'a>>) {
+        match self {
+            TypeSignature::Array(a) => a.collect_object_types(out),
+            TypeSignature::Object(o) => {
+                out.push(o);
+                for t in o.type_arguments.iter().flatten() {
+                    t.collect_object_types(out);
+                }
+            }
+            TypeSignature::TypeParameters(params, rest) => {
+                for p in params {
+                    p.collect_object_types(out);
+                }
+                rest.collect_object_types(out);
+            }
+            TypeSignature::TypeParameter(t) => t.ts.collect_object_types(out),
+            _ => {}
+        }
+    }
+
+    /// Whether a value of this type occupies a register pair (long and double)
+    pub fn is_wide(&self) -> bool {
+        matches!(self, TypeSignature::Long | TypeSignature::Double)
+    }
+
+    /// The element type of an array, one level down: `[[I` gives `[I`
+    pub fn component_type(&self) -> Option<&TypeSignature<'a>> {
+        match self {
+            TypeSignature::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// How deeply arrays are nested: 2 for `[[I`, 0 for anything that isn't an array
+    pub fn array_dimensions(&self) -> usize {
+        let mut t = self;
+        let mut n = 0;
+        while let Some(c) = t.component_type() {
+            t = c;
+            n += 1;
+        }
+        n
+    }
+
     pub fn to_java(&self) -> String {
         match self {
             TypeSignature::Array(a) => format!("{}[]", a.to_java()),
@@ -113,14 +336,88 @@ impl TypeSignature<'_> {
     }
 }
 
+/// Splits the inside of `<...>` on the commas that aren't nested in further type arguments
+fn split_type_arguments(s: &str) -> Result<Vec<&str>, SmaliError> {
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut args = vec![];
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| SmaliError::new(&format!("Unbalanced type arguments in {s}")))?
+            }
+            ',' if depth == 0 => {
+                args.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(SmaliError::new(&format!(
+            "Unbalanced type arguments in {s}"
+        )));
+    }
+    args.push(&s[start..]);
+    Ok(args)
+}
+
+/// A Java type argument, with wildcards becoming their JNI marker followed by the bound
+fn java_type_argument(arg: &str) -> Result<Vec<TypeSignature<'static>>, SmaliError> {
+    let arg = arg.trim();
+    if arg == "?" {
+        return Ok(vec![TypeSignature::WildcardStar]);
+    }
+    if let Some(bound) = arg.strip_prefix("? extends ") {
+        return Ok(vec![
+            TypeSignature::WildcardPlus,
+            TypeSignature::from_java(bound)?,
+        ]);
+    }
+    if let Some(bound) = arg.strip_prefix("? super ") {
+        return Ok(vec![
+            TypeSignature::WildcardMinus,
+            TypeSignature::from_java(bound)?,
+        ]);
+    }
+    Ok(vec![TypeSignature::from_java(arg)?])
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
 pub struct TypeParameter<'a> {
     pub ident: Cow<'a, str>,
     pub ts: TypeSignature<'a>,
 }
 
-pub fn parse_type_parameter<'a>()
--> impl ModalParser<&'a str, TypeParameter<'a>, InputError<&'a str>> {
+impl TypeParameter<'_> {
+    /// Copies any borrowed text, detaching the parameter from the parsed source
+    pub fn into_owned(self) -> TypeParameter<'static> {
+        TypeParameter {
+            ident: Cow::Owned(self.ident.into_owned()),
+            ts: self.ts.into_owned(),
+        }
+    }
+}
+
+pub fn parse_type_parameter<'a>() -> impl ModalParser<&'a str, TypeParameter<'a>, ParseFailure> {
+    parse_type_parameter_with(ParseOptions::default())
+}
+
+/// Parses a type parameter with the nesting limit of `options`
+pub fn parse_type_parameter_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, TypeParameter<'a>, ParseFailure> {
+    type_parameter(options, 0)
+}
+
+/// A type parameter whose type is `depth` levels into another type
+pub(crate) fn type_parameter<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, TypeParameter<'a>, ParseFailure> {
     (
         terminated(
             take_while(0.., |c: char| {
@@ -128,10 +425,7 @@ pub fn parse_type_parameter<'a>()
             }),
             one_of(':'),
         ),
-        |input: &mut &'a str| {
-            //println!("test2");
-            parse_typesignature().parse_next(input)
-        },
+        move |input: &mut &'a str| nested_typesignature(options, depth).parse_next(input),
     )
         .map(|(ident, ts)| TypeParameter {
             ident: ident.into(),
@@ -140,8 +434,33 @@ pub fn parse_type_parameter<'a>()
 }
 
 // Its needed to be recursive, sadly ;(
-pub fn parse_typesignature<'a>() -> impl ModalParser<&'a str, TypeSignature<'a>, InputError<&'a str>>
-{
+pub fn parse_typesignature<'a>() -> impl ModalParser<&'a str, TypeSignature<'a>, ParseFailure> {
+    parse_typesignature_with(ParseOptions::default())
+}
+
+/// Parses a type signature with the nesting limit of `options`
+pub fn parse_typesignature_with<'a>(
+    options: ParseOptions,
+) -> impl ModalParser<&'a str, TypeSignature<'a>, ParseFailure> {
+    nested_typesignature(options, 0)
+}
+
+/// A type signature `depth` levels into another, which fails once that is deeper than
+/// [`ParseOptions::max_depth`]
+pub(crate) fn nested_typesignature<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, TypeSignature<'a>, ParseFailure> {
+    move |input: &mut &'a str| {
+        enter_nested(options, depth)?;
+        typesignature(options, depth).parse_next(input)
+    }
+}
+
+fn typesignature<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, TypeSignature<'a>, ParseFailure> {
     ws(alt((
         alt((
             one_of('Z').value(TypeSignature::Bool),
@@ -157,21 +476,43 @@ pub fn parse_typesignature<'a>() -> impl ModalParser<&'a str, TypeSignature<'a>,
             one_of('+').value(TypeSignature::WildcardPlus),
             one_of('-').value(TypeSignature::WildcardMinus),
         )),
-        (parse_type_parameters(), |input: &mut &'a str| {
-            parse_typesignature().parse_next(input)
-        })
+        (
+            type_parameters(options, depth + 1),
+            move |input: &mut &'a str| nested_typesignature(options, depth + 1).parse_next(input),
+        )
             .map(|(ts, ts_rest)| TypeSignature::TypeParameters(ts, Box::new(ts_rest))),
-        parse_object_identifier().map(|o| TypeSignature::Object(Box::new(o))),
+        object_identifier(options, depth + 1).map(|o| TypeSignature::Object(Box::new(o))),
         delimited(one_of('T'), take_while(0.., |x| x != ';'), one_of(';'))
             .map(|name: &str| TypeSignature::TypeVariableSignature(Cow::Borrowed(name))),
-        preceded(one_of('['), |input: &mut &'a str| {
-            parse_typesignature().parse_next(input)
-        })
-        .map(|arr| TypeSignature::Array(Box::new(arr))),
-        parse_type_parameter().map(|t| TypeSignature::TypeParameter(Box::new(t))),
+        parse_array(options, depth + 1),
+        type_parameter(options, depth + 1).map(|t| TypeSignature::TypeParameter(Box::new(t))),
     )))
 }
 
+/// Parses an array type, taking all its `[`s at once so the dimensions are checked
+/// against [`MAX_ARRAY_DIMENSIONS`] rather than recursing once per dimension. The
+/// element type is `depth` levels deep.
+fn parse_array<'a>(
+    options: ParseOptions,
+    depth: usize,
+) -> impl ModalParser<&'a str, TypeSignature<'a>, ParseFailure> {
+    move |input: &mut &'a str| {
+        let start = *input;
+        let dims = take_while(1.., '[').parse_next(input)?.len();
+        if dims > MAX_ARRAY_DIMENSIONS {
+            *input = start;
+            return Err(fail(format!(
+                "array of {dims} dimensions, more than {MAX_ARRAY_DIMENSIONS}"
+            )));
+        }
+        let mut ts = nested_typesignature(options, depth).parse_next(input)?;
+        for _ in 0..dims {
+            ts = TypeSignature::Array(Box::new(ts));
+        }
+        Ok(ts)
+    }
+}
+
 mod tests {
 
     #[allow(unused_imports)]
@@ -182,13 +523,13 @@ mod tests {
         use super::*;
         use winnow::Parser;
         let t = parse_typesignature().parse_next(&mut "[B").unwrap();
-        println!("{t:?}");
+        assert_eq!(t, TypeSignature::Array(Box::new(TypeSignature::Byte)));
         let t = parse_typesignature().parse_next(&mut "V").unwrap();
-        println!("{t:?}");
+        assert_eq!(t, TypeSignature::Void);
         let t = parse_typesignature()
             .parse_next(&mut "Lcom/none/Class;")
             .unwrap();
-        println!("{t:?}");
+        assert_eq!(t.to_jni(), "Lcom/none/Class;");
     }
 
     #[test]
@@ -202,7 +543,6 @@ mod tests {
     fn test_signature2() {
         let ts = "Lorg/jf/dexlib2/writer/DexWriter<Lorg/jf/dexlib2/writer/builder/BuilderStringReference;Lorg/jf/dexlib2/writer/builder/BuilderStringReference;Lorg/jf/dexlib2/writer/builder/BuilderTypeReference;Lorg/jf/dexlib2/writer/builder/BuilderTypeReference;Lorg/jf/dexlib2/writer/builder/BuilderMethodProtoReference;Lorg/jf/dexlib2/writer/builder/BuilderFieldReference;Lorg/jf/dexlib2/writer/builder/BuilderMethodReference;Lorg/jf/dexlib2/writer/builder/BuilderClassDef;Lorg/jf/dexlib2/writer/builder/BuilderCallSiteReference;Lorg/jf/dexlib2/writer/builder/BuilderMethodHandleReference;Lorg/jf/dexlib2/writer/builder/BuilderAnnotation;Lorg/jf/dexlib2/writer/builder/BuilderAnnotationSet;Lorg/jf/dexlib2/writer/builder/BuilderTypeList;Lorg/jf/dexlib2/writer/builder/BuilderField;Lorg/jf/dexlib2/writer/builder/BuilderMethod;Lorg/jf/dexlib2/writer/builder/BuilderEncodedValues$BuilderArrayEncodedValue;Lorg/jf/dexlib2/writer/builder/BuilderEncodedValues$BuilderEncodedValue;Lorg/jf/dexlib2/writer/builder/BuilderAnnotationElement;Lorg/jf/dexlib2/writer/builder/BuilderStringPool;Lorg/jf/dexlib2/writer/builder/BuilderTypePool;Lorg/jf/dexlib2/writer/builder/BuilderProtoPool;Lorg/jf/dexlib2/writer/builder/BuilderFieldPool;Lorg/jf/dexlib2/writer/builder/BuilderMethodPool;Lorg/jf/dexlib2/writer/builder/BuilderClassPool;Lorg/jf/dexlib2/writer/builder/BuilderCallSitePool;Lorg/jf/dexlib2/writer/builder/BuilderMethodHandlePool;Lorg/jf/dexlib2/writer/builder/BuilderTypeListPool;Lorg/jf/dexlib2/writer/builder/BuilderAnnotationPool;Lorg/jf/dexlib2/writer/builder/BuilderAnnotationSetPool;Lorg/jf/dexlib2/writer/builder/BuilderEncodedArrayPool;>.SectionProvider;";
         let o = TypeSignature::from_jni(ts);
-        println!("{o:?}");
         assert_eq!(o.to_jni(), ts);
     }
 
@@ -210,10 +550,40 @@ mod tests {
     fn test_signature3() {
         let ts = "<TSource:Ljava/lang/Object;TAccumulate:Ljava/lang/Object;TResult:Ljava/lang/Object;>Ljava/lang/Object;";
         let o = TypeSignature::from_jni(ts);
-        println!("{o:?}");
         assert_eq!(o.to_jni(), ts);
     }
 
+    #[test]
+    fn test_descriptor() {
+        for ts in ["I", "[[J", "Ljava/lang/String;", "[Ljava/util/List<TT;>;"] {
+            let o = TypeSignature::parse_descriptor(ts).unwrap();
+            assert_eq!(o, TypeSignature::from_jni(ts));
+            assert_eq!(o.descriptor(), ts);
+        }
+        // generics are kept, unlike in a method descriptor
+        let list = TypeSignature::parse_descriptor("Ljava/util/List<TT;>;").unwrap();
+        assert_eq!(list.descriptor(), "Ljava/util/List<TT;>;");
+        assert_eq!(list.erased().descriptor(), "Ljava/util/List;");
+
+        for bad in ["", "Q", "I;", "[", "Ljava/lang/String"] {
+            assert!(TypeSignature::parse_descriptor(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_try_from_jni() {
+        let ts = "Ljava/util/Map<TK;Ljava/util/List<TV;>;>;";
+        let t = TypeSignature::try_from_jni(ts).unwrap();
+        assert_eq!(t, TypeSignature::from_jni(ts));
+        let e = TypeSignature::try_from_jni("Q").unwrap_err();
+        assert!(
+            e.details
+                .starts_with("failed to parse type signature at line 1")
+        );
+        assert!(TypeSignature::try_from_jni("Ljava/util/List<").is_err());
+        assert!(TypeSignature::try_from_jni("II").is_err());
+    }
+
     #[test]
     fn test_signature4() {
         use super::*;
@@ -221,6 +591,187 @@ mod tests {
 
         let mut ts = "CONSTANT_FIELD:I";
         let o = parse_type_parameter().parse_next(&mut ts).unwrap();
-        println!("{o:?}");
+        assert_eq!(o.ident, "CONSTANT_FIELD");
+        assert_eq!(o.ts, TypeSignature::Int);
+    }
+
+    #[test]
+    fn test_array_signatures() {
+        use super::*;
+        use winnow::Parser;
+
+        let ts = "[Ljava/util/List<Ljava/lang/String;>;";
+        let mut input = ts;
+        let o = parse_typesignature().parse_next(&mut input).unwrap();
+        assert!(input.is_empty());
+        let TypeSignature::Array(elem) = &o else {
+            panic!("expected an array, got {o:?}");
+        };
+        let TypeSignature::Object(elem) = elem.as_ref() else {
+            panic!("expected an object element, got {elem:?}");
+        };
+        assert_eq!(elem.as_jni_type(), "Ljava/util/List<Ljava/lang/String;>;");
+        assert_eq!(o.to_jni(), ts);
+
+        let ts = "[[I";
+        let o = TypeSignature::from_jni(ts);
+        assert_eq!(
+            o,
+            TypeSignature::Array(Box::new(TypeSignature::Array(Box::new(TypeSignature::Int))))
+        );
+        assert_eq!(o.to_jni(), ts);
+        assert_eq!(o.to_java(), "int[][]");
+
+        let ts = "[[Ljava/util/Map<Ljava/lang/String;[Ljava/util/List<*>;>;";
+        assert_eq!(TypeSignature::from_jni(ts).to_jni(), ts);
+    }
+
+    #[test]
+    fn test_from_java() {
+        assert_eq!(
+            TypeSignature::from_java("boolean").unwrap(),
+            TypeSignature::Bool
+        );
+        assert_eq!(TypeSignature::from_java("int[][]").unwrap().to_jni(), "[[I");
+        assert_eq!(
+            TypeSignature::from_java("java.lang.String[]")
+                .unwrap()
+                .to_jni(),
+            "[Ljava/lang/String;"
+        );
+        assert_eq!(
+            TypeSignature::from_java("java.util.List<String>")
+                .unwrap()
+                .to_jni(),
+            "Ljava/util/List<Ljava/lang/String;>;"
+        );
+        assert_eq!(
+            TypeSignature::from_java("java.util.Map.Entry<String, ? extends Number>[]")
+                .unwrap()
+                .to_jni(),
+            "[Ljava/util/Map$Entry<Ljava/lang/String;+Ljava/lang/Number;>;"
+        );
+        assert_eq!(
+            TypeSignature::from_java("java.util.List<?>")
+                .unwrap()
+                .to_jni(),
+            "Ljava/util/List<*>;"
+        );
+
+        let ts = TypeSignature::from_java("long[]").unwrap();
+        assert_eq!(ts.to_java(), "long[]");
+
+        assert!(TypeSignature::from_java("").is_err());
+        assert!(TypeSignature::from_java("java.util.List<String").is_err());
+        assert!(TypeSignature::from_java("java..String").is_err());
+    }
+
+    #[test]
+    fn test_component_type() {
+        use super::*;
+        use winnow::Parser;
+
+        let t = parse_typesignature().parse("[[Ljava/lang/String;").unwrap();
+        assert_eq!(t.array_dimensions(), 2);
+        let inner = t.component_type().unwrap();
+        assert_eq!(inner.to_jni(), "[Ljava/lang/String;");
+        let element = inner.component_type().unwrap();
+        assert!(matches!(element, TypeSignature::Object(o) if o.class_name == "java/lang/String"));
+        assert_eq!(element.array_dimensions(), 0);
+        assert!(element.component_type().is_none());
+        assert!(TypeSignature::Int.component_type().is_none());
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        use super::*;
+        use crate::{
+            MAX_ARRAY_DIMENSIONS, ParseOptions,
+            class::{parse_class_complete, parse_class_with},
+        };
+        use winnow::Parser;
+
+        let array = |dims: usize| format!("{}I", "[".repeat(dims));
+        let (deepest, too_deep, huge) = (
+            array(MAX_ARRAY_DIMENSIONS),
+            array(MAX_ARRAY_DIMENSIONS + 1),
+            array(1_000_000),
+        );
+        let t = parse_typesignature().parse(deepest.as_str()).unwrap();
+        assert_eq!(t.array_dimensions(), MAX_ARRAY_DIMENSIONS);
+        assert!(parse_typesignature().parse(too_deep.as_str()).is_err());
+        assert!(parse_typesignature().parse(huge.as_str()).is_err());
+
+        // the widest array is still legal inside a method signature and generic type
+        let smali = format!(
+            ".class La;\n.super Ljava/lang/Object;\n.field a:Ljava/util/List<{deepest}>;\n.method public abstract m({deepest}){deepest}\n.end method\n"
+        );
+        assert!(parse_class_complete(&smali).is_ok());
+
+        let smali = format!(
+            ".class La;\n.super Ljava/lang/Object;\n.field b:{}\n",
+            array(100_000)
+        );
+        let e = parse_class_complete(&smali).unwrap_err();
+        let reason = "array of 100000 dimensions, more than 255 at line 3";
+        assert!(e.details.starts_with(reason), "{}", e.details);
+
+        // the limit applies to generic nesting, which arrays don't add to
+        let options = ParseOptions {
+            max_depth: 4,
+            ..Default::default()
+        };
+        assert!(parse_typesignature_with(options).parse("[[[[[[I").is_ok());
+        let list = |depth: usize| {
+            format!(
+                "{}Ljava/lang/String;{}",
+                "Ljava/util/List<".repeat(depth),
+                ">;".repeat(depth)
+            )
+        };
+        let (deepest, too_deep) = (list(3), list(4));
+        let typesignature = || parse_typesignature_with(options);
+        assert!(typesignature().parse(deepest.as_str()).is_ok());
+        assert!(typesignature().parse(too_deep.as_str()).is_err());
+        let smali = format!(
+            ".class La;\n.super Ljava/lang/Object;\n.field b:{}\n",
+            list(4)
+        );
+        let e = parse_class_with(options).parse(smali.as_str()).unwrap_err();
+        assert_eq!(e.inner().reason.as_deref(), Some("nesting deeper than 4"));
+        // each parse counts its own depth, a failed one leaves nothing behind
+        assert!(typesignature().parse(deepest.as_str()).is_ok());
+        // and the limit is only that of the parse given it
+        assert!(parse_typesignature().parse(too_deep.as_str()).is_ok());
+    }
+
+    #[test]
+    fn test_suffix() {
+        use super::*;
+        use crate::object_identifier::ObjectIdentifier;
+
+        let ts = "Lorg/jf/dexlib2/writer/DexWriter<Lorg/jf/dexlib2/writer/builder/BuilderStringReference;Lorg/jf/dexlib2/writer/builder/BuilderTypeReference;>.SectionProvider;";
+        let t = TypeSignature::from_jni(ts);
+        assert_eq!(t.to_jni(), ts);
+        let TypeSignature::Object(o) = &t else {
+            panic!("{t:?}");
+        };
+        assert_eq!(o.suffix.as_deref(), Some("SectionProvider"));
+        assert_eq!(
+            o.as_java_type(),
+            "org.jf.dexlib2.writer.DexWriter.SectionProvider"
+        );
+        assert_eq!(
+            t.to_java(),
+            "org.jf.dexlib2.writer.DexWriter.SectionProvider"
+        );
+
+        let o = ObjectIdentifier::from_java_type("com.example.Outer").with_suffix("Inner");
+        assert_eq!(o.as_jni_type(), "Lcom/example/Outer.Inner;");
+        assert_eq!(o.as_java_type(), "com.example.Outer.Inner");
+        assert_eq!(
+            ObjectIdentifier::from_java_type("com.example.Outer").as_java_type(),
+            "com.example.Outer"
+        );
     }
 }